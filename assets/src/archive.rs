@@ -0,0 +1,543 @@
+//! A single-file archive format for shipping assets
+//!
+//! An archive starts with a header, followed by the data of each entry, followed by an index:
+//!
+//! ```text
+//! header: magic (b"HSAR") | version: u32 | index offset: u64
+//! data:   the stored bytes of each entry, back to back
+//! index:  entry count: u32 | entries
+//! entry:  path length: u16 | path | compression: u8 | offset: u64 | stored size: u64 | size: u64
+//!         | checksum: u32
+//! ```
+//!
+//! All integers are little-endian. Paths are UTF-8, relative to the assets directory, and
+//! separated by `/`. The checksum is the CRC-32 of the uncompressed data, and is verified whenever
+//! an entry is read.
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::{self, Crc};
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+/// The file extension used for archives
+pub const EXTENSION: &str = "hsa";
+
+/// The version of the archive format written by this module
+pub const VERSION: u32 = 1;
+
+const MAGIC: &[u8; 4] = b"HSAR";
+const HEADER_SIZE: u64 = 16;
+/// The offset of the index offset in the header
+const INDEX_OFFSET_POSITION: u64 = 8;
+
+/// How the data of an entry is stored
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Deflate,
+}
+
+impl Compression {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Deflate),
+            _ => None,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Deflate => 1,
+        }
+    }
+}
+
+/// The index entry of a file in an archive
+#[derive(Clone, Debug)]
+struct Entry {
+    compression: Compression,
+    offset: u64,
+    stored_size: u64,
+    size: u64,
+    checksum: u32,
+}
+
+quick_error! {
+    /// An error while reading or writing an archive
+    #[derive(Debug)]
+    pub enum ArchiveError {
+        Io(err: io::Error) {
+            display("IO error: {}", err)
+            from()
+        }
+        InvalidMagic {
+            display("Not an asset archive")
+        }
+        UnsupportedVersion(version: u32) {
+            display("Unsupported archive version {} (expected {})", version, VERSION)
+        }
+        InvalidIndex(reason: String) {
+            display("Invalid archive index: {}", reason)
+        }
+        InvalidPath(path: PathBuf) {
+            display("Path `{}` can't be stored in an archive", path.display())
+        }
+    }
+}
+
+trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+/// An archive opened for reading
+pub struct Archive {
+    reader: Mutex<Box<ReadSeek>>,
+    entries: HashMap<String, Entry>,
+}
+
+impl Archive {
+    /// Opens the archive at the provided path, and reads its index
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ArchiveError> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads the index of an archive from the provided reader
+    pub fn from_reader<R>(mut reader: R) -> Result<Self, ArchiveError>
+    where
+        R: Read + Seek + Send + 'static,
+    {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(ArchiveError::InvalidMagic);
+        }
+
+        let version = read_u32(&mut reader)?;
+        if version != VERSION {
+            return Err(ArchiveError::UnsupportedVersion(version));
+        }
+
+        let index_offset = read_u64(&mut reader)?;
+        reader.seek(SeekFrom::Start(index_offset))?;
+
+        let count = read_u32(&mut reader)?;
+        // The count isn't used to reserve memory, since a corrupted index could make it huge
+        let mut entries = HashMap::new();
+
+        for _ in 0..count {
+            let mut path = vec![0; read_u16(&mut reader)? as usize];
+            reader.read_exact(&mut path)?;
+            let path = String::from_utf8(path)
+                .map_err(|_| ArchiveError::InvalidIndex("Path is not valid UTF-8".to_string()))?;
+
+            let compression = Compression::from_u8(read_u8(&mut reader)?).ok_or_else(|| {
+                ArchiveError::InvalidIndex(format!("Unknown compression for `{}`", path))
+            })?;
+
+            let entry = Entry {
+                compression,
+                offset: read_u64(&mut reader)?,
+                stored_size: read_u64(&mut reader)?,
+                size: read_u64(&mut reader)?,
+                checksum: read_u32(&mut reader)?,
+            };
+
+            let end = entry.offset.checked_add(entry.stored_size);
+
+            if entry.offset < HEADER_SIZE || end.map_or(true, |end| end > index_offset) {
+                return Err(ArchiveError::InvalidIndex(format!(
+                    "Data of `{}` is out of bounds",
+                    path
+                )));
+            }
+
+            entries.insert(path, entry);
+        }
+
+        Ok(Self {
+            reader: Mutex::new(Box::new(reader)),
+            entries,
+        })
+    }
+
+    /// Returns whether the archive contains a file at the provided relative path
+    pub fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
+        entry_name(path.as_ref())
+            .map(|name| self.entries.contains_key(&name))
+            .unwrap_or(false)
+    }
+
+    /// Returns the relative paths of all files in the archive
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.entries.keys().map(PathBuf::from).collect()
+    }
+
+    /// Returns the bytes of the file at the provided relative path
+    ///
+    /// An error with the kind `InvalidData` is returned if the file is corrupted.
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> io::Result<Vec<u8>> {
+        let path = path.as_ref();
+        let entry = entry_name(path)
+            .and_then(|name| self.entries.get(&name))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("`{}` is not in the archive", path.display()),
+                )
+            })?;
+
+        let corrupted = |what: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} mismatch for `{}`", what, path.display()),
+            )
+        };
+
+        // The sizes in the index are only used to limit how much is read, not to reserve memory,
+        // since a corrupted index could make them huge
+        let mut stored = Vec::new();
+        {
+            let mut reader = self.reader.lock().unwrap();
+            reader.seek(SeekFrom::Start(entry.offset))?;
+            reader
+                .by_ref()
+                .take(entry.stored_size)
+                .read_to_end(&mut stored)?;
+        }
+
+        if stored.len() as u64 != entry.stored_size {
+            return Err(corrupted("Size"));
+        }
+
+        let data = match entry.compression {
+            Compression::None => stored,
+            Compression::Deflate => {
+                let mut data = Vec::new();
+                DeflateDecoder::new(stored.as_slice())
+                    .take(entry.size)
+                    .read_to_end(&mut data)?;
+                data
+            }
+        };
+
+        if data.len() as u64 != entry.size {
+            return Err(corrupted("Size"));
+        }
+
+        if checksum(&data) != entry.checksum {
+            return Err(corrupted("Checksum"));
+        }
+
+        Ok(data)
+    }
+}
+
+/// Writes an archive containing the provided files, given as pairs of relative paths and data
+///
+/// If `compression` is `Deflate`, each file is compressed unless that would make it larger (as is
+/// the case for most images).
+pub fn write_archive<W: Write + Seek>(
+    writer: &mut W,
+    files: &[(PathBuf, Vec<u8>)],
+    compression: Compression,
+) -> Result<(), ArchiveError> {
+    writer.write_all(MAGIC)?;
+    write_u32(writer, VERSION)?;
+    // The index offset is written once the data has been written
+    write_u64(writer, 0)?;
+
+    let mut index = Vec::with_capacity(files.len());
+    let mut offset = HEADER_SIZE;
+
+    for (path, data) in files {
+        let name = entry_name(path)
+            .filter(|name| name.len() <= u16::max_value() as usize)
+            .ok_or_else(|| ArchiveError::InvalidPath(path.clone()))?;
+
+        let compressed = match compression {
+            Compression::Deflate => Some(deflate(data)?),
+            Compression::None => None,
+        };
+
+        let (entry_compression, stored): (_, &[u8]) = match compressed {
+            Some(ref c) if c.len() < data.len() => (Compression::Deflate, c),
+            _ => (Compression::None, data),
+        };
+
+        writer.write_all(stored)?;
+
+        index.push((
+            name,
+            Entry {
+                compression: entry_compression,
+                offset,
+                stored_size: stored.len() as u64,
+                size: data.len() as u64,
+                checksum: checksum(data),
+            },
+        ));
+
+        offset += stored.len() as u64;
+    }
+
+    write_u32(writer, index.len() as u32)?;
+
+    for (name, entry) in index {
+        write_u16(writer, name.len() as u16)?;
+        writer.write_all(name.as_bytes())?;
+        writer.write_all(&[entry.compression.to_u8()])?;
+        write_u64(writer, entry.offset)?;
+        write_u64(writer, entry.stored_size)?;
+        write_u64(writer, entry.size)?;
+        write_u32(writer, entry.checksum)?;
+    }
+
+    writer.seek(SeekFrom::Start(INDEX_OFFSET_POSITION))?;
+    write_u64(writer, offset)?;
+
+    Ok(())
+}
+
+/// Packs all files in the provided directory into an archive at `output`, and returns the number of
+/// files that were packed
+pub fn pack_dir<P: AsRef<Path>, Q: AsRef<Path>>(
+    dir: P,
+    output: Q,
+    compression: Compression,
+) -> Result<usize, ArchiveError> {
+    let dir = dir.as_ref();
+    let mut files = Vec::new();
+    collect_files(dir, dir, &mut files)?;
+
+    // Sort the files so packing the same directory twice produces the same archive
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut writer = BufWriter::new(File::create(output)?);
+    write_archive(&mut writer, &files, compression)?;
+    writer.flush()?;
+
+    Ok(files.len())
+}
+
+/// Adds the relative paths and data of all files in `dir` (recursively) to `files`
+fn collect_files(
+    root: &Path,
+    dir: &Path,
+    files: &mut Vec<(PathBuf, Vec<u8>)>,
+) -> Result<(), ArchiveError> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            let relative = path
+                .strip_prefix(root)
+                .map(Path::to_owned)
+                .map_err(|_| ArchiveError::InvalidPath(path.clone()))?;
+
+            files.push((relative, fs::read(&path)?));
+        }
+    }
+
+    Ok(())
+}
+
+/// Converts a relative path to the form used in the archive index, or returns `None` if it can't be
+/// stored in an archive
+fn entry_name(path: &Path) -> Option<String> {
+    let components = path
+        .components()
+        .map(|c| match c {
+            Component::Normal(s) => s.to_str(),
+            _ => None,
+        }).collect::<Option<Vec<_>>>()?;
+
+    if components.is_empty() {
+        None
+    } else {
+        Some(components.join("/"))
+    }
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+fn deflate(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut buf = [0; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut buf = [0; 2];
+    reader.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn write_u16<W: Write>(writer: &mut W, value: u16) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+fn write_u64<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    fn test_files() -> Vec<(PathBuf, Vec<u8>)> {
+        vec![
+            (PathBuf::from("shaders/main.vert"), b"void main() {}".repeat(20)),
+            (PathBuf::from("models/box.obj"), vec![1, 2, 3]),
+            (PathBuf::from("empty.txt"), Vec::new()),
+        ]
+    }
+
+    fn write_test_archive(compression: Compression) -> Vec<u8> {
+        let mut data = Cursor::new(Vec::new());
+        write_archive(&mut data, &test_files(), compression).unwrap();
+        data.into_inner()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for &compression in &[Compression::None, Compression::Deflate] {
+            let data = write_test_archive(compression);
+            let archive = Archive::from_reader(Cursor::new(data)).unwrap();
+
+            for (path, data) in test_files() {
+                assert!(archive.contains(&path));
+                assert_eq!(archive.read(&path).unwrap(), data);
+            }
+
+            assert_eq!(archive.paths().len(), 3);
+            assert!(!archive.contains("models/missing.obj"));
+            assert_eq!(
+                archive.read("models/missing.obj").unwrap_err().kind(),
+                io::ErrorKind::NotFound
+            );
+        }
+    }
+
+    #[test]
+    fn test_compression() {
+        let uncompressed = write_test_archive(Compression::None);
+        let compressed = write_test_archive(Compression::Deflate);
+
+        // Only the repetitive shader is compressed
+        assert!(compressed.len() < uncompressed.len());
+    }
+
+    #[test]
+    fn test_corruption() {
+        let mut data = write_test_archive(Compression::None);
+        // Corrupt the first byte of the first entry
+        data[HEADER_SIZE as usize] ^= 0xff;
+
+        let archive = Archive::from_reader(Cursor::new(data)).unwrap();
+        assert_eq!(
+            archive.read("shaders/main.vert").unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert!(archive.read("models/box.obj").is_ok());
+    }
+
+    #[test]
+    fn test_corrupted_sizes() {
+        let files = [(PathBuf::from("a"), b"aaaa".repeat(100))];
+        let mut data = Cursor::new(Vec::new());
+        write_archive(&mut data, &files, Compression::Deflate).unwrap();
+        let data = data.into_inner();
+
+        // The index holds the entry count and a single entry with a one byte path
+        let index_offset = data.len() - (4 + 2 + 1 + 1 + 8 * 3 + 4);
+        // Sets the offset (0), stored size (1) or size (2) of the entry
+        let set_field = |data: &mut Vec<u8>, field: usize, value: u64| {
+            let start = index_offset + 8 + field * 8;
+            data[start..start + 8].copy_from_slice(&value.to_le_bytes());
+        };
+
+        // A huge size is reported as corruption instead of being allocated
+        let mut huge_size = data.clone();
+        set_field(&mut huge_size, 2, u64::max_value());
+        let archive = Archive::from_reader(Cursor::new(huge_size)).unwrap();
+        assert_eq!(
+            archive.read("a").unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        // A stored size that makes the end of the data overflow is rejected with the index
+        let mut overflowing = data.clone();
+        set_field(&mut overflowing, 1, u64::max_value());
+
+        match Archive::from_reader(Cursor::new(overflowing)) {
+            Err(ArchiveError::InvalidIndex(_)) => {}
+            _ => panic!("Expected an invalid index error"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let mut data = write_test_archive(Compression::None);
+        data[0] = b'X';
+
+        match Archive::from_reader(Cursor::new(data)) {
+            Err(ArchiveError::InvalidMagic) => {}
+            _ => panic!("Expected an invalid magic error"),
+        }
+
+        let mut data = write_test_archive(Compression::None);
+        data[4] = 2;
+
+        match Archive::from_reader(Cursor::new(data)) {
+            Err(ArchiveError::UnsupportedVersion(2)) => {}
+            _ => panic!("Expected an unsupported version error"),
+        }
+    }
+
+    #[test]
+    fn test_entry_name() {
+        assert_eq!(
+            entry_name(Path::new("models/box.obj")),
+            Some("models/box.obj".to_string())
+        );
+        assert_eq!(entry_name(Path::new("../box.obj")), None);
+        assert_eq!(entry_name(Path::new("")), None);
+    }
+}
@@ -0,0 +1,45 @@
+//! Packs an assets directory into a single archive
+//!
+//! Usage: `pack_assets <assets directory> <output file> [--no_compression]`
+
+extern crate assets;
+
+use assets::archive::{self, Compression};
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+fn main() {
+    let mut paths = Vec::new();
+    let mut compression = Compression::Deflate;
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--no_compression" => compression = Compression::None,
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    let (dir, output) = match paths.as_slice() {
+        [dir, output] => (dir, output),
+        _ => {
+            eprintln!("Usage: pack_assets <assets directory> <output file> [--no_compression]");
+            process::exit(1);
+        }
+    };
+
+    // An archive inside the directory would be packed into the next archive made from it
+    if output.starts_with(dir) {
+        eprintln!("The output file must not be inside the assets directory");
+        process::exit(1);
+    }
+
+    match archive::pack_dir(dir, output, compression) {
+        Ok(count) => println!("Packed {} files into `{}`", count, output.display()),
+        Err(e) => {
+            eprintln!("Error packing `{}`: {}", dir.display(), e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! A cache of loaded assets, shared through reference counted handles
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// A shared handle to a cached asset
+pub type Handle<T> = Arc<T>;
+
+/// A handle to a cached asset of any type
+///
+/// This can be stored to keep an asset alive without knowing its type.
+pub type AnyHandle = Arc<Any + Send + Sync>;
+
+/// A key identifying a cached asset
+///
+/// Assets are identified by the path they were loaded from and the parameters they were loaded
+/// with, so the same file loaded in two different ways is cached twice.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    path: PathBuf,
+    params: String,
+}
+
+impl CacheKey {
+    pub fn new<P: Into<PathBuf>, S: Into<String>>(path: P, params: S) -> Self {
+        Self {
+            path: path.into(),
+            params: params.into(),
+        }
+    }
+
+    /// Returns the path of the asset
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// A cache of loaded assets
+///
+/// Each entry is stored as a `Handle`, so the number of handles to it outside the cache can be
+/// tracked. Entries with no outside handles are removed by `AssetCache::evict_unused`.
+#[derive(Default)]
+pub struct AssetCache {
+    entries: Mutex<HashMap<(TypeId, CacheKey), AnyHandle>>,
+}
+
+impl AssetCache {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns a handle to the asset with the provided key, calling `load` to load it if it is not
+    /// cached
+    pub fn get_or_load<T, E, F>(&self, key: CacheKey, load: F) -> Result<Handle<T>, E>
+    where
+        T: Any + Send + Sync,
+        F: FnOnce() -> Result<T, E>,
+    {
+        let key = (TypeId::of::<T>(), key);
+
+        if let Some(handle) = self.get_entry::<T>(&key) {
+            return Ok(handle);
+        }
+
+        // NOTE: The lock is not held while loading, because loading an asset may load other
+        //       assets through this cache
+        let asset: AnyHandle = Arc::new(load()?);
+
+        let mut entries = self.entries.lock().unwrap();
+        // If the asset was loaded by another thread in the meantime, use that one instead
+        let handle = entries.entry(key).or_insert(asset).clone();
+
+        Ok(handle
+            .downcast::<T>()
+            .unwrap_or_else(|_| unreachable!("Cached asset has the wrong type")))
+    }
+
+    /// Returns a handle to the asset with the provided key, or `None` if it is not cached
+    pub fn get<T: Any + Send + Sync>(&self, key: &CacheKey) -> Option<Handle<T>> {
+        self.get_entry(&(TypeId::of::<T>(), key.clone()))
+    }
+
+    fn get_entry<T: Any + Send + Sync>(&self, key: &(TypeId, CacheKey)) -> Option<Handle<T>> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(key)
+            .cloned()
+            .and_then(|handle| handle.downcast::<T>().ok())
+    }
+
+    /// Returns the number of handles to the asset with the provided key outside the cache, or
+    /// `None` if it is not cached
+    pub fn ref_count<T: Any + Send + Sync>(&self, key: &CacheKey) -> Option<usize> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(&(TypeId::of::<T>(), key.clone()))
+            .map(|handle| Arc::strong_count(handle) - 1)
+    }
+
+    /// Removes all assets loaded from the provided path, so they are loaded again the next time
+    /// they are requested
+    ///
+    /// Existing handles to the assets remain valid.
+    pub fn invalidate<P: AsRef<Path>>(&self, path: P) {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|(_, key), _| key.path != path.as_ref());
+    }
+
+    /// Removes all assets that have no handles outside the cache, and returns how many were removed
+    ///
+    /// This should be called when the level changes, after the entities of the previous level have
+    /// been removed.
+    pub fn evict_unused(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let len = entries.len();
+
+        // Evicting an asset may leave assets that it held handles to unused, so this is repeated
+        // until no more assets are evicted
+        loop {
+            let before = entries.len();
+            entries.retain(|_, handle| Arc::strong_count(handle) > 1);
+
+            if entries.len() == before {
+                break;
+            }
+        }
+
+        len - entries.len()
+    }
+
+    /// Returns the number of cached assets
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Returns whether the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(value: u32) -> Result<u32, ()> {
+        Ok(value)
+    }
+
+    #[test]
+    fn test_shared_handles() {
+        let cache = AssetCache::new();
+        let key = CacheKey::new("a.png", "srgba8");
+
+        let a = cache.get_or_load(key.clone(), || load(1)).unwrap();
+        let b = cache
+            .get_or_load(key.clone(), || -> Result<u32, ()> { panic!("Asset was loaded twice") })
+            .unwrap();
+
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(Arc::ptr_eq(&a, &cache.get::<u32>(&key).unwrap()));
+        assert_eq!(cache.ref_count::<u32>(&key), Some(2));
+        assert!(cache.get::<u32>(&CacheKey::new("b.png", "srgba8")).is_none());
+
+        // Different parameters or types are cached separately
+        let c = cache.get_or_load(CacheKey::new("a.png", "rgba8"), || load(2)).unwrap();
+        let d = cache.get_or_load(key.clone(), || Ok::<_, ()>(3u64)).unwrap();
+        assert_eq!((*a, *c, *d), (1, 2, 3));
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn test_evict_unused() {
+        let cache = AssetCache::new();
+        let key = CacheKey::new("a.obj", "");
+
+        let a = cache.get_or_load(key.clone(), || load(1)).unwrap();
+        cache.get_or_load(CacheKey::new("b.obj", ""), || load(2)).unwrap();
+
+        assert_eq!(cache.evict_unused(), 1);
+        assert_eq!(cache.len(), 1);
+
+        drop(a);
+        assert_eq!(cache.ref_count::<u32>(&key), Some(0));
+        assert_eq!(cache.evict_unused(), 1);
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let cache = AssetCache::new();
+        let key = CacheKey::new("a.obj", "");
+
+        let a = cache.get_or_load(key.clone(), || load(1)).unwrap();
+        cache.invalidate("a.obj");
+
+        let b = cache.get_or_load(key, || load(2)).unwrap();
+        assert_eq!((*a, *b), (1, 2));
+    }
+}
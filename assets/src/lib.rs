@@ -1,61 +1,184 @@
 //! Asset management
 
+extern crate common;
+extern crate flate2;
 #[macro_use]
 extern crate quick_error;
-#[macro_use]
-extern crate lazy_static;
-extern crate regex;
+extern crate notify;
 #[macro_use]
 extern crate slog;
 
+pub mod archive;
+pub mod cache;
 pub mod shader;
+pub mod vfs;
+pub mod watcher;
+
+use common::{console, specs};
 
-use std::fs::File;
-use std::io::{self, Read};
+use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub use cache::{AnyHandle, CacheKey, Handle};
+
+quick_error! {
+    /// An error while opening the assets directories or archives
+    #[derive(Debug)]
+    pub enum AssetsError {
+        Io(err: shader::IoError) {
+            display("{}", err)
+            from()
+        }
+        Archive(path: PathBuf, err: archive::ArchiveError) {
+            display("Error opening asset archive `{}`: {}", path.display(), err)
+        }
+        NoRoots {
+            display("No assets directory was provided")
+        }
+    }
+}
+
+/// A directory or archive that assets are read from
+struct Layer {
+    root: PathBuf,
+    vfs: vfs::Vfs,
+}
+
+impl Layer {
+    fn open(root: PathBuf) -> Result<Self, AssetsError> {
+        let root = root.canonicalize().map_err(|e| shader::IoError(root, e))?;
+        let vfs = vfs::Vfs::open(&root).map_err(|e| AssetsError::Archive(root.clone(), e))?;
 
-/// A type that calculates paths to assets based on the location of the assets directory
+        Ok(Self { root, vfs })
+    }
+}
+
+/// A type that calculates paths to assets based on the location of the assets directory, reads
+/// them, and caches loaded assets
+///
+/// Assets are read from an ordered list of layers, each of which is either a directory or an
+/// archive (see the `archive` module). The first layer is the base game, and each following layer
+/// (such as a mod) overrides the files of the layers before it.
+///
+/// Asset paths are always built from the root of the base layer, and are resolved to the layer
+/// that provides them when they are read, so they should only be read with `Assets::read_bytes`.
 pub struct Assets {
     assets_dir: PathBuf,
+    layers: Vec<Layer>,
     // These directories are calculated when the type is constructed
     shaders_dir: PathBuf,
     models_dir: PathBuf,
     fonts_dir: PathBuf,
+    scenes_dir: PathBuf,
+    waves_dir: PathBuf,
+    lang_dir: PathBuf,
+    cache: cache::AssetCache,
 }
 
 impl Assets {
-    pub fn new<P: Into<PathBuf>>(
-        log: &slog::Logger,
-        assets_dir: P,
-    ) -> Result<Self, shader::IoError> {
-        let assets_dir = assets_dir.into();
-        let assets_dir = assets_dir
-            .canonicalize()
-            .map_err(|e| shader::IoError(assets_dir, e))?;
+    /// Opens the provided asset roots, which are directories or archives ordered from lowest to
+    /// highest priority
+    pub fn new<I, P>(log: &slog::Logger, roots: I) -> Result<Self, AssetsError>
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        let layers = roots
+            .into_iter()
+            .map(|r| Layer::open(r.into()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let assets_dir = match layers.first() {
+            Some(l) => l.root.clone(),
+            None => return Err(AssetsError::NoRoots),
+        };
         let shaders_dir = assets_dir.join("shaders");
         let models_dir = assets_dir.join("models");
         let fonts_dir = assets_dir.join("fonts");
+        let scenes_dir = assets_dir.join("scenes");
+        let waves_dir = assets_dir.join("waves");
+        let lang_dir = assets_dir.join("lang");
 
-        info!(log, "Creating assets manager"; o!("assets_dir" => assets_dir.to_str().unwrap()));
+        for layer in &layers {
+            info!(log, "Adding asset layer"; o!(
+                "root" => layer.root.to_str().unwrap(),
+                "archive" => layer.vfs.is_archive()
+            ));
+        }
 
         Ok(Self {
             assets_dir,
+            layers,
             shaders_dir,
             models_dir,
             fonts_dir,
+            scenes_dir,
+            waves_dir,
+            lang_dir,
+            cache: cache::AssetCache::new(),
         })
     }
 
-    /// Returns the path to the assets directory
+    /// Returns the cache of loaded assets
+    pub fn cache(&self) -> &cache::AssetCache {
+        &self.cache
+    }
+
+    /// Returns the path to the assets directory (or archive) of the base layer
     pub fn get_assets_dir(&self) -> &Path {
         &self.assets_dir
     }
 
+    /// Returns whether the base layer is an archive instead of a directory
+    pub fn is_archive(&self) -> bool {
+        self.layers[0].vfs.is_archive()
+    }
+
+    /// Returns the roots of all layers, from lowest to highest priority
+    pub fn layer_roots(&self) -> Vec<&Path> {
+        self.layers.iter().map(|l| l.root.as_path()).collect()
+    }
+
+    /// Returns the provided directory (given as an asset path) in each layer that is a directory on
+    /// disk, from lowest to highest priority
+    ///
+    /// This is used to watch the files of all layers for changes.
+    pub fn get_layer_dirs<P: AsRef<Path>>(&self, path: P) -> Vec<PathBuf> {
+        let relative = match self.relative_path(path.as_ref()) {
+            Ok(p) => p,
+            Err(_) => return Vec::new(),
+        };
+
+        self.layers
+            .iter()
+            .filter(|l| !l.vfs.is_archive())
+            .map(|l| l.root.join(relative))
+            .filter(|p| p.is_dir())
+            .collect()
+    }
+
+    /// Converts a path to a file on disk inside one of the layers to the corresponding asset path
+    ///
+    /// Returns `None` if the file isn't inside any of the layers.
+    pub fn asset_path_of<P: AsRef<Path>>(&self, path: P) -> Option<PathBuf> {
+        self.layers
+            .iter()
+            .filter_map(|l| path.as_ref().strip_prefix(&l.root).ok())
+            .next()
+            .map(|relative| self.assets_dir.join(relative))
+    }
+
     /// Returns a path to a shader file given a path relative to the shaders directory
     pub fn get_shader_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
         self.shaders_dir.join(path)
     }
 
+    /// Returns the path to the models directory
+    pub fn get_models_dir(&self) -> &Path {
+        &self.models_dir
+    }
+
     /// Returns a path to a model file given a path relative to the models directory
     pub fn get_model_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
         self.models_dir.join(path)
@@ -65,12 +188,122 @@ impl Assets {
     pub fn get_font_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
         self.fonts_dir.join(path)
     }
+
+    /// Returns a path to a scene file given a path relative to the scenes directory
+    pub fn get_scene_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.scenes_dir.join(path)
+    }
+
+    /// Returns a path to a wave file given a path relative to the waves directory
+    pub fn get_wave_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.waves_dir.join(path)
+    }
+
+    /// Returns the path to the language directory, which contains the localized string tables
+    pub fn get_lang_dir(&self) -> &Path {
+        &self.lang_dir
+    }
+
+    /// Returns a path to a string table given a path relative to the language directory
+    pub fn get_lang_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.lang_dir.join(path)
+    }
+
+    /// Returns the bytes in the asset file at the provided path
+    ///
+    /// The path should be one returned by the `get_*_path` functions (or built from
+    /// `get_assets_dir`). The file is read from the highest priority layer that contains it, and
+    /// this works the same way whether that layer is a directory or an archive.
+    pub fn read_bytes<P: AsRef<Path>>(&self, path: P) -> io::Result<Vec<u8>> {
+        let relative = self.relative_path(path.as_ref())?;
+
+        // If no layer has the file, reading it from the base layer returns a suitable error
+        self.find_layer(relative)
+            .unwrap_or(&self.layers[0])
+            .vfs
+            .read(relative)
+    }
+
+    /// Returns whether an asset file exists at the provided path in any layer
+    pub fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.relative_path(path.as_ref())
+            .map(|p| self.find_layer(p).is_some())
+            .unwrap_or(false)
+    }
+
+    /// Returns the root of the layer that provides the asset file at the provided path, or `None`
+    /// if no layer has it
+    pub fn which_layer<P: AsRef<Path>>(&self, path: P) -> Option<&Path> {
+        self.relative_path(path.as_ref())
+            .ok()
+            .and_then(|p| self.find_layer(p))
+            .map(|l| l.root.as_path())
+    }
+
+    /// Returns the paths of all asset files in all layers, sorted and without duplicates
+    pub fn files(&self) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+
+        for layer in &self.layers {
+            files.extend(layer.vfs.files()?.into_iter().map(|p| self.assets_dir.join(p)));
+        }
+
+        files.sort();
+        files.dedup();
+
+        Ok(files)
+    }
+
+    /// Returns the highest priority layer that contains the file at the provided relative path
+    fn find_layer(&self, relative: &Path) -> Option<&Layer> {
+        self.layers.iter().rev().find(|l| l.vfs.exists(relative))
+    }
+
+    /// Returns the provided path relative to the assets directory
+    fn relative_path<'a>(&self, path: &'a Path) -> io::Result<&'a Path> {
+        path.strip_prefix(&self.assets_dir).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("`{}` is not in the assets directory", path.display()),
+            )
+        })
+    }
 }
 
-/// Returns the bytes in the file at the provided path
-pub fn read_bytes<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
-    let mut file = File::open(path)?;
-    let mut data = Vec::new();
-    file.read_to_end(&mut data)?;
-    Ok(data)
+/// Registers console commands for asset management
+pub fn register_commands(commands: &mut console::CommandRegistry) {
+    commands.register(
+        "evict_assets",
+        "evict_assets",
+        "Removes all cached assets that are no longer in use",
+        Box::new(|world: &mut specs::World, _: &[&str]| {
+            let assets = world.read_resource::<Arc<Assets>>();
+            let evicted = assets.cache().evict_unused();
+
+            Ok(Some(format!(
+                "Evicted {} assets ({} remaining)",
+                evicted,
+                assets.cache().len()
+            )))
+        }),
+    );
+
+    commands.register(
+        "which_asset",
+        "which_asset <path>",
+        "Prints which asset layer (the base game or a mod) provides a file",
+        Box::new(|world: &mut specs::World, args: &[&str]| {
+            let path = match args {
+                [path] => *path,
+                _ => return Err("Expected a path".to_string()),
+            };
+
+            let assets = world.read_resource::<Arc<Assets>>();
+
+            match assets.which_layer(assets.get_assets_dir().join(path)) {
+                Some(root) => Ok(Some(format!("`{}` is provided by `{}`", path, root.display()))),
+                None => Err(format!("`{}` is not in any asset layer", path)),
+            }
+        }),
+    );
 }
@@ -1,13 +1,26 @@
 //! Shader loading and preprocessing
+//!
+//! Shaders are preprocessed before they are passed to the driver. The supported directives are:
+//!
+//! * `#include "path"`, which is replaced by the file at `path` (relative to the shaders directory)
+//! * `#pragma once`, which makes later `#include`s of the same file do nothing
+//! * `#ifdef`, `#ifndef`, `#if`, `#elif`, `#else` and `#endif`, which are evaluated against the
+//!   provided defines and the `#define`s and `#undef`s in the shader itself (so include guards
+//!   work as they do in C)
+//!
+//! The provided defines are inserted as `#define` statements after the first line (which should be
+//! the `#version` directive), and `#line` directives are inserted around included files so line
+//! numbers in driver errors refer to the original files. The source string number of a `#line`
+//! directive is the index of the file in the order the files were first included, with the main
+//! file being 0. Before GLSL 3.30, `#line N` makes the line after it N + 1 instead of N, so the
+//! directives are adjusted to the version of the main file. A `SourceMap` is returned along with
+//! the code, which maps lines of the output and compiler errors back to the original files.
 
-use regex::bytes::{Captures, Regex, Replacer};
-
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::string::FromUtf8Error;
 use std::{fmt, io};
 
-use read_bytes;
 use Assets;
 
 const MAX_RECURSION_DEPTH: usize = 32;
@@ -50,136 +63,1079 @@ quick_error! {
         MaxIncludeRecursion {
             display("Maximum recursion depth reached while processing `#include` directives")
         }
-        Utf8(err: FromUtf8Error) {
-            display("Invalid UTF-8 in shader name: {:?}", err)
-            from()
+        IncludeCycle(chain: Vec<PathBuf>) {
+            display("Include cycle: {}", format_chain(chain))
+        }
+        Utf8(path: PathBuf, err: FromUtf8Error) {
+            display("Invalid UTF-8 in `{}`: {}", path.display(), err)
+        }
+        Directive(path: PathBuf, line: usize, message: String) {
+            display("Error in `{}` at line {}: {}", path.display(), line, message)
         }
     }
 }
 
-/// Loads a shader from the file at the provided path
+/// Formats a chain of `#include`d files as `a.glsl -> b.glsl -> a.glsl`
+fn format_chain(chain: &[PathBuf]) -> String {
+    chain
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+/// A preprocessed shader
+#[derive(Clone, Debug)]
+pub struct ShaderSource {
+    /// The preprocessed code, which can be passed to the driver
+    pub code: Vec<u8>,
+    /// Maps the lines of `code` back to the files they came from
+    pub source_map: SourceMap,
+}
+
+/// A line in a shader file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// The path of the file, relative to the shaders directory
+    pub file: PathBuf,
+    /// The line number, starting at 1
+    pub line: usize,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}:{}", self.file.display(), self.line)
+    }
+}
+
+/// A message from the GLSL compiler, with its location mapped back to the original file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompilerMessage {
+    /// The location the message refers to, or `None` if it couldn't be found
+    pub location: Option<SourceLocation>,
+    pub message: String,
+}
+
+impl fmt::Display for CompilerMessage {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.location {
+            Some(ref location) => write!(fmt, "{}: {}", location, self.message),
+            None => write!(fmt, "{}", self.message),
+        }
+    }
+}
+
+/// Maps the lines of a preprocessed shader back to the files and lines they came from
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SourceMap {
+    /// The files of the shader, indexed by their source string number
+    files: Vec<PathBuf>,
+    /// The source string number and line number of each line of the output, or `None` for lines
+    /// inserted by the preprocessor
+    lines: Vec<Option<(usize, usize)>>,
+}
+
+impl SourceMap {
+    /// Returns the files of the shader, with the main file first
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Returns the original location of a line (starting at 1) of the preprocessed code
+    pub fn location(&self, line: usize) -> Option<SourceLocation> {
+        let (source, line) = (*self.lines.get(line.checked_sub(1)?)?)?;
+        self.resolve(source, line)
+    }
+
+    /// Returns the location with the provided source string number and line number, as set by the
+    /// `#line` directives that the preprocessor inserts
+    pub fn resolve(&self, source: usize, line: usize) -> Option<SourceLocation> {
+        self.files.get(source).map(|file| SourceLocation {
+            file: file.clone(),
+            line,
+        })
+    }
+
+    /// Splits the info log of a failed compilation into messages, and maps their locations back to
+    /// the original files
+    ///
+    /// Locations are recognized in the formats used by the common drivers (`0:12(5): `,
+    /// `0(12) : ` and `ERROR: 0:12: `).
+    pub fn map_compiler_log(&self, log: &str) -> Vec<CompilerMessage> {
+        log.lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(|l| match parse_log_location(l) {
+                Some((source, line, message)) => CompilerMessage {
+                    location: self.resolve(source, line),
+                    message,
+                },
+                None => CompilerMessage {
+                    location: None,
+                    message: l.to_string(),
+                },
+            }).collect()
+    }
+}
+
+/// Parses the source string number and line number at the start of a line of a compiler log, and
+/// returns them with the rest of the line
+fn parse_log_location(line: &str) -> Option<(usize, usize, String)> {
+    fn number(s: &str) -> Option<(usize, &str)> {
+        let len = s.find(|c: char| !c.is_digit(10)).unwrap_or_else(|| s.len());
+        let n = s[..len].parse().ok()?;
+        Some((n, &s[len..]))
+    }
+
+    // Some drivers start each line with the severity
+    let prefix = ["ERROR: ", "WARNING: "]
+        .iter()
+        .find(|p| line.starts_with(**p))
+        .map(|p| &line[..p.len()])
+        .unwrap_or("");
+
+    let (source, rest) = number(&line[prefix.len()..])?;
+
+    if !rest.starts_with(':') && !rest.starts_with('(') {
+        return None;
+    }
+
+    let (line_number, rest) = number(&rest[1..])?;
+
+    // Skip the rest of the location, such as a column number
+    let message = match rest.find(": ") {
+        Some(i) => rest[i + 2..].trim(),
+        None => rest.trim(),
+    };
+
+    Some((source, line_number, format!("{}{}", prefix, message)))
+}
+
+/// Loads a shader from the file at the provided path, which is relative to the shaders directory
 ///
-/// Applies special parsing such as processing `#include` directives and inserting `#define`s
+/// The shader is preprocessed as described in the module documentation.
 pub fn load_shader_file<P: AsRef<Path>>(
     assets: &Assets,
     path: P,
     defines: &HashMap<String, String>,
-) -> Result<Vec<u8>, ShaderLoadingError> {
-    load_shader_file_impl(assets, path.as_ref(), defines)
+) -> Result<ShaderSource, ShaderLoadingError> {
+    let read = |path: &Path| -> Result<String, ShaderLoadingError> {
+        let path = assets.get_shader_path(path);
+        let bytes = assets
+            .read_bytes(&path)
+            .map_err(|err| ShaderLoadingError::Io(IoError(path.clone(), err)))?;
+
+        String::from_utf8(bytes).map_err(|err| ShaderLoadingError::Utf8(path, err))
+    };
+
+    preprocess(&read, path.as_ref(), defines).map(|(code, source_map)| ShaderSource {
+        code: code.into_bytes(),
+        source_map,
+    })
 }
 
-pub fn load_shader_file_impl(
-    assets: &Assets,
+/// Preprocesses the shader at the provided path, reading files with `read`
+fn preprocess(
+    read: &Fn(&Path) -> Result<String, ShaderLoadingError>,
     path: &Path,
     defines: &HashMap<String, String>,
-) -> Result<Vec<u8>, ShaderLoadingError> {
-    let mut result = load_shader_file_with_includes(assets, path, 0)?;
+) -> Result<(String, SourceMap), ShaderLoadingError> {
+    let mut preprocessor = Preprocessor {
+        read,
+        initial_defines: defines,
+        defines: defines.clone(),
+        once: HashSet::new(),
+        chain: Vec::new(),
+        // Without a `#version` directive, the shader is GLSL 1.10
+        line_offset: 1,
+        output: String::new(),
+        source_map: SourceMap::default(),
+    };
 
-    // The index at which to insert the `#define` statements
-    let defines_index = result
-        .iter()
-        .position(|byte| *byte == b'\n')
-        .map(|p| p + 1)
-        .unwrap_or_else(|| result.len());
+    preprocessor.process_file(path)?;
+
+    Ok((preprocessor.output, preprocessor.source_map))
+}
+
+/// The state of an `#if` (or `#ifdef` or `#ifndef`) block
+struct Condition {
+    /// Whether the lines of the current branch are included
+    active: bool,
+    /// Whether a branch of the block has already been included
+    taken: bool,
+    /// Whether the `#else` branch has been reached
+    seen_else: bool,
+}
+
+struct Preprocessor<'a> {
+    read: &'a Fn(&Path) -> Result<String, ShaderLoadingError>,
+    initial_defines: &'a HashMap<String, String>,
+    /// The provided defines, updated by the `#define`s and `#undef`s processed so far
+    defines: HashMap<String, String>,
+    /// The files containing `#pragma once` that have been processed
+    once: HashSet<PathBuf>,
+    /// The files being processed, from the main file to the current one
+    chain: Vec<PathBuf>,
+    /// The amount to subtract from the line numbers of `#line` directives (see `line_offset`)
+    line_offset: usize,
+    output: String,
+    source_map: SourceMap,
+}
+
+impl<'a> Preprocessor<'a> {
+    fn process_file(&mut self, path: &Path) -> Result<(), ShaderLoadingError> {
+        // This is checked first, so files using `#pragma once` can include each other
+        if self.once.contains(path) {
+            return Ok(());
+        }
+
+        if self.chain.iter().any(|p| p == path) {
+            let mut chain = self.chain.clone();
+            chain.push(path.to_owned());
+            return Err(ShaderLoadingError::IncludeCycle(chain));
+        }
+
+        if self.chain.len() > MAX_RECURSION_DEPTH {
+            return Err(ShaderLoadingError::MaxIncludeRecursion);
+        }
+
+        let source = (self.read)(path)?;
+        let index = self.file_index(path);
+        let is_main = self.chain.is_empty();
+        self.chain.push(path.to_owned());
 
-    for (key, val) in defines {
-        let define_statement = format!("#define {} {}", key, val).into_bytes();
+        if !is_main {
+            self.push_line_directive(1, index);
+        }
+
+        let mut conditions: Vec<Condition> = Vec::new();
+        let mut line_count = 0;
 
-        // Many characters will be inserted individually, so reserve space to avoid frequent
-        // allocations
-        result.reserve(define_statement.len() + 1);
+        for (i, line) in source.lines().enumerate() {
+            let number = i + 1;
+            line_count = number;
 
-        result.insert(defines_index, b'\n');
+            let error = |message: String| {
+                ShaderLoadingError::Directive(path.to_owned(), number, message)
+            };
+            let active = conditions.iter().all(|c| c.active);
+            let location = Some((index, number));
 
-        for c in define_statement.into_iter().rev() {
-            result.insert(defines_index, c);
+            // Lines that are removed are replaced by empty lines, so the line numbers stay the same
+            match parse_directive(line) {
+                Some((name, args)) if is_conditional(name) => {
+                    self.conditional(&mut conditions, name, args).map_err(error)?;
+                    self.push_line("", location);
+                }
+                Some(_) | None if !active => self.push_line("", location),
+                Some(("version", args)) if is_main => {
+                    self.line_offset = line_offset(args);
+                    self.push_line(line, location);
+                }
+                Some(("pragma", "once")) => {
+                    self.once.insert(path.to_owned());
+                    self.push_line("", location);
+                }
+                Some(("include", args)) => {
+                    let include = parse_include(args)
+                        .ok_or_else(|| error(format!("Invalid `#include` arguments `{}`", args)))?;
+
+                    self.process_file(Path::new(include))?;
+                    self.push_line_directive(number + 1, index);
+                }
+                Some(("define", args)) => {
+                    let (name, value) = parse_define(args);
+                    self.defines.insert(name.to_owned(), value.to_owned());
+                    self.push_line(line, location);
+                }
+                Some(("undef", args)) => {
+                    self.defines.remove(first_word(args));
+                    self.push_line(line, location);
+                }
+                _ => self.push_line(line, location),
+            }
+
+            if is_main && number == 1 {
+                self.insert_defines();
+            }
+        }
+
+        if !conditions.is_empty() {
+            return Err(ShaderLoadingError::Directive(
+                path.to_owned(),
+                line_count,
+                "Unterminated conditional directive".to_string(),
+            ));
         }
+
+        self.chain.pop();
+
+        Ok(())
     }
 
-    Ok(result)
+    /// Processes a conditional directive, updating the stack of conditions of the current file
+    fn conditional(
+        &self,
+        conditions: &mut Vec<Condition>,
+        name: &str,
+        args: &str,
+    ) -> Result<(), String> {
+        // Whether the block containing the directive is included
+        fn parent_active(conditions: &[Condition]) -> bool {
+            conditions.iter().all(|c| c.active)
+        }
+
+        match name {
+            "ifdef" | "ifndef" | "if" => {
+                let value = if parent_active(conditions) {
+                    match name {
+                        "ifdef" => self.defines.contains_key(first_word(args)),
+                        "ifndef" => !self.defines.contains_key(first_word(args)),
+                        _ => evaluate(strip_comment(args), &self.defines)? != 0,
+                    }
+                } else {
+                    // Conditions in excluded blocks are not evaluated
+                    false
+                };
+
+                conditions.push(Condition {
+                    active: value,
+                    taken: value,
+                    seen_else: false,
+                });
+            }
+            "elif" | "else" => {
+                let (condition, parents) = match conditions.split_last_mut() {
+                    Some(c) => c,
+                    None => return Err(format!("`#{}` without `#if`", name)),
+                };
+
+                if condition.seen_else {
+                    return Err(format!("`#{}` after `#else`", name));
+                }
+
+                let value = if parent_active(parents) && !condition.taken {
+                    name == "else" || evaluate(strip_comment(args), &self.defines)? != 0
+                } else {
+                    false
+                };
+
+                condition.active = value;
+                condition.taken |= value;
+                condition.seen_else = name == "else";
+            }
+            "endif" => {
+                if conditions.pop().is_none() {
+                    return Err("`#endif` without `#if`".to_string());
+                }
+            }
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    /// Inserts the provided defines as `#define` statements, followed by a `#line` directive that
+    /// restores the line numbers of the main file
+    fn insert_defines(&mut self) {
+        // The defines are sorted so the output is the same every time the shader is loaded
+        let mut defines = self.initial_defines.iter().collect::<Vec<_>>();
+        defines.sort();
+
+        for (key, val) in defines {
+            self.push_line(&format!("#define {} {}", key, val), None);
+        }
+
+        self.push_line_directive(2, 0);
+    }
+
+    /// Adds a `#line` directive that makes the line after it have the provided line number and
+    /// source string number
+    fn push_line_directive(&mut self, line: usize, source: usize) {
+        let line = line - self.line_offset;
+        self.push_line(&format!("#line {} {}", line, source), None);
+    }
+
+    /// Adds a line to the output, which came from the provided source string number and line
+    /// number
+    fn push_line(&mut self, line: &str, location: Option<(usize, usize)>) {
+        self.output.push_str(line);
+        self.output.push('\n');
+        self.source_map.lines.push(location);
+    }
+
+    /// Returns the source string number of the provided file
+    fn file_index(&mut self, path: &Path) -> usize {
+        let files = &mut self.source_map.files;
+
+        match files.iter().position(|p| p == path) {
+            Some(i) => i,
+            None => {
+                files.push(path.to_owned());
+                files.len() - 1
+            }
+        }
+    }
+}
+
+/// Splits a preprocessor directive into its name and arguments, or returns `None` if the line is
+/// not a directive
+fn parse_directive(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+
+    if !line.starts_with('#') {
+        return None;
+    }
+
+    let line = line[1..].trim();
+    let end = line.find(char::is_whitespace).unwrap_or_else(|| line.len());
+
+    Some((&line[..end], line[end..].trim()))
 }
 
-/// Loads the shader from the file at the provided path, and processes `#include` directives
+/// Returns the amount to subtract from the line number of a `#line` directive, from the arguments
+/// of the `#version` directive
 ///
-/// `#define` processing happens in `load_shader_file` so that the recursive `#include` processing
-/// can avoid applying `#define`s to `#include`ed shaders
-fn load_shader_file_with_includes(
-    assets: &Assets,
-    path: &Path,
-    recurses: usize,
-) -> Result<Vec<u8>, ShaderLoadingError> {
-    if recurses > MAX_RECURSION_DEPTH {
-        return Err(ShaderLoadingError::MaxIncludeRecursion);
+/// In GLSL 3.30 and later (and GLSL ES 3.00 and later), `#line N` makes the line after it N. In
+/// earlier versions, it makes the line after it N + 1.
+fn line_offset(version_args: &str) -> usize {
+    let mut args = strip_comment(version_args).split_whitespace();
+    let version = args.next().and_then(|v| v.parse::<u32>().ok()).unwrap_or(110);
+    let es = args.next() == Some("es");
+
+    if version >= 330 || (es && version >= 300) {
+        0
+    } else {
+        1
     }
+}
+
+fn is_conditional(name: &str) -> bool {
+    match name {
+        "ifdef" | "ifndef" | "if" | "elif" | "else" | "endif" => true,
+        _ => false,
+    }
+}
 
-    let path = assets.get_shader_path(path);
-    let bytes =
-        read_bytes(&path).map_err(|err| ShaderLoadingError::Io(IoError(path.to_owned(), err)))?;
+/// Returns the path in the arguments of an `#include` directive
+fn parse_include(args: &str) -> Option<&str> {
+    let args = args.trim();
 
-    lazy_static! {
-        static ref FIND_INCLUDE: Regex = Regex::new(r#"#include "(.*)""#).unwrap();
+    if !args.starts_with('"') {
+        return None;
     }
 
-    let mut replacer = IncludeReplacer::new(assets, recurses);
+    args[1..].find('"').map(|end| &args[1..end + 1])
+}
 
-    let result = FIND_INCLUDE.replace_all(&bytes, &mut replacer).into_owned();
+/// Returns the name and value of a `#define` directive
+fn parse_define(args: &str) -> (&str, &str) {
+    let end = args
+        .find(|c: char| c.is_whitespace() || c == '(')
+        .unwrap_or_else(|| args.len());
 
-    let _ = replacer.error?;
+    (&args[..end], strip_comment(&args[end..]).trim())
+}
 
-    Ok(result)
+fn first_word(s: &str) -> &str {
+    s.split_whitespace().next().unwrap_or("")
 }
 
-// NOTE: All these `recurses` variables are for tracking recursion depth so an error can be made if
-//       it exceeds the limit
+fn strip_comment(s: &str) -> &str {
+    s.split("//").next().unwrap_or("")
+}
 
-/// A type for replacing `#include` directives with the file contents at the path specified by the
-/// directive
-struct IncludeReplacer<'a> {
-    assets: &'a Assets,
-    error: Result<(), ShaderLoadingError>,
-    recurses: usize,
+/// A token in the expression of an `#if` or `#elif` directive
+#[derive(Clone, Debug, PartialEq)]
+enum Token<'a> {
+    Number(i64),
+    Ident(&'a str),
+    Op(&'static str),
+    LeftParen,
+    RightParen,
 }
 
-impl<'a> IncludeReplacer<'a> {
-    fn new(assets: &'a Assets, recurses: usize) -> Self {
-        IncludeReplacer {
-            assets,
-            error: Ok(()),
-            recurses,
-        }
+const OPERATORS: [&str; 20] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "|", "^", "&", "<", ">", "+", "-", "*", "/",
+    "%", "!", "~",
+];
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = expr.trim();
+
+    while !rest.is_empty() {
+        let c = rest.chars().next().unwrap();
+
+        let len = if c.is_digit(10) {
+            let len = rest
+                .find(|c: char| !c.is_alphanumeric())
+                .unwrap_or_else(|| rest.len());
+            tokens.push(Token::Number(parse_number(&rest[..len])?));
+            len
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or_else(|| rest.len());
+            tokens.push(Token::Ident(&rest[..len]));
+            len
+        } else if c == '(' {
+            tokens.push(Token::LeftParen);
+            1
+        } else if c == ')' {
+            tokens.push(Token::RightParen);
+            1
+        } else {
+            match OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+                Some(op) => {
+                    tokens.push(Token::Op(*op));
+                    op.len()
+                }
+                None => return Err(format!("Unexpected character `{}`", c)),
+            }
+        };
+
+        rest = rest[len..].trim();
     }
+
+    Ok(tokens)
 }
 
-impl<'a> Replacer for IncludeReplacer<'a> {
-    fn replace_append(&mut self, caps: &Captures, dst: &mut Vec<u8>) {
-        let file_contents = match replace_include(self.assets, caps, self.recurses) {
-            Ok(bytes) => bytes,
-            Err(e) => {
-                self.error = Err(e);
-                return;
+/// Parses a decimal or hexadecimal integer literal, ignoring `u` suffixes
+fn parse_number(s: &str) -> Result<i64, String> {
+    let digits = s.trim_right_matches(|c: char| c == 'u' || c == 'U');
+
+    let result = if digits.starts_with("0x") || digits.starts_with("0X") {
+        i64::from_str_radix(&digits[2..], 16)
+    } else {
+        digits.parse()
+    };
+
+    result.map_err(|_| format!("Invalid number `{}`", s))
+}
+
+/// Evaluates the expression of an `#if` or `#elif` directive
+///
+/// Identifiers are replaced by the value of the define with that name, or 0 if there is no such
+/// define.
+fn evaluate(expr: &str, defines: &HashMap<String, String>) -> Result<i64, String> {
+    evaluate_with_depth(expr, defines, 0, true)
+}
+
+/// Evaluates an expression, or only checks its syntax if `evaluated` is `false` (for the operands
+/// of `&&` and `||` that are skipped)
+fn evaluate_with_depth(
+    expr: &str,
+    defines: &HashMap<String, String>,
+    depth: usize,
+    evaluated: bool,
+) -> Result<i64, String> {
+    if depth > MAX_RECURSION_DEPTH {
+        return Err("Maximum recursion depth reached while expanding defines".to_string());
+    }
+
+    let tokens = tokenize(expr)?;
+
+    if tokens.is_empty() {
+        return Err("Expected an expression".to_string());
+    }
+
+    let mut parser = ExprParser {
+        tokens: &tokens,
+        pos: 0,
+        defines,
+        depth,
+        evaluated,
+    };
+    let value = parser.expression(0)?;
+
+    match tokens.get(parser.pos) {
+        Some(t) => Err(format!("Unexpected token {:?} in `{}`", t, expr)),
+        None => Ok(value),
+    }
+}
+
+/// A precedence climbing parser for preprocessor expressions, which evaluates them as they are
+/// parsed
+struct ExprParser<'a, 'b: 'a> {
+    tokens: &'a [Token<'b>],
+    pos: usize,
+    defines: &'a HashMap<String, String>,
+    depth: usize,
+    /// Whether the current operand is evaluated, which is not the case for the right operand of
+    /// `&&` and `||` if the left operand decides the result
+    evaluated: bool,
+}
+
+impl<'a, 'b> ExprParser<'a, 'b> {
+    fn next(&mut self) -> Option<Token<'b>> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expression(&mut self, min_precedence: u8) -> Result<i64, String> {
+        let mut lhs = self.unary()?;
+
+        while let Some(&Token::Op(op)) = self.tokens.get(self.pos) {
+            let precedence = match binary_precedence(op) {
+                Some(p) if p >= min_precedence => p,
+                _ => break,
+            };
+
+            self.pos += 1;
+
+            // As in C, the right operand of `&&` and `||` isn't evaluated if the left operand
+            // decides the result, so errors such as division by zero are ignored in it
+            let evaluated = self.evaluated;
+            self.evaluated &= match op {
+                "&&" => lhs != 0,
+                "||" => lhs == 0,
+                _ => true,
+            };
+            let rhs = self.expression(precedence + 1);
+            self.evaluated = evaluated;
+
+            lhs = match apply_binary(op, lhs, rhs?) {
+                Err(_) if !evaluated => 0,
+                result => result?,
+            };
+        }
+
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(n),
+            Some(Token::Ident("defined")) => self.defined(),
+            Some(Token::Ident(name)) => match self.defines.get(name) {
+                Some(value) if value.trim().is_empty() => {
+                    Err(format!("`{}` is defined without a value", name))
+                }
+                Some(value) => {
+                    evaluate_with_depth(value, self.defines, self.depth + 1, self.evaluated)
+                }
+                None => Ok(0),
+            },
+            Some(Token::LeftParen) => {
+                let value = self.expression(0)?;
+
+                match self.next() {
+                    Some(Token::RightParen) => Ok(value),
+                    _ => Err("Expected `)`".to_string()),
+                }
             }
+            Some(Token::Op("!")) => self.unary().map(|v| (v == 0) as i64),
+            Some(Token::Op("-")) => self.unary().map(i64::wrapping_neg),
+            Some(Token::Op("+")) => self.unary(),
+            Some(Token::Op("~")) => self.unary().map(|v| !v),
+            Some(t) => Err(format!("Unexpected token {:?}", t)),
+            None => Err("Unexpected end of expression".to_string()),
+        }
+    }
+
+    /// Evaluates `defined NAME` or `defined(NAME)`, after the `defined` token
+    fn defined(&mut self) -> Result<i64, String> {
+        let parenthesized = self.tokens.get(self.pos) == Some(&Token::LeftParen);
+
+        if parenthesized {
+            self.pos += 1;
+        }
+
+        let name = match self.next() {
+            Some(Token::Ident(name)) => name,
+            _ => return Err("Expected a name after `defined`".to_string()),
         };
 
-        dst.extend(file_contents);
-        dst.extend(b"\n");
+        if parenthesized && self.next() != Some(Token::RightParen) {
+            return Err("Expected `)`".to_string());
+        }
+
+        Ok(self.defines.contains_key(name) as i64)
     }
 }
 
-impl<'a: 'b, 'b> Replacer for &'b mut IncludeReplacer<'a> {
-    fn replace_append(&mut self, caps: &Captures, dst: &mut Vec<u8>) {
-        (**self).replace_append(caps, dst)
-    }
+fn binary_precedence(op: &str) -> Option<u8> {
+    let precedence = match op {
+        "||" => 1,
+        "&&" => 2,
+        "|" => 3,
+        "^" => 4,
+        "&" => 5,
+        "==" | "!=" => 6,
+        "<" | ">" | "<=" | ">=" => 7,
+        "<<" | ">>" => 8,
+        "+" | "-" => 9,
+        "*" | "/" | "%" => 10,
+        _ => return None,
+    };
+
+    Some(precedence)
 }
 
-/// The actual implementation of `replace_append` for `IncludeReplacer`, made separate for nicer
-/// error handling
-fn replace_include(
-    assets: &Assets,
-    caps: &Captures,
-    recurses: usize,
-) -> Result<Vec<u8>, ShaderLoadingError> {
-    let name = caps[1].to_vec();
-    let name = String::from_utf8(name).map_err(|e| ShaderLoadingError::Utf8(e))?;
+fn apply_binary(op: &str, lhs: i64, rhs: i64) -> Result<i64, String> {
+    let result = match op {
+        "||" => (lhs != 0 || rhs != 0) as i64,
+        "&&" => (lhs != 0 && rhs != 0) as i64,
+        "|" => lhs | rhs,
+        "^" => lhs ^ rhs,
+        "&" => lhs & rhs,
+        "==" => (lhs == rhs) as i64,
+        "!=" => (lhs != rhs) as i64,
+        "<" => (lhs < rhs) as i64,
+        ">" => (lhs > rhs) as i64,
+        "<=" => (lhs <= rhs) as i64,
+        ">=" => (lhs >= rhs) as i64,
+        "<<" => lhs.checked_shl(rhs as u32).unwrap_or(0),
+        ">>" => lhs.checked_shr(rhs as u32).unwrap_or(0),
+        "+" => lhs.wrapping_add(rhs),
+        "-" => lhs.wrapping_sub(rhs),
+        "*" => lhs.wrapping_mul(rhs),
+        "/" | "%" if rhs == 0 => return Err("Division by zero".to_string()),
+        "/" => lhs.wrapping_div(rhs),
+        "%" => lhs.wrapping_rem(rhs),
+        _ => unreachable!(),
+    };
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defines(defines: &[(&str, &str)]) -> HashMap<String, String> {
+        defines
+            .iter()
+            .map(|&(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    /// Preprocesses `main.glsl` from the provided files
+    fn run(
+        files: &[(&str, &str)],
+        defines: &[(&str, &str)],
+    ) -> Result<(String, SourceMap), ShaderLoadingError> {
+        let files = files
+            .iter()
+            .map(|&(p, s)| (PathBuf::from(p), s.to_string()))
+            .collect::<HashMap<_, _>>();
+        let read = |path: &Path| {
+            files.get(path).cloned().ok_or_else(|| {
+                let err = io::Error::new(io::ErrorKind::NotFound, "Not found");
+                ShaderLoadingError::Io(IoError(path.to_owned(), err))
+            })
+        };
+
+        preprocess(&read, Path::new("main.glsl"), &self::defines(defines))
+    }
+
+    /// Returns the lines of the output that aren't empty or directives
+    fn code(output: &str) -> Vec<&str> {
+        output
+            .lines()
+            .filter(|l| !l.trim().is_empty() && !l.starts_with('#'))
+            .collect()
+    }
+
+    #[test]
+    fn test_conditionals() {
+        let main = "#version 150
+#ifdef SHADOWS_ENABLED
+shadows
+#else
+no_shadows
+#endif
+#ifndef SHADOWS_ENABLED
+not_defined
+#endif
+#if MAX_LIGHTS > 4 && defined(SHADOWS_ENABLED)
+many
+#elif MAX_LIGHTS > 2
+some
+#else
+few
+#endif
+";
+        let enabled = run(
+            &[("main.glsl", main)],
+            &[("SHADOWS_ENABLED", "1"), ("MAX_LIGHTS", "8")],
+        );
+        assert_eq!(code(&enabled.unwrap().0), ["shadows", "many"]);
+
+        let disabled = run(&[("main.glsl", main)], &[("MAX_LIGHTS", "3")]);
+        assert_eq!(code(&disabled.unwrap().0), ["no_shadows", "not_defined", "some"]);
+
+        let none = run(&[("main.glsl", main)], &[]);
+        assert_eq!(code(&none.unwrap().0), ["no_shadows", "not_defined", "few"]);
+    }
+
+    #[test]
+    fn test_nested_conditionals() {
+        let main = "#version 150
+#if 0
+#if 1
+a
+#else
+b
+#endif
+#elif 1
+#ifdef A
+c
+#endif
+d
+#endif
+";
+        let (output, _) = run(&[("main.glsl", main)], &[("A", "")]).unwrap();
+        assert_eq!(code(&output), ["c", "d"]);
+    }
+
+    #[test]
+    fn test_pragma_once_and_include_guards() {
+        let files = [
+            (
+                "main.glsl",
+                "#version 150
+#include \"once.glsl\"
+#include \"guarded.glsl\"
+#include \"once.glsl\"
+#include \"guarded.glsl\"
+main
+",
+            ),
+            ("once.glsl", "#pragma once\nonce\n"),
+            (
+                "guarded.glsl",
+                "#ifndef GUARDED_GLSL\n#define GUARDED_GLSL\nguarded\n#endif // GUARDED_GLSL\n",
+            ),
+        ];
+
+        let (output, _) = run(&files, &[]).unwrap();
+        assert_eq!(code(&output), ["once", "guarded", "main"]);
+    }
+
+    /// Returns the source string number and line number that a driver assigns to each line of the
+    /// output, following the `#line` semantics of GLSL 3.30 or of earlier versions
+    fn driver_locations(output: &str, glsl_330: bool) -> Vec<(usize, usize)> {
+        let (mut source, mut line) = (0, 1);
 
-    load_shader_file_with_includes(assets, &Path::new(&name), recurses + 1)
+        output
+            .lines()
+            .map(|l| {
+                let location = (source, line);
+                line += 1;
+
+                if let Some(("line", args)) = parse_directive(l) {
+                    let mut args = args.split_whitespace().map(|a| a.parse().unwrap());
+                    line = args.next().unwrap() + if glsl_330 { 0 } else { 1 };
+                    source = args.next().unwrap_or(source);
+                }
+
+                location
+            }).collect()
+    }
+
+    #[test]
+    fn test_line_directives_and_source_map() {
+        let files = |version| {
+            [
+                ("main.glsl", format!("#version {}\n#include \"a.glsl\"\nmain\n", version)),
+                ("a.glsl", "a\n".to_string()),
+            ]
+        };
+        let run_version = |version| {
+            let files = files(version);
+            let files = files.iter().map(|&(p, ref s)| (p, s.as_str())).collect::<Vec<_>>();
+            run(&files, &[("A", "1")]).unwrap()
+        };
+
+        let (output, source_map) = run_version("330");
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            [
+                "#version 330",
+                "#define A 1",
+                "#line 2 0",
+                "#line 1 1",
+                "a",
+                "#line 3 0",
+                "main",
+            ]
+        );
+
+        // Before GLSL 3.30, `#line N` makes the next line N + 1
+        let (output_150, source_map_150) = run_version("150");
+        assert_eq!(
+            output_150.lines().collect::<Vec<_>>(),
+            [
+                "#version 150",
+                "#define A 1",
+                "#line 1 0",
+                "#line 0 1",
+                "a",
+                "#line 2 0",
+                "main",
+            ]
+        );
+        assert_eq!(source_map_150, source_map);
+
+        // The lines of the original files are numbered by the driver as the source map says
+        for &(output, glsl_330) in &[(&output, true), (&output_150, false)] {
+            let driver = driver_locations(output, glsl_330);
+
+            for (i, location) in source_map.lines.iter().enumerate() {
+                if let Some(location) = *location {
+                    assert_eq!(driver[i], location, "line {} of\n{}", i + 1, output);
+                }
+            }
+        }
+
+        let location = |file: &str, line| {
+            Some(SourceLocation {
+                file: PathBuf::from(file),
+                line,
+            })
+        };
+
+        assert_eq!(source_map.location(1), location("main.glsl", 1));
+        assert_eq!(source_map.location(2), None);
+        assert_eq!(source_map.location(5), location("a.glsl", 1));
+        assert_eq!(source_map.location(7), location("main.glsl", 3));
+        assert_eq!(source_map.location(8), None);
+        assert_eq!(source_map.resolve(1, 1), location("a.glsl", 1));
+    }
+
+    #[test]
+    fn test_line_offset() {
+        assert_eq!(line_offset("110"), 1);
+        assert_eq!(line_offset("150 core"), 1);
+        assert_eq!(line_offset("330"), 0);
+        assert_eq!(line_offset("450 core // comment"), 0);
+        assert_eq!(line_offset("100 es"), 1);
+        assert_eq!(line_offset("300 es"), 0);
+    }
+
+    #[test]
+    fn test_compiler_log() {
+        let source_map = SourceMap {
+            files: vec![PathBuf::from("main.glsl"), PathBuf::from("lib.glsl")],
+            lines: Vec::new(),
+        };
+        let log = "0:12(5): error: `x' undeclared
+1(3) : error C1008: undefined variable \"y\"
+
+ERROR: 1:7: 'z' : syntax error
+5:1(1): error: unknown source
+error: no location
+";
+        let location = |file: &str, line| {
+            Some(SourceLocation {
+                file: PathBuf::from(file),
+                line,
+            })
+        };
+
+        assert_eq!(
+            source_map.map_compiler_log(log),
+            [
+                CompilerMessage {
+                    location: location("main.glsl", 12),
+                    message: "error: `x' undeclared".to_string(),
+                },
+                CompilerMessage {
+                    location: location("lib.glsl", 3),
+                    message: "error C1008: undefined variable \"y\"".to_string(),
+                },
+                CompilerMessage {
+                    location: location("lib.glsl", 7),
+                    message: "ERROR: 'z' : syntax error".to_string(),
+                },
+                CompilerMessage {
+                    location: None,
+                    message: "error: unknown source".to_string(),
+                },
+                CompilerMessage {
+                    location: None,
+                    message: "error: no location".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let files = [
+            ("main.glsl", "#include \"a.glsl\"\n"),
+            ("a.glsl", "#include \"b.glsl\"\n"),
+            ("b.glsl", "#include \"a.glsl\"\n"),
+        ];
+
+        match run(&files, &[]) {
+            Err(ShaderLoadingError::IncludeCycle(chain)) => assert_eq!(
+                format_chain(&chain),
+                "main.glsl -> a.glsl -> b.glsl -> a.glsl"
+            ),
+            other => panic!("Expected an include cycle, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_pragma_once_mutual_include() {
+        let files = [
+            ("main.glsl", "#version 150\n#include \"a.glsl\"\nmain\n"),
+            ("a.glsl", "#pragma once\n#include \"b.glsl\"\na\n"),
+            ("b.glsl", "#pragma once\n#include \"a.glsl\"\nb\n"),
+        ];
+
+        let (output, _) = run(&files, &[]).unwrap();
+        assert_eq!(code(&output), ["b", "a", "main"]);
+    }
+
+    #[test]
+    fn test_invalid_directives() {
+        let unterminated = run(&[("main.glsl", "#version 150\n#ifdef A\n")], &[]);
+        let unmatched = run(&[("main.glsl", "#version 150\n#endif\n")], &[]);
+        let else_elif = run(&[("main.glsl", "#if 1\n#else\n#elif 1\n#endif\n")], &[]);
+
+        for result in &[unterminated, unmatched, else_elif] {
+            match *result {
+                Err(ShaderLoadingError::Directive(..)) => {}
+                ref other => panic!("Expected a directive error, found {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_expressions() {
+        let defines = defines(&[("A", "2"), ("B", "A * 3"), ("EMPTY", "")]);
+
+        let cases = [
+            ("1 + 2 * 3", 7),
+            ("(1 + 2) * 3", 9),
+            ("10 / 3 % 2", 1),
+            ("0x10 - 1u", 15),
+            ("-A + ~0", -3),
+            ("B == 6 && !defined C", 1),
+            ("defined(EMPTY) || 1 / 1 == 0", 1),
+            ("1 << 4 | 1", 17),
+            // The right operand is not evaluated if the left one decides the result
+            ("defined(N) && 100 / N > 1", 0),
+            ("A || 1 % 0", 1),
+            ("0 && (B / 0 || 1)", 0),
+        ];
+
+        for &(expr, expected) in &cases {
+            assert_eq!(evaluate(expr, &defines), Ok(expected), "{}", expr);
+        }
+
+        // The ternary operator is not supported
+        assert!(evaluate("A >= 2 ? 1 : 0", &defines).is_err());
+        assert!(evaluate("EMPTY", &defines).is_err());
+        assert!(evaluate("1 / 0", &defines).is_err());
+        assert!(evaluate("1 && 1 / 0", &defines).is_err());
+        assert!(evaluate("0 && (1", &defines).is_err());
+        assert!(evaluate("(1", &defines).is_err());
+        assert!(evaluate("", &defines).is_err());
+    }
 }
@@ -0,0 +1,80 @@
+//! A virtual filesystem, which reads assets from either a directory or an archive
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use archive::{Archive, ArchiveError};
+
+/// A source of asset files
+///
+/// All paths are relative to the root of the source.
+pub enum Vfs {
+    /// A directory on disk
+    Dir(PathBuf),
+    /// An archive created by `archive::pack_dir`
+    Archive(Archive),
+}
+
+impl Vfs {
+    /// Opens the provided path, which may be either a directory or an archive
+    pub fn open(path: &Path) -> Result<Self, ArchiveError> {
+        if path.is_dir() {
+            Ok(Vfs::Dir(path.to_owned()))
+        } else {
+            Archive::open(path).map(Vfs::Archive)
+        }
+    }
+
+    /// Returns the bytes in the file at the provided path
+    pub fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match *self {
+            Vfs::Dir(ref root) => fs::read(root.join(path)),
+            Vfs::Archive(ref archive) => archive.read(path),
+        }
+    }
+
+    /// Returns whether a file exists at the provided path
+    pub fn exists(&self, path: &Path) -> bool {
+        match *self {
+            Vfs::Dir(ref root) => root.join(path).is_file(),
+            Vfs::Archive(ref archive) => archive.contains(path),
+        }
+    }
+
+    /// Returns the paths of all files
+    pub fn files(&self) -> io::Result<Vec<PathBuf>> {
+        match *self {
+            Vfs::Dir(ref root) => {
+                let mut files = Vec::new();
+                list_files(root, root, &mut files)?;
+                Ok(files)
+            }
+            Vfs::Archive(ref archive) => Ok(archive.paths()),
+        }
+    }
+
+    /// Returns whether the files are read from an archive
+    pub fn is_archive(&self) -> bool {
+        if let Vfs::Archive(_) = *self {
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Adds the paths of all files in `dir` (recursively), relative to `root`, to `files`
+fn list_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            list_files(root, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            files.push(relative.to_owned());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+//! Watching of asset directories for changes
+
+use notify::{self, DebouncedEvent, RecursiveMode, Watcher};
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// The delay used to debounce file events (editors often write a file several times when saving)
+const WATCHER_DELAY_MS: u64 = 200;
+
+/// Watches directories and their subdirectories for changed files
+pub struct AssetWatcher {
+    // NOTE: The watcher stops when dropped, so it must be stored here
+    _watcher: notify::RecommendedWatcher,
+    receiver: mpsc::Receiver<DebouncedEvent>,
+}
+
+impl AssetWatcher {
+    /// Starts watching the provided directories
+    pub fn new<I, P>(dirs: I) -> notify::Result<Self>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::watcher(sender, Duration::from_millis(WATCHER_DELAY_MS))?;
+
+        for dir in dirs {
+            watcher.watch(dir, RecursiveMode::Recursive)?;
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            receiver,
+        })
+    }
+
+    /// Returns the paths of all files that were written or created since the last call to this
+    /// function, without duplicates
+    pub fn changed_paths(&self) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+
+        for event in self.receiver.try_iter() {
+            let path = match event {
+                DebouncedEvent::Write(p) | DebouncedEvent::Create(p) => p,
+                DebouncedEvent::Rename(_, p) => p,
+                _ => continue,
+            };
+
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+
+        paths
+    }
+}
@@ -1,11 +1,14 @@
 //! Configuration of Horde Survival
 
+use config_file;
 use gfx::texture;
 use glutin;
+use ron;
+use serde::{Deserialize, Deserializer};
 use structopt::StructOpt;
 
 use std::fmt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub const DEFAULT_SENSITIVITY: ::Float = 0.0035;
 
@@ -20,9 +23,108 @@ pub struct CommandLineConfig {
 #[derive(StructOpt, Debug)]
 #[structopt(name = "horde_survival")]
 struct RawCommandLineConfig {
-    /// The path to the folder containing game assets
+    /// The path to the folder or archive (created with `pack_assets`) containing game assets
     #[structopt(long = "assets_path", parse(from_os_str))]
     assets_path: Option<PathBuf>,
+    /// Allow debug cheats (such as noclip) to be enabled in release builds
+    #[structopt(long = "enable_cheats")]
+    enable_cheats: bool,
+    /// A folder or archive containing a mod, which overrides game assets (can be repeated, with
+    /// later mods taking priority)
+    #[structopt(long = "mod", parse(from_os_str))]
+    mods: Vec<PathBuf>,
+    /// The configuration file to load and save instead of the default one
+    #[structopt(long = "config", parse(from_os_str))]
+    config_path: Option<PathBuf>,
+    /// Start with the default configuration instead of loading the configuration file (which is
+    /// backed up to `settings.ron.reset.bak`, because it is still overwritten on exit)
+    #[structopt(long = "reset-config")]
+    reset_config: bool,
+    /// Set a configuration field to a RON value, such as `graphics.shadows=true` (can be repeated)
+    #[structopt(
+        long = "set",
+        raw(number_of_values = "1"),
+        parse(try_from_str = "parse_field_override")
+    )]
+    overrides: Vec<FieldOverride>,
+    /// Don't save the fields set with `--set` to the configuration file on exit
+    #[structopt(long = "no-save-overrides")]
+    no_save_overrides: bool,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+/// A configuration field set on the command line with `--set path=value`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldOverride {
+    pub path: String,
+    pub value: String,
+}
+
+/// Parses the value of a `--set` option
+fn parse_field_override(s: &str) -> Result<FieldOverride, String> {
+    let mut split = s.splitn(2, '=');
+    let path = split.next().unwrap_or("").trim();
+    let value = match split.next() {
+        Some(v) => v.trim(),
+        None => return Err(format!("Expected `field=value`, found `{}`", s)),
+    };
+
+    if !FIELD_PATHS.contains(&path) {
+        return Err(SetFieldError::UnknownField(path.to_string()).to_string());
+    }
+
+    Ok(FieldOverride {
+        path: path.to_string(),
+        value: value.to_string(),
+    })
+}
+
+/// The fields replaced by `--set` options, along with their values before and after
+///
+/// This is used to keep the overrides out of the configuration file when it is saved.
+#[derive(Debug, Default)]
+pub struct OverriddenFields {
+    /// The path, original value, and overridden value of each field, serialized as RON
+    fields: Vec<(String, String, String)>,
+}
+
+impl OverriddenFields {
+    /// Restores the original value of each overridden field that still has its overridden value
+    ///
+    /// Fields that were changed while the game was running (such as in the options menu) keep their
+    /// new values.
+    pub fn restore(&self, config: &mut Config) {
+        for &(ref path, ref original, ref overridden) in &self.fields {
+            if config.get_field(path).as_ref() == Some(overridden) {
+                // The original value was serialized from the same field, so this can't fail
+                let _ = config.set_field(path, original);
+            }
+        }
+    }
+}
+
+quick_error! {
+    /// An error while applying the `--set` options
+    #[derive(Debug)]
+    pub enum OverrideError {
+        Field(err: SetFieldError) {
+            display("{}", err)
+            from()
+        }
+        Validation(err: ValidationError) {
+            display("Invalid configuration: {}", err)
+            from()
+        }
+    }
+}
+
+/// A command to run instead of starting the game
+#[derive(StructOpt, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Checks that all asset files (including those of mods) can be loaded, and prints any problems
+    #[structopt(name = "check-assets")]
+    CheckAssets,
 }
 
 impl CommandLineConfig {
@@ -39,19 +141,293 @@ impl CommandLineConfig {
             .clone()
             .unwrap_or_else(|| self.default_assets_path.clone())
     }
+
+    /// Returns whether the `enable_cheats` flag was set
+    pub fn enable_cheats(&self) -> bool {
+        self.config.enable_cheats
+    }
+
+    /// Returns the values of the `mod` option, in the order they were specified
+    pub fn mods(&self) -> &[PathBuf] {
+        &self.config.mods
+    }
+
+    /// Returns the command to run instead of starting the game, if one was specified
+    pub fn command(&self) -> Option<Command> {
+        self.config.command
+    }
+
+    /// Returns the value of the `config` option
+    pub fn config_path(&self) -> Option<&Path> {
+        self.config.config_path.as_ref().map(|p| p.as_path())
+    }
+
+    /// Returns whether the `reset-config` flag was set
+    pub fn reset_config(&self) -> bool {
+        self.config.reset_config
+    }
+
+    /// Returns the values of the `set` option, in the order they were specified
+    pub fn overrides(&self) -> &[FieldOverride] {
+        &self.config.overrides
+    }
+
+    /// Returns whether the fields set with the `set` option should be saved to the configuration
+    /// file
+    pub fn save_overrides(&self) -> bool {
+        !self.config.no_save_overrides
+    }
+
+    /// Sets each field given by the `set` option in the provided `Config`, and validates the result
+    ///
+    /// Later values take priority if a field is set more than once.
+    pub fn apply_overrides(&self, config: &mut Config) -> Result<OverriddenFields, OverrideError> {
+        let mut overridden = OverriddenFields::default();
+
+        for o in self.overrides() {
+            let original = config.get_field(&o.path);
+            config.set_field(&o.path, &o.value)?;
+
+            // Only the first original value is kept if a field is set more than once
+            if !overridden.fields.iter().any(|f| f.0 == o.path) {
+                if let Some(original) = original {
+                    overridden.fields.push((o.path.clone(), original, String::new()));
+                }
+            }
+        }
+
+        // The overridden values are serialized again so they are in the same format as the values
+        // they are compared to when restoring
+        for field in &mut overridden.fields {
+            field.2 = config.get_field(&field.0).unwrap_or_default();
+        }
+
+        config.validate()?;
+
+        Ok(overridden)
+    }
+
+    /// Returns the roots that assets are loaded from, from lowest to highest priority
+    ///
+    /// The base game comes first, followed by the mods in the configuration file, followed by the
+    /// mods specified on the command line.
+    pub fn asset_roots(&self, config: &Config) -> Vec<PathBuf> {
+        let mut roots = vec![self.assets_path()];
+        roots.extend(config.assets.mods.iter().cloned());
+        roots.extend(self.mods().iter().cloned());
+
+        roots
+    }
 }
 
 /// A type that holds all configuration options that can be customized in the configuration file
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Config {
+    /// The version of the configuration file layout (see the `config_file` module)
+    pub version: u32,
     pub graphics: GraphicsConfig,
     pub window: WindowConfig,
     pub camera: CameraConfig,
     pub bindings: BindConfig,
+    pub gamepad: GamepadConfig,
+    pub assets: AssetsConfig,
+    pub interface: InterfaceConfig,
+}
+
+/// A section of the `Config`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigSection {
+    Graphics,
+    Window,
+    Camera,
+    Bindings,
+    Gamepad,
+    Assets,
+    Interface,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+quick_error! {
+    /// An error caused by an invalid configuration value
+    #[derive(Debug)]
+    pub enum ValidationError {
+        WindowSize(width: u32, height: u32) {
+            display("Invalid window size: {}x{}", width, height)
+        }
+        ShadowMapSize(size: texture::Size) {
+            display("Shadow map size must be a power of two: {}", size)
+        }
+        Fov(fov: f32) {
+            display("Field of view must be between 0 and 180 degrees: {}", fov)
+        }
+        Sensitivity(sensitivity: ::Float) {
+            display("Camera sensitivity must be positive: {}", sensitivity)
+        }
+        Smoothing(smoothing: ::Float) {
+            display("Mouse smoothing must be at least 0 and less than 1: {}", smoothing)
+        }
+        Deadzone(deadzone: f32) {
+            display("Gamepad stick deadzone must be at least 0 and less than 1: {}", deadzone)
+        }
+        Curve(curve: f32) {
+            display("Gamepad stick response curve must be positive: {}", curve)
+        }
+    }
+}
+
+impl Config {
+    /// Returns an error if any configuration value is invalid
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.window.width == 0 || self.window.height == 0 {
+            return Err(ValidationError::WindowSize(
+                self.window.width,
+                self.window.height,
+            ));
+        }
+
+        if !self.graphics.shadow_map_size.is_power_of_two() {
+            return Err(ValidationError::ShadowMapSize(self.graphics.shadow_map_size));
+        }
+
+        if !(self.camera.fov > 0.0 && self.camera.fov < 180.0) {
+            return Err(ValidationError::Fov(self.camera.fov));
+        }
+
+        if !(self.camera.sensitivity > 0.0) {
+            return Err(ValidationError::Sensitivity(self.camera.sensitivity));
+        }
+
+        if !(self.camera.smoothing >= 0.0 && self.camera.smoothing < 1.0) {
+            return Err(ValidationError::Smoothing(self.camera.smoothing));
+        }
+
+        for stick in &[&self.gamepad.movement_stick, &self.gamepad.look_stick] {
+            for &deadzone in &[stick.deadzone_x, stick.deadzone_y] {
+                if !(deadzone >= 0.0 && deadzone < 1.0) {
+                    return Err(ValidationError::Deadzone(deadzone));
+                }
+            }
+
+            if !(stick.curve > 0.0) {
+                return Err(ValidationError::Curve(stick.curve));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the sections that differ between this `Config` and the provided one
+    pub fn changed_sections(&self, other: &Config) -> Vec<ConfigSection> {
+        let mut sections = Vec::new();
+
+        if self.graphics != other.graphics {
+            sections.push(ConfigSection::Graphics);
+        }
+
+        if self.window != other.window {
+            sections.push(ConfigSection::Window);
+        }
+
+        if self.camera != other.camera {
+            sections.push(ConfigSection::Camera);
+        }
+
+        if self.bindings != other.bindings {
+            sections.push(ConfigSection::Bindings);
+        }
+
+        if self.gamepad != other.gamepad {
+            sections.push(ConfigSection::Gamepad);
+        }
+
+        if self.assets != other.assets {
+            sections.push(ConfigSection::Assets);
+        }
+
+        if self.interface != other.interface {
+            sections.push(ConfigSection::Interface);
+        }
+
+        sections
+    }
+}
+
+quick_error! {
+    /// An error while setting a field of the `Config` by its path
+    #[derive(Debug)]
+    pub enum SetFieldError {
+        UnknownField(path: String) {
+            display("Unknown configuration field `{}`", path)
+        }
+        InvalidValue(path: String, err: ron::de::Error) {
+            display("Invalid value for configuration field `{}`: {}", path, err)
+        }
+    }
+}
+
+macro_rules! config_fields {
+    (
+        actions {
+            $(
+                $name:ident: $action:ident, $category:ident, $mode:ident, $default:expr
+                $(, $secondary:expr)*;
+            )*
+        }
+        $($section:ident: $variant:ident { $($field:ident,)* },)*
+    ) => {
+        config_fields! {
+            @fields
+            $($section: $variant { $($field,)* },)*
+            bindings: Bindings { $($name,)* },
+        }
+    };
+    (@fields $($section:ident: $variant:ident { $($field:ident,)* },)*) => {
+        /// The paths of all fields that can be set with `Config::set_field`
+        pub const FIELD_PATHS: &[&str] = &[
+            $($(concat!(stringify!($section), ".", stringify!($field)),)*)*
+        ];
+
+        impl Config {
+            /// Sets the field at the provided path (such as `graphics.shadows`) to the value,
+            /// which is deserialized from RON
+            ///
+            /// Returns the section of the `Config` that was changed.
+            pub fn set_field(
+                &mut self,
+                path: &str,
+                value: &str,
+            ) -> Result<ConfigSection, SetFieldError> {
+                match path {
+                    $($(
+                        concat!(stringify!($section), ".", stringify!($field)) => {
+                            self.$section.$field = ron::de::from_str(value).map_err(|e| {
+                                SetFieldError::InvalidValue(path.to_string(), e)
+                            })?;
+
+                            Ok(ConfigSection::$variant)
+                        }
+                    )*)*
+                    _ => Err(SetFieldError::UnknownField(path.to_string())),
+                }
+            }
+
+            /// Returns the value of the field at the provided path serialized as RON, or `None`
+            /// if there is no field at that path
+            pub fn get_field(&self, path: &str) -> Option<String> {
+                match path {
+                    $($(
+                        concat!(stringify!($section), ".", stringify!($field)) => {
+                            ron::ser::to_string(&self.$section.$field).ok()
+                        }
+                    )*)*
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(default)]
 pub struct GraphicsConfig {
     pub postprocessing: bool,
@@ -60,86 +436,196 @@ pub struct GraphicsConfig {
     pub particles: bool,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+/// A set of graphics settings that are changed together
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphicsPreset {
+    Low,
+    Medium,
+    High,
+    Ultra,
+    /// The graphics settings don't match any preset, because an individual setting was changed
+    Custom,
+}
+
+impl GraphicsPreset {
+    /// All presets except `Custom`, from the lowest quality to the highest
+    pub const ALL: [GraphicsPreset; 4] = [
+        GraphicsPreset::Low,
+        GraphicsPreset::Medium,
+        GraphicsPreset::High,
+        GraphicsPreset::Ultra,
+    ];
+
+    /// Returns the graphics settings of the preset, or `None` for `Custom`
+    // NOTE: If a setting is added to `GraphicsConfig`, set it in each preset here too
+    pub fn settings(self) -> Option<GraphicsConfig> {
+        match self {
+            GraphicsPreset::Low => Some(GraphicsConfig {
+                postprocessing: false,
+                shadows: false,
+                shadow_map_size: 1024,
+                particles: false,
+            }),
+            GraphicsPreset::Medium => Some(GraphicsConfig {
+                postprocessing: false,
+                shadows: true,
+                shadow_map_size: 1024,
+                particles: true,
+            }),
+            GraphicsPreset::High => Some(GraphicsConfig {
+                postprocessing: true,
+                shadows: true,
+                shadow_map_size: 2048,
+                particles: true,
+            }),
+            GraphicsPreset::Ultra => Some(GraphicsConfig {
+                postprocessing: true,
+                shadows: true,
+                shadow_map_size: 4096,
+                particles: true,
+            }),
+            GraphicsPreset::Custom => None,
+        }
+    }
+
+    /// Returns the name of the preset in lowercase, which is used in localization keys
+    pub fn name(self) -> &'static str {
+        match self {
+            GraphicsPreset::Low => "low",
+            GraphicsPreset::Medium => "medium",
+            GraphicsPreset::High => "high",
+            GraphicsPreset::Ultra => "ultra",
+            GraphicsPreset::Custom => "custom",
+        }
+    }
+}
+
+impl GraphicsConfig {
+    /// Returns the preset that these settings match, or `GraphicsPreset::Custom` if they don't
+    /// match any preset
+    pub fn preset(&self) -> GraphicsPreset {
+        GraphicsPreset::ALL
+            .iter()
+            .cloned()
+            .find(|p| p.settings().as_ref() == Some(self))
+            .unwrap_or(GraphicsPreset::Custom)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(default)]
 pub struct WindowConfig {
     pub width: u32,
     pub height: u32,
     pub fullscreen: bool,
     pub vsync: bool,
+    /// Whether to pause the game when the window loses focus, instead of continuing to simulate it
+    /// in the background
+    pub pause_on_focus_loss: bool,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(default)]
 pub struct CameraConfig {
+    /// The rotation in radians per unit of raw mouse motion, which doesn't depend on the window
+    /// size or DPI
     pub sensitivity: ::Float,
     pub fov: f32,
+    /// Whether moving the mouse up looks down
+    pub invert_y: bool,
+    /// The fraction of the mouse motion of each frame that is carried over to the next frame, from
+    /// 0 (no smoothing) to less than 1
+    pub smoothing: ::Float,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(default)]
-pub struct BindConfig {
-    pub move_forward: Bind,
-    pub move_backward: Bind,
-    pub move_left: Bind,
-    pub move_right: Bind,
-    pub jump: Bind,
-    pub reload_shaders: Bind,
+pub struct GamepadConfig {
+    /// Whether gamepad input is used
+    pub enabled: bool,
+    /// The stick that moves the player
+    pub movement_stick: StickConfig,
+    /// The stick that rotates the camera, where the sensitivity is in radians per second
+    pub look_stick: StickConfig,
+    /// The button that pauses and unpauses the game, like Escape on the keyboard
+    ///
+    /// The other buttons are bound to actions in the `bindings` section.
+    pub pause: GamepadButton,
 }
 
-#[derive(Clone)]
-pub enum BindName {
-    MoveForward,
-    MoveBackward,
-    MoveLeft,
-    MoveRight,
-    Jump,
+/// The response of an analog stick
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StickConfig {
+    /// Horizontal values with a smaller magnitude than this (from 0 to 1) are ignored
+    pub deadzone_x: f32,
+    /// Vertical values with a smaller magnitude than this (from 0 to 1) are ignored
+    pub deadzone_y: f32,
+    /// The exponent applied to values outside the deadzone, where values above 1 give more
+    /// precision near the center of the stick
+    pub curve: f32,
+    /// The multiplier applied to values after the response curve
+    pub sensitivity: f32,
+    /// Whether to invert the vertical axis
+    pub invert_y: bool,
 }
 
-impl BindConfig {
-    pub fn set(&mut self, name: BindName, value: Bind) {
-        *self.get_mut(name) = value;
-    }
+/// A gamepad button that can be bound to an action, named by its position on the gamepad
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    LeftTrigger,
+    RightBumper,
+    RightTrigger,
+    Select,
+    Start,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
 
-    pub fn get_mut(&mut self, name: BindName) -> &mut Bind {
-        match name {
-            BindName::MoveForward => &mut self.move_forward,
-            BindName::MoveLeft => &mut self.move_left,
-            BindName::MoveRight => &mut self.move_right,
-            BindName::MoveBackward => &mut self.move_backward,
-            BindName::Jump => &mut self.jump,
-        }
-    }
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AssetsConfig {
+    /// Folders or archives containing mods, which override game assets (later mods take priority)
+    ///
+    /// Changes take effect after restarting the game.
+    pub mods: Vec<PathBuf>,
+}
 
-    /// Returns whether the provided `Bind` is already assigned to an action
-    pub fn is_in_use(&self, bind: &Bind) -> bool {
-        // NOTE: If new binds are added, add them here as well
-        let binds = [
-            &self.move_forward,
-            &self.move_left,
-            &self.move_right,
-            &self.move_backward,
-            &self.jump,
-        ];
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InterfaceConfig {
+    /// The code of the language of the UI text (such as `en`), which names the string table in the
+    /// `lang` asset directory
+    pub language: String,
+}
 
-        for b in &binds {
-            if **b == *bind {
-                return true;
-            }
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: config_file::CONFIG_VERSION,
+            graphics: Default::default(),
+            window: Default::default(),
+            camera: Default::default(),
+            bindings: Default::default(),
+            gamepad: Default::default(),
+            assets: Default::default(),
+            interface: Default::default(),
         }
-
-        false
     }
 }
 
 impl Default for GraphicsConfig {
     fn default() -> Self {
-        Self {
-            postprocessing: false,
-            shadows: false,
-            shadow_map_size: 1024,
-            particles: false,
-        }
+        GraphicsPreset::Low.settings().unwrap()
     }
 }
 
@@ -150,36 +636,49 @@ impl Default for WindowConfig {
             height: 600,
             fullscreen: false,
             vsync: false,
+            pause_on_focus_loss: true,
         }
     }
 }
-impl Default for BindConfig {
+impl Default for GamepadConfig {
     fn default() -> Self {
         Self {
-            move_forward: Bind {
-                modifiers: Default::default(),
-                key: Key::W,
+            enabled: true,
+            movement_stick: StickConfig {
+                deadzone_x: 0.15,
+                deadzone_y: 0.15,
+                curve: 1.0,
+                sensitivity: 1.0,
+                invert_y: false,
             },
-            move_backward: Bind {
-                modifiers: Default::default(),
-                key: Key::S,
-            },
-            move_left: Bind {
-                modifiers: Default::default(),
-                key: Key::A,
-            },
-            move_right: Bind {
-                modifiers: Default::default(),
-                key: Key::D,
-            },
-            jump: Bind {
-                modifiers: Default::default(),
-                key: Key::Space,
-            },
-            reload_shaders: Bind {
-                modifiers: Default::default(),
-                key: Key::F1,
+            look_stick: StickConfig {
+                deadzone_x: 0.1,
+                deadzone_y: 0.1,
+                curve: 2.0,
+                sensitivity: 3.0,
+                invert_y: false,
             },
+            pause: GamepadButton::Start,
+        }
+    }
+}
+
+impl Default for StickConfig {
+    fn default() -> Self {
+        Self {
+            deadzone_x: 0.1,
+            deadzone_y: 0.1,
+            curve: 1.0,
+            sensitivity: 1.0,
+            invert_y: false,
+        }
+    }
+}
+
+impl Default for InterfaceConfig {
+    fn default() -> Self {
+        Self {
+            language: "en".to_string(),
         }
     }
 }
@@ -189,6 +688,8 @@ impl Default for CameraConfig {
         Self {
             sensitivity: DEFAULT_SENSITIVITY,
             fov: 45.0,
+            invert_y: false,
+            smoothing: 0.0,
         }
     }
 }
@@ -232,16 +733,115 @@ impl Into<glutin::ModifiersState> for ModifiersState {
     }
 }
 
-#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(default)]
+/// A mouse button that can be bound to an action
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    /// An extra button (such as the side buttons of some mice), identified by its number
+    Extra(u8),
+}
+
+impl From<glutin::MouseButton> for MouseButton {
+    fn from(button: glutin::MouseButton) -> Self {
+        match button {
+            glutin::MouseButton::Left => MouseButton::Left,
+            glutin::MouseButton::Right => MouseButton::Right,
+            glutin::MouseButton::Middle => MouseButton::Middle,
+            glutin::MouseButton::Other(n) => MouseButton::Extra(n),
+        }
+    }
+}
+
+/// The input that triggers a `Bind`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Input {
+    Key(Key),
+    Mouse(MouseButton),
+    /// The mouse wheel was scrolled up, which is a press that is released immediately
+    WheelUp,
+    /// The mouse wheel was scrolled down, which is a press that is released immediately
+    WheelDown,
+    /// A gamepad button, which has no modifiers
+    Gamepad(GamepadButton),
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Input::Key(Default::default())
+    }
+}
+
+impl From<Key> for Input {
+    fn from(key: Key) -> Self {
+        Input::Key(key)
+    }
+}
+
+impl From<GamepadButton> for Input {
+    fn from(button: GamepadButton) -> Self {
+        Input::Gamepad(button)
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Input::Key(ref key) => write!(fmt, "{:?}", key),
+            Input::Mouse(MouseButton::Left) => write!(fmt, "Left Mouse"),
+            Input::Mouse(MouseButton::Right) => write!(fmt, "Right Mouse"),
+            Input::Mouse(MouseButton::Middle) => write!(fmt, "Middle Mouse"),
+            Input::Mouse(MouseButton::Extra(n)) => write!(fmt, "Mouse {}", n),
+            Input::WheelUp => write!(fmt, "Wheel Up"),
+            Input::WheelDown => write!(fmt, "Wheel Down"),
+            Input::Gamepad(button) => write!(fmt, "Gamepad {:?}", button),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
 pub struct Bind {
-    pub key: Key,
+    pub input: Input,
     pub modifiers: ModifiersState,
 }
 
 impl Bind {
-    pub fn new(key: Key, modifiers: ModifiersState) -> Self {
-        Self { key, modifiers }
+    pub fn new<I: Into<Input>>(input: I, modifiers: ModifiersState) -> Self {
+        Self {
+            input: input.into(),
+            modifiers,
+        }
+    }
+}
+
+/// A `Bind` as it is written in the configuration file
+///
+/// Binds used to only support keys, which were stored in a `key` field instead of `input`, so both
+/// fields are accepted.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct RawBind {
+    input: Option<Input>,
+    key: Option<Key>,
+    modifiers: ModifiersState,
+}
+
+impl<'de> Deserialize<'de> for Bind {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = RawBind::deserialize(deserializer)?;
+        let input = raw
+            .input
+            .or_else(|| raw.key.map(Input::Key))
+            .unwrap_or_default();
+
+        Ok(Self {
+            input,
+            modifiers: raw.modifiers,
+        })
     }
 }
 
@@ -251,7 +851,7 @@ impl fmt::Display for Bind {
         const SHIFT_PREFIX: &str = "Shift+";
         const ALT_PREFIX: &str = "Alt+";
 
-        let key_string = format!("{:?}", self.key);
+        let key_string = self.input.to_string();
 
         let mut string = String::with_capacity(
             key_string.len()
@@ -278,6 +878,261 @@ impl fmt::Display for Bind {
     }
 }
 
+/// The group of actions that an action is listed under in the options menu
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActionCategory {
+    Movement,
+    Interface,
+    Debug,
+}
+
+impl ActionCategory {
+    /// Returns the name of the category in lowercase, which is used in localization keys
+    pub fn name(self) -> &'static str {
+        match self {
+            ActionCategory::Movement => "movement",
+            ActionCategory::Interface => "interface",
+            ActionCategory::Debug => "debug",
+        }
+    }
+}
+
+/// How the binds of an action control it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActionMode {
+    /// The action happens once when a bind is pressed
+    Press,
+    /// The action is active while a bind is held down
+    Hold,
+    /// The action is activated by pressing a bind, and deactivated by pressing it again
+    Toggle,
+}
+
+/// The registry entry of an action
+#[derive(Clone, Copy, Debug)]
+pub struct ActionInfo {
+    pub action: Action,
+    /// The name of the action's field in the `bindings` section, which is also used in
+    /// localization keys
+    pub name: &'static str,
+    pub category: ActionCategory,
+    pub mode: ActionMode,
+}
+
+/// Which of the binds of an action to use
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BindSlot {
+    Primary,
+    Secondary,
+}
+
+/// The binds of an action, either of which triggers it
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActionBinds {
+    pub primary: Option<Bind>,
+    pub secondary: Option<Bind>,
+}
+
+impl ActionBinds {
+    /// Returns `ActionBinds` with the provided primary bind and no secondary bind
+    pub fn new(primary: Bind) -> Self {
+        Self {
+            primary: Some(primary),
+            secondary: None,
+        }
+    }
+
+    /// Sets the secondary bind
+    pub fn with_secondary(mut self, secondary: Bind) -> Self {
+        self.secondary = Some(secondary);
+        self
+    }
+
+    /// Returns the bind in the provided slot
+    pub fn get(&self, slot: BindSlot) -> Option<&Bind> {
+        match slot {
+            BindSlot::Primary => self.primary.as_ref(),
+            BindSlot::Secondary => self.secondary.as_ref(),
+        }
+    }
+
+    /// Returns a mutable reference to the bind in the provided slot
+    pub fn get_mut(&mut self, slot: BindSlot) -> &mut Option<Bind> {
+        match slot {
+            BindSlot::Primary => &mut self.primary,
+            BindSlot::Secondary => &mut self.secondary,
+        }
+    }
+
+    /// Returns the slot of the bind that is equal to the provided one, including its modifiers
+    pub fn slot_of(&self, bind: &Bind) -> Option<BindSlot> {
+        [BindSlot::Primary, BindSlot::Secondary]
+            .iter()
+            .cloned()
+            .find(|&s| self.get(s) == Some(bind))
+    }
+
+    /// Returns the bind whose input is the provided one, regardless of its modifiers
+    pub fn with_input(&self, input: &Input) -> Option<&Bind> {
+        self.primary
+            .iter()
+            .chain(self.secondary.iter())
+            .find(|b| b.input == *input)
+    }
+}
+
+macro_rules! actions {
+    (
+        actions {
+            $(
+                $name:ident: $action:ident, $category:ident, $mode:ident, $default:expr
+                $(, $secondary:expr)*;
+            )*
+        }
+    ) => {
+        /// An action that can be bound to inputs
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum Action {
+            $($action,)*
+        }
+
+        /// The registry of all actions, in the order they are listed in the options menu
+        pub const ACTIONS: &[ActionInfo] = &[
+            $(ActionInfo {
+                action: Action::$action,
+                name: stringify!($name),
+                category: ActionCategory::$category,
+                mode: ActionMode::$mode,
+            },)*
+        ];
+
+        /// The binds of each action
+        #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(default)]
+        pub struct BindConfig {
+            $(pub $name: ActionBinds,)*
+        }
+
+        impl BindConfig {
+            /// Returns the binds of the provided action
+            pub fn get(&self, action: Action) -> &ActionBinds {
+                match action {
+                    $(Action::$action => &self.$name,)*
+                }
+            }
+
+            /// Returns a mutable reference to the binds of the provided action
+            pub fn get_mut(&mut self, action: Action) -> &mut ActionBinds {
+                match action {
+                    $(Action::$action => &mut self.$name,)*
+                }
+            }
+        }
+
+        impl Default for BindConfig {
+            fn default() -> Self {
+                Self {
+                    $(
+                        $name: ActionBinds::new(Bind::new($default, Default::default()))
+                            $(.with_secondary(Bind::new($secondary, Default::default())))*,
+                    )*
+                }
+            }
+        }
+    }
+}
+
+/// Invokes the provided macro with the registry of all actions added to the start of its input,
+/// so everything that lists the actions is generated from this list
+///
+/// Each action has a default primary bind, and optionally a default secondary bind (which is used
+/// for gamepad buttons).
+// NOTE: Each action also needs an `options.bind.<name>` string in the localization table
+macro_rules! action_registry {
+    ($callback:ident! { $($input:tt)* }) => {
+        $callback! {
+            actions {
+                move_forward: MoveForward, Movement, Hold, Key::W;
+                move_backward: MoveBackward, Movement, Hold, Key::S;
+                move_left: MoveLeft, Movement, Hold, Key::A;
+                move_right: MoveRight, Movement, Hold, Key::D;
+                jump: Jump, Movement, Hold, Key::Space, GamepadButton::South;
+                sprint: Sprint, Movement, Hold, Key::E, GamepadButton::LeftStick;
+                crouch: Crouch, Movement, Toggle, Key::C, GamepadButton::East;
+                dash: Dash, Movement, Press, Key::Q, GamepadButton::West;
+                ascend: Ascend, Movement, Hold, Key::R, GamepadButton::RightBumper;
+                descend: Descend, Movement, Hold, Key::F, GamepadButton::LeftBumper;
+                toggle_console: ToggleConsole, Interface, Press, Key::Grave;
+                reload_shaders: ReloadShaders, Debug, Press, Key::F1;
+            }
+            $($input)*
+        }
+    };
+}
+
+action_registry! {
+    actions! {}
+}
+
+// NOTE: If new configuration fields are added, add them here as well. The fields of the `bindings`
+// section are generated from the action registry.
+action_registry! {
+    config_fields! {
+        graphics: Graphics {
+            postprocessing,
+            shadows,
+            shadow_map_size,
+            particles,
+        },
+        window: Window {
+            width,
+            height,
+            fullscreen,
+            vsync,
+            pause_on_focus_loss,
+        },
+        camera: Camera {
+            sensitivity,
+            fov,
+            invert_y,
+            smoothing,
+        },
+        gamepad: Gamepad {
+            enabled,
+            movement_stick,
+            look_stick,
+            pause,
+        },
+        assets: Assets {
+            mods,
+        },
+        interface: Interface {
+            language,
+        },
+    }
+}
+
+impl Action {
+    /// Returns the registry entry of the action
+    pub fn info(self) -> &'static ActionInfo {
+        ACTIONS
+            .iter()
+            .find(|a| a.action == self)
+            .expect("Action missing from registry")
+    }
+}
+
+impl BindConfig {
+    /// Returns the action and slot that the provided `Bind` is assigned to, if any
+    pub fn find(&self, bind: &Bind) -> Option<(Action, BindSlot)> {
+        ACTIONS
+            .iter()
+            .filter_map(|a| self.get(a.action).slot_of(bind).map(|slot| (a.action, slot)))
+            .next()
+    }
+}
+
 macro_rules! make_key_struct {
     (default = $default:ident, $($key:ident),*,) => {
         #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -479,3 +1334,204 @@ make_key_struct! {
     Paste,
     Cut,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_field() {
+        let mut config = Config::default();
+
+        assert_eq!(
+            config.set_field("graphics.shadows", "true").unwrap(),
+            ConfigSection::Graphics
+        );
+        assert!(config.graphics.shadows);
+
+        assert_eq!(
+            config.set_field("window.width", "1920").unwrap(),
+            ConfigSection::Window
+        );
+        assert_eq!(config.window.width, 1920);
+
+        config
+            .set_field(
+                "bindings.jump",
+                "(primary: Some((input: Key(J), modifiers: (shift: true))))",
+            ).unwrap();
+        let jump = config.bindings.jump.primary.unwrap();
+        assert_eq!(jump.input, Input::Key(Key::J));
+        assert!(jump.modifiers.shift);
+        assert_eq!(config.bindings.jump.secondary, None);
+
+        assert_eq!(
+            config.set_field("assets.mods", r#"["mods/a", "mods/b.hsa"]"#).unwrap(),
+            ConfigSection::Assets
+        );
+        assert_eq!(
+            config.assets.mods,
+            vec![PathBuf::from("mods/a"), PathBuf::from("mods/b.hsa")]
+        );
+    }
+
+    #[test]
+    fn test_set_field_errors() {
+        let mut config = Config::default();
+
+        match config.set_field("graphics.missing", "true") {
+            Err(SetFieldError::UnknownField(_)) => {}
+            other => panic!("Expected `UnknownField`, found {:?}", other),
+        }
+
+        match config.set_field("window.width", "wide") {
+            Err(SetFieldError::InvalidValue(..)) => {}
+            other => panic!("Expected `InvalidValue`, found {:?}", other),
+        }
+
+        // The field should be unchanged after an error
+        assert_eq!(config.window.width, WindowConfig::default().width);
+    }
+
+    #[test]
+    fn test_parse_field_override() {
+        assert_eq!(
+            parse_field_override("window.width=1920"),
+            Ok(FieldOverride {
+                path: "window.width".to_string(),
+                value: "1920".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_field_override("interface.language=\"a=b\"").unwrap().value,
+            "\"a=b\""
+        );
+        assert!(parse_field_override("graphics.shadows").is_err());
+        assert!(parse_field_override("graphics.missing=true").is_err());
+    }
+
+    #[test]
+    fn test_restore_overridden_fields() {
+        let mut config = Config::default();
+        let overridden = OverriddenFields {
+            fields: vec![
+                (
+                    "window.width".to_string(),
+                    config.get_field("window.width").unwrap(),
+                    "1920".to_string(),
+                ),
+                (
+                    "window.height".to_string(),
+                    config.get_field("window.height").unwrap(),
+                    "1080".to_string(),
+                ),
+            ],
+        };
+        config.set_field("window.width", "1920").unwrap();
+        // Changed after being overridden, so it should be kept
+        config.set_field("window.height", "900").unwrap();
+
+        overridden.restore(&mut config);
+
+        assert_eq!(config.window.width, WindowConfig::default().width);
+        assert_eq!(config.window.height, 900);
+    }
+
+    #[test]
+    fn test_graphics_presets() {
+        for &preset in &GraphicsPreset::ALL {
+            let settings = preset.settings().unwrap();
+            assert_eq!(settings.preset(), preset);
+
+            let mut config = Config::default();
+            config.graphics = settings;
+            assert!(config.validate().is_ok());
+        }
+
+        let mut custom = GraphicsPreset::High.settings().unwrap();
+        custom.particles = false;
+        assert_eq!(custom.preset(), GraphicsPreset::Custom);
+        assert_eq!(GraphicsPreset::Custom.settings(), None);
+    }
+
+    #[test]
+    fn test_bind_serialization() {
+        let binds = [
+            Bind::new(Key::W, Default::default()),
+            Bind::new(Input::Mouse(MouseButton::Extra(4)), Default::default()),
+            Bind::new(
+                Input::WheelDown,
+                ModifiersState {
+                    ctrl: true,
+                    ..Default::default()
+                },
+            ),
+        ];
+
+        for bind in &binds {
+            let serialized = ron::ser::to_string(bind).unwrap();
+            assert_eq!(ron::de::from_str::<Bind>(&serialized).unwrap(), *bind);
+        }
+    }
+
+    #[test]
+    fn test_old_bind_format() {
+        let bind: Bind = ron::de::from_str("(key: Space, modifiers: (alt: true))").unwrap();
+        assert_eq!(bind.input, Input::Key(Key::Space));
+        assert!(bind.modifiers.alt);
+
+        let bind: Bind = ron::de::from_str("(input: Mouse(Right))").unwrap();
+        assert_eq!(bind.input, Input::Mouse(MouseButton::Right));
+        assert_eq!(bind.modifiers, ModifiersState::default());
+    }
+
+    #[test]
+    fn test_action_registry() {
+        let config = Config::default();
+
+        for info in ACTIONS {
+            assert_eq!(info.action.info().name, info.name);
+
+            let path = format!("bindings.{}", info.name);
+            assert!(FIELD_PATHS.contains(&path.as_str()), "{} is not a field", path);
+
+            // Each default bind is only used by one action
+            let primary = config.bindings.get(info.action).primary.clone().unwrap();
+            assert_eq!(
+                config.bindings.find(&primary),
+                Some((info.action, BindSlot::Primary))
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = Config::default();
+        assert!(config.validate().is_ok());
+
+        config.graphics.shadow_map_size = 1000;
+        assert!(config.validate().is_err());
+
+        config = Config::default();
+        config.camera.fov = 0.0;
+        assert!(config.validate().is_err());
+
+        config = Config::default();
+        config.camera.smoothing = 1.0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_changed_sections() {
+        let a = Config::default();
+        let mut b = a.clone();
+        assert!(a.changed_sections(&b).is_empty());
+
+        b.window.vsync = !b.window.vsync;
+        b.camera.fov += 1.0;
+        assert_eq!(
+            a.changed_sections(&b),
+            vec![ConfigSection::Window, ConfigSection::Camera]
+        );
+    }
+}
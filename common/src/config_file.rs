@@ -0,0 +1,695 @@
+//! Reading and migrating the configuration file
+//!
+//! The configuration file stores the version of its layout in the `version` field. When the layout
+//! changes (such as when a field is renamed or moved), `CONFIG_VERSION` is incremented and a
+//! migration is added to `MIGRATIONS`, which upgrades files written with the previous layout.
+//!
+//! Migrations work on a loosely parsed form of the file, in which each section is a list of fields
+//! whose values are kept as RON source text. This form is also used to apply each field separately
+//! with `Config::set_field`, so a field with an invalid value is reported and reset to its default
+//! without affecting the rest of the file, and unknown fields are reported instead of being
+//! silently ignored.
+
+use ron;
+
+use std::fmt;
+
+use config::{self, Config, SetFieldError, ValidationError, FIELD_PATHS};
+
+/// The version of the configuration file layout that this version of the game writes
+pub const CONFIG_VERSION: u32 = 3;
+
+/// A function that upgrades a configuration file from one version to the next
+type Migration = fn(&mut RawConfig) -> Result<(), String>;
+
+/// The migrations between each version, where the migration at index `n` upgrades a file from
+/// version `n` to version `n + 1`
+// NOTE: If `CONFIG_VERSION` is incremented, add a migration from the previous version here
+const MIGRATIONS: &[Migration] = &[migrate_v0, migrate_v1, migrate_v2];
+
+/// Version 0 files are from before the `version` field existed, and otherwise have the same layout
+/// as version 1
+fn migrate_v0(_: &mut RawConfig) -> Result<(), String> {
+    Ok(())
+}
+
+/// Version 2 replaced the single bind of each action with a primary and a secondary bind, so each
+/// bind becomes the primary bind of its action
+fn migrate_v1(raw: &mut RawConfig) -> Result<(), String> {
+    for field in &mut raw.section("bindings").fields {
+        field.1 = format!("(primary: Some({}))", field.1);
+    }
+
+    Ok(())
+}
+
+/// Version 3 replaced the `gamepad.jump` button with gamepad binds in the `bindings` section, so
+/// actions without a secondary bind get their default gamepad bind (or the old jump button)
+fn migrate_v2(raw: &mut RawConfig) -> Result<(), String> {
+    let jump = raw
+        .section("gamepad")
+        .remove("jump")
+        .and_then(|b| ron::de::from_str(&b).ok())
+        .map(|b: config::GamepadButton| config::Bind::new(b, Default::default()));
+    let defaults = config::BindConfig::default();
+    let bindings = raw.section("bindings");
+
+    for info in config::ACTIONS {
+        let default = defaults.get(info.action);
+        let secondary = match jump {
+            Some(ref jump) if info.action == config::Action::Jump => jump.clone(),
+            _ => match default.secondary {
+                Some(ref s) => s.clone(),
+                None => continue,
+            },
+        };
+
+        let mut binds = match bindings.get(info.name) {
+            // Invalid binds are left for `load` to report
+            Some(value) => match ron::de::from_str::<config::ActionBinds>(value) {
+                Ok(b) => b,
+                Err(_) => continue,
+            },
+            None => default.clone(),
+        };
+
+        // Actions that aren't in the file already have their default binds, which only change if
+        // the old jump button is used instead
+        if binds.secondary.is_none() || binds == *default {
+            binds.secondary = Some(secondary);
+            let value = ron::ser::to_string(&binds).map_err(|e| e.to_string())?;
+            bindings.insert(info.name, value);
+        }
+    }
+
+    Ok(())
+}
+
+quick_error! {
+    /// An error that prevents the configuration file from being loaded
+    #[derive(Debug)]
+    pub enum ConfigFileError {
+        Syntax(err: SyntaxError) {
+            display("Syntax error in configuration file: {}", err)
+            from()
+        }
+        InvalidVersion(value: String) {
+            display("Invalid configuration file version: {}", value)
+        }
+        NewerVersion(version: u32) {
+            display(
+                "Configuration file version {} is newer than the supported version {}",
+                version,
+                CONFIG_VERSION
+            )
+        }
+        Migration(version: u32, message: String) {
+            display(
+                "Error migrating configuration file from version {}: {}",
+                version,
+                message
+            )
+        }
+    }
+}
+
+quick_error! {
+    /// A problem with part of the configuration file, which was ignored or reset to its default
+    #[derive(Debug)]
+    pub enum FieldWarning {
+        Field(err: SetFieldError) {
+            display("{}", err)
+            from()
+        }
+        NotAStruct(section: String) {
+            display("Configuration section `{}` is not a struct", section)
+        }
+        Invalid(path: String, err: ValidationError) {
+            display("Invalid value for configuration field `{}`: {}", path, err)
+        }
+    }
+}
+
+/// A configuration file that was loaded
+#[derive(Debug)]
+pub struct LoadedConfig {
+    pub config: Config,
+    /// The version of the file, which is older than `CONFIG_VERSION` if it was migrated
+    pub version: u32,
+    /// Problems with fields of the file
+    pub warnings: Vec<FieldWarning>,
+}
+
+impl LoadedConfig {
+    /// Returns whether the file was written with an older layout and was migrated
+    pub fn migrated(&self) -> bool {
+        self.version < CONFIG_VERSION
+    }
+}
+
+/// Loads a `Config` from the contents of a configuration file, migrating it if it is from an older
+/// version
+pub fn load(data: &str) -> Result<LoadedConfig, ConfigFileError> {
+    let mut raw = RawStruct::parse(data)?;
+
+    let version = match raw.remove("version") {
+        Some(v) => v
+            .parse()
+            .map_err(|_| ConfigFileError::InvalidVersion(v))?,
+        None => 0,
+    };
+
+    if version > CONFIG_VERSION {
+        return Err(ConfigFileError::NewerVersion(version));
+    }
+
+    let mut warnings = Vec::new();
+    let mut sections = Vec::new();
+
+    for (name, value) in raw.fields {
+        if !is_section(&name) {
+            warnings.push(SetFieldError::UnknownField(name).into());
+            continue;
+        }
+
+        match RawStruct::parse(&value) {
+            Ok(section) => sections.push((name, section)),
+            Err(_) => warnings.push(FieldWarning::NotAStruct(name)),
+        }
+    }
+
+    let mut raw_config = RawConfig { sections };
+
+    for (v, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(&mut raw_config).map_err(|e| ConfigFileError::Migration(v as u32, e))?;
+    }
+
+    let mut config = Config::default();
+
+    for (section, fields) in raw_config.sections {
+        for (field, value) in fields.fields {
+            let path = format!("{}.{}", section, field);
+            let previous = config.get_field(&path);
+
+            if let Err(e) = config.set_field(&path, &value) {
+                warnings.push(e.into());
+                continue;
+            }
+
+            // The configuration was valid before this field was set, so if it is invalid now, this
+            // field is the cause
+            if let Err(e) = config.validate() {
+                if let Some(previous) = previous {
+                    config
+                        .set_field(&path, &previous)
+                        .expect("Failed to restore configuration field");
+                }
+
+                warnings.push(FieldWarning::Invalid(path, e));
+            }
+        }
+    }
+
+    Ok(LoadedConfig {
+        config,
+        version,
+        warnings,
+    })
+}
+
+/// Returns whether a section with the provided name exists
+fn is_section(name: &str) -> bool {
+    FIELD_PATHS
+        .iter()
+        .any(|p| p.split('.').next() == Some(name))
+}
+
+/// The sections of a configuration file, as they are seen by migrations
+#[derive(Debug, Default)]
+struct RawConfig {
+    sections: Vec<(String, RawStruct)>,
+}
+
+impl RawConfig {
+    /// Returns the section with the provided name, adding it if it doesn't exist
+    fn section(&mut self, name: &str) -> &mut RawStruct {
+        let index = self.sections.iter().position(|&(ref n, _)| n == name);
+
+        match index {
+            Some(i) => &mut self.sections[i].1,
+            None => {
+                self.sections.push((name.to_string(), RawStruct::default()));
+                &mut self.sections.last_mut().unwrap().1
+            }
+        }
+    }
+
+    /// Moves a field (given by a path such as `bindings.forward`) to another path, if it exists
+    #[allow(dead_code)]
+    fn move_field(&mut self, from: &str, to: &str) {
+        let (from_section, from_field) = split_path(from);
+        let (to_section, to_field) = split_path(to);
+
+        let value = self.section(from_section).remove(from_field);
+
+        if let Some(value) = value {
+            self.section(to_section).insert(to_field, value);
+        }
+    }
+}
+
+/// Splits a field path into its section and field names
+fn split_path(path: &str) -> (&str, &str) {
+    let mut split = path.splitn(2, '.');
+    let section = split.next().unwrap_or("");
+    let field = split.next().unwrap_or("");
+
+    (section, field)
+}
+
+/// A syntax error in the configuration file
+#[derive(Debug, PartialEq)]
+pub struct SyntaxError {
+    line: usize,
+    column: usize,
+    message: &'static str,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// A RON struct whose field values are kept as source text
+#[derive(Clone, Debug, Default, PartialEq)]
+struct RawStruct {
+    fields: Vec<(String, String)>,
+}
+
+impl RawStruct {
+    /// Parses a RON struct, such as `(a: 1, b: (c: true))` or `Name(a: 1)`
+    ///
+    /// Only the outer struct is parsed, so the values of its fields can have any syntax.
+    fn parse(source: &str) -> Result<Self, SyntaxError> {
+        let mut parser = Parser { source, pos: 0 };
+        let mut fields = Vec::new();
+
+        parser.skip_whitespace();
+        // Struct names are optional
+        parser.identifier();
+        parser.skip_whitespace();
+        parser.expect('(', "Expected `(`")?;
+
+        loop {
+            parser.skip_whitespace();
+
+            if parser.eat(')') {
+                break;
+            }
+
+            let name = parser
+                .identifier()
+                .ok_or_else(|| parser.error("Expected field name"))?;
+
+            parser.skip_whitespace();
+            parser.expect(':', "Expected `:`")?;
+
+            let value = parser.value()?;
+
+            if value.is_empty() {
+                return Err(parser.error("Expected value"));
+            }
+
+            fields.push((name.to_string(), value.to_string()));
+
+            if !parser.eat(',') {
+                parser.expect(')', "Expected `,` or `)`")?;
+                break;
+            }
+        }
+
+        parser.skip_whitespace();
+
+        if parser.pos < source.len() {
+            return Err(parser.error("Unexpected characters after struct"));
+        }
+
+        Ok(Self { fields })
+    }
+
+    /// Returns the value of the field with the provided name
+    fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|field| field.0 == name)
+            .map(|field| &field.1[..])
+    }
+
+    /// Removes the field with the provided name, returning its value
+    fn remove(&mut self, name: &str) -> Option<String> {
+        let index = self.fields.iter().position(|&(ref n, _)| n == name)?;
+
+        Some(self.fields.remove(index).1)
+    }
+
+    /// Sets the value of the field with the provided name, adding the field if it doesn't exist
+    fn insert(&mut self, name: &str, value: String) {
+        match self.fields.iter_mut().find(|field| field.0 == name) {
+            Some(field) => field.1 = value,
+            None => self.fields.push((name.to_string(), value)),
+        }
+    }
+}
+
+/// A minimal RON parser that only understands enough syntax to split a struct into its fields
+struct Parser<'a> {
+    source: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char, message: &'static str) -> Result<(), SyntaxError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(message))
+        }
+    }
+
+    /// Skips whitespace and comments
+    fn skip_whitespace(&mut self) {
+        loop {
+            let rest = &self.source[self.pos..];
+
+            if rest.starts_with("//") {
+                self.pos += rest.find('\n').unwrap_or_else(|| rest.len());
+            } else if rest.starts_with("/*") {
+                self.pos += rest[2..].find("*/").map_or(rest.len(), |i| i + 4);
+            } else if self.peek().map_or(false, char::is_whitespace) {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Parses an identifier, returning `None` if there isn't one
+    fn identifier(&mut self) -> Option<&'a str> {
+        let start = self.pos;
+
+        while self
+            .peek()
+            .map_or(false, |c| c.is_alphanumeric() || c == '_')
+        {
+            self.bump();
+        }
+
+        if self.pos == start {
+            None
+        } else {
+            Some(&self.source[start..self.pos])
+        }
+    }
+
+    /// Parses the source text of a value, which ends at a `,` or `)` outside of any brackets
+    fn value(&mut self) -> Result<&'a str, SyntaxError> {
+        self.skip_whitespace();
+
+        let start = self.pos;
+        let mut end = self.pos;
+        let mut depth = 0usize;
+
+        loop {
+            self.skip_whitespace();
+
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(self.error("Unexpected end of file")),
+            };
+
+            match c {
+                ',' | ')' if depth == 0 => break,
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => {
+                    depth = depth
+                        .checked_sub(1)
+                        .ok_or_else(|| self.error("Unmatched closing bracket"))?
+                }
+                '"' | '\'' => {
+                    self.string(c)?;
+                    end = self.pos;
+                    continue;
+                }
+                _ => {}
+            }
+
+            self.bump();
+            end = self.pos;
+        }
+
+        Ok(&self.source[start..end])
+    }
+
+    /// Skips a string or character literal that starts with the provided quote
+    fn string(&mut self, quote: char) -> Result<(), SyntaxError> {
+        let start = self.pos;
+        self.bump();
+
+        loop {
+            match self.bump() {
+                Some('\\') => {
+                    self.bump();
+                }
+                Some(c) if c == quote => return Ok(()),
+                Some(_) => {}
+                None => {
+                    self.pos = start;
+                    return Err(self.error("Unterminated string"));
+                }
+            }
+        }
+    }
+
+    fn error(&self, message: &'static str) -> SyntaxError {
+        let before = &self.source[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+
+        SyntaxError {
+            line,
+            column,
+            message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::{
+        Bind, BindConfig, CameraConfig, GamepadButton, Input, Key, MouseButton, WindowConfig,
+    };
+
+    #[test]
+    fn test_parse() {
+        let raw = RawStruct::parse(
+            r#"Config( // A comment
+                a: 1,
+                b: (c: "x, y)", d: [1, 2]),
+                /* Another comment */ e: Some('('),
+            )"#,
+        ).unwrap();
+
+        assert_eq!(
+            raw.fields,
+            vec![
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), r#"(c: "x, y)", d: [1, 2])"#.to_string()),
+                ("e".to_string(), "Some('(')".to_string()),
+            ]
+        );
+
+        assert_eq!(RawStruct::parse("()").unwrap(), RawStruct::default());
+        assert_eq!(RawStruct::parse("(a: 1)").unwrap().fields.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(RawStruct::parse("(a: 1").is_err());
+        assert!(RawStruct::parse("(a 1)").is_err());
+        assert!(RawStruct::parse("(a: )").is_err());
+        assert!(RawStruct::parse("(a: \"1)").is_err());
+        assert!(RawStruct::parse("(a: 1) b").is_err());
+
+        let err = RawStruct::parse("(\n    a: ]\n)").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 8));
+    }
+
+    #[test]
+    fn test_load_unversioned() {
+        let loaded = load(
+            "(
+                window: (width: 1024, height: 768),
+                bindings: (jump: (key: J)),
+            )",
+        ).unwrap();
+
+        assert!(loaded.migrated());
+        assert_eq!(loaded.version, 0);
+        assert!(loaded.warnings.is_empty());
+        assert_eq!(loaded.config.window.width, 1024);
+        assert_eq!(
+            loaded.config.bindings.jump.primary.unwrap().input,
+            Input::Key(Key::J)
+        );
+    }
+
+    #[test]
+    fn test_load_warnings() {
+        let loaded = load(
+            "(
+                version: 3,
+                window: (width: \"wide\", height: 768, depth: 3),
+                bindings: (jump: (primary: Some((input: Key(J))))),
+                sound: (volume: 1.0),
+                camera: 3,
+            )",
+        ).unwrap();
+
+        assert!(!loaded.migrated());
+        assert_eq!(loaded.warnings.len(), 4);
+
+        // Invalid fields are reset without affecting the others
+        assert_eq!(loaded.config.window.width, WindowConfig::default().width);
+        assert_eq!(loaded.config.window.height, 768);
+        assert_eq!(
+            loaded.config.bindings.jump.primary.unwrap().input,
+            Input::Key(Key::J)
+        );
+    }
+
+    #[test]
+    fn test_load_errors() {
+        match load("(version: 1000)") {
+            Err(ConfigFileError::NewerVersion(1000)) => {}
+            other => panic!("Expected `NewerVersion`, found {:?}", other),
+        }
+
+        match load("(version: one)") {
+            Err(ConfigFileError::InvalidVersion(_)) => {}
+            other => panic!("Expected `InvalidVersion`, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_load_invalid_values() {
+        let loaded = load(
+            "(
+                version: 3,
+                window: (width: 0, height: 768),
+                camera: (fov: 500.0, sensitivity: 2.0),
+            )",
+        ).unwrap();
+
+        assert_eq!(loaded.warnings.len(), 2);
+
+        // Values that fail validation are reset without affecting the others
+        assert!(loaded.config.validate().is_ok());
+        assert_eq!(loaded.config.window.width, WindowConfig::default().width);
+        assert_eq!(loaded.config.window.height, 768);
+        assert_eq!(loaded.config.camera.fov, CameraConfig::default().fov);
+        assert_eq!(loaded.config.camera.sensitivity, 2.0);
+    }
+
+    #[test]
+    fn test_migrations() {
+        assert_eq!(MIGRATIONS.len(), CONFIG_VERSION as usize);
+
+        let mut raw = RawConfig::default();
+        raw.section("bindings").insert("forward", "(key: W)".to_string());
+        raw.move_field("bindings.forward", "bindings.move_forward");
+
+        assert_eq!(
+            raw.section("bindings").fields,
+            vec![("move_forward".to_string(), "(key: W)".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_migrate_v1() {
+        let loaded = load(
+            "(
+                version: 1,
+                bindings: (move_left: (key: Left), jump: (input: Mouse(Right))),
+            )",
+        ).unwrap();
+
+        assert!(loaded.migrated());
+        assert!(loaded.warnings.is_empty());
+
+        let bindings = &loaded.config.bindings;
+        assert_eq!(bindings.move_left.primary.as_ref().unwrap().input, Input::Key(Key::Left));
+        assert_eq!(
+            bindings.jump.primary.as_ref().unwrap().input,
+            Input::Mouse(MouseButton::Right)
+        );
+        assert_eq!(bindings.jump.secondary, BindConfig::default().jump.secondary);
+        // Actions that weren't in the file keep their defaults
+        assert_eq!(bindings.move_right, BindConfig::default().move_right);
+    }
+
+    #[test]
+    fn test_migrate_v2() {
+        let loaded = load(
+            "(
+                version: 2,
+                bindings: (
+                    jump: (primary: Some((input: Key(J)))),
+                    sprint: (primary: Some((input: Key(E))), secondary: Some((input: Key(X)))),
+                    crouch: (primary: None),
+                ),
+                gamepad: (jump: North, pause: Select),
+            )",
+        ).unwrap();
+
+        assert!(loaded.migrated());
+        assert!(loaded.warnings.is_empty());
+
+        let bindings = &loaded.config.bindings;
+        let defaults = BindConfig::default();
+
+        // The old gamepad jump button becomes the secondary bind of jumping
+        assert_eq!(bindings.jump.primary.as_ref().unwrap().input, Input::Key(Key::J));
+        assert_eq!(
+            bindings.jump.secondary,
+            Some(Bind::new(GamepadButton::North, Default::default()))
+        );
+        // Existing secondary binds are kept, and other actions get their default gamepad binds
+        assert_eq!(bindings.sprint.secondary.as_ref().unwrap().input, Input::Key(Key::X));
+        assert_eq!(bindings.crouch.primary, None);
+        assert_eq!(bindings.crouch.secondary, defaults.crouch.secondary);
+        assert_eq!(bindings.dash, defaults.dash);
+        assert_eq!(loaded.config.gamepad.pause, GamepadButton::Select);
+    }
+}
@@ -0,0 +1,384 @@
+//! Types for the in-game developer console
+//!
+//! Subsystems register commands with a `CommandRegistry`, and the UI submits lines of input to the
+//! `Console` resource. Queued lines are executed by the main loop, which has access to the whole
+//! `World`.
+
+use slog::{self, Drain};
+use specs;
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::{self, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// The maximum number of lines stored in the console output
+pub const MAX_OUTPUT_LINES: usize = 256;
+
+/// The maximum number of entries stored in the console input history
+pub const MAX_HISTORY_ENTRIES: usize = 64;
+
+/// The result of running a console command
+///
+/// `Ok` may contain a message to print to the console output, and `Err` contains a message
+/// explaining why the command failed.
+pub type CommandResult = Result<Option<String>, String>;
+
+/// A function that runs a console command with the provided arguments
+// NOTE: This is not required to be `Send` or `Sync` because the registry is owned by the main loop
+//       and not stored as a resource
+pub type CommandFn = Box<Fn(&mut specs::World, &[&str]) -> CommandResult>;
+
+/// A command that can be run from the console
+pub struct Command {
+    usage: String,
+    description: String,
+    handler: CommandFn,
+}
+
+impl Command {
+    /// Returns the usage string of this command (for example, `tp <x> <y> <z>`)
+    pub fn usage(&self) -> &str {
+        &self.usage
+    }
+
+    /// Returns the description of this command
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+/// A registry of console commands, keyed by name
+pub struct CommandRegistry {
+    commands: BTreeMap<String, Command>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self {
+            commands: BTreeMap::new(),
+        }
+    }
+
+    /// Registers a command with the provided name
+    ///
+    /// Panics if a command with the same name has already been registered.
+    pub fn register<S, U, D>(&mut self, name: S, usage: U, description: D, handler: CommandFn)
+    where
+        S: Into<String>,
+        U: Into<String>,
+        D: Into<String>,
+    {
+        let name = name.into();
+        let command = Command {
+            usage: usage.into(),
+            description: description.into(),
+            handler,
+        };
+
+        assert!(
+            self.commands.insert(name.clone(), command).is_none(),
+            "A console command with this name has already been registered: {}",
+            name
+        );
+    }
+
+    /// Returns the command with the provided name
+    pub fn get(&self, name: &str) -> Option<&Command> {
+        self.commands.get(name)
+    }
+
+    /// Returns an iterator over the names of all registered commands, in alphabetical order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.commands.keys().map(|k| k.as_str())
+    }
+
+    /// Parses and runs a line of console input
+    pub fn execute(&self, world: &mut specs::World, line: &str) -> CommandResult {
+        let args = line.split_whitespace().collect::<Vec<_>>();
+
+        let (name, args) = match args.split_first() {
+            Some((name, args)) => (*name, args),
+            None => return Ok(None),
+        };
+
+        if name == "help" {
+            return Ok(Some(self.help()));
+        }
+
+        match self.commands.get(name) {
+            Some(command) => (command.handler)(world, args),
+            None => Err(format!("Unknown command `{}` (try `help`)", name)),
+        }
+    }
+
+    /// Returns a list of all commands and their descriptions
+    fn help(&self) -> String {
+        let mut help = String::from("help - Lists all commands");
+
+        for command in self.commands.values() {
+            let _ = write!(help, "\n{} - {}", command.usage, command.description);
+        }
+
+        help
+    }
+}
+
+/// A list of lines printed to the console
+///
+/// This is shared between the `Console` resource and the logger, so log output is mirrored into
+/// the console.
+#[derive(Clone, Default)]
+pub struct ConsoleLog(Arc<Mutex<VecDeque<String>>>);
+
+impl ConsoleLog {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Appends a line to the log, removing the oldest line if `MAX_OUTPUT_LINES` is exceeded
+    pub fn push<S: Into<String>>(&self, line: S) {
+        let mut lines = self.lines();
+
+        for line in line.into().lines() {
+            lines.push_back(line.to_string());
+        }
+
+        while lines.len() > MAX_OUTPUT_LINES {
+            lines.pop_front();
+        }
+    }
+
+    /// Returns a guard to the lines of the log, with the oldest line first
+    pub fn lines(&self) -> MutexGuard<VecDeque<String>> {
+        self.0.lock().unwrap()
+    }
+}
+
+/// A `slog` drain that writes records to a `ConsoleLog`
+pub struct ConsoleDrain(ConsoleLog);
+
+impl ConsoleDrain {
+    pub fn new(log: ConsoleLog) -> Self {
+        ConsoleDrain(log)
+    }
+}
+
+impl Drain for ConsoleDrain {
+    type Ok = ();
+    type Err = slog::Never;
+
+    fn log(&self, record: &slog::Record, _: &slog::OwnedKVList) -> Result<(), slog::Never> {
+        let mut line = format!("{} {}", record.level().as_short_str(), record.msg());
+        let mut serializer = KvSerializer(&mut line);
+        let _ = slog::KV::serialize(&record.kv(), record, &mut serializer);
+
+        self.0.push(line);
+
+        Ok(())
+    }
+}
+
+/// Appends key-value pairs of a record to a string
+struct KvSerializer<'a>(&'a mut String);
+
+impl<'a> slog::Serializer for KvSerializer<'a> {
+    fn emit_arguments(&mut self, key: slog::Key, val: &fmt::Arguments) -> slog::Result {
+        let _ = write!(self.0, ", {}: {}", key, val);
+        Ok(())
+    }
+}
+
+/// The state of the developer console
+pub struct Console {
+    output: ConsoleLog,
+    history: Vec<String>,
+    queued: Vec<String>,
+    command_names: Vec<String>,
+}
+
+impl Console {
+    pub fn new(output: ConsoleLog) -> Self {
+        Self {
+            output,
+            history: Vec::new(),
+            queued: Vec::new(),
+            command_names: Vec::new(),
+        }
+    }
+
+    /// Returns the console output
+    pub fn output(&self) -> &ConsoleLog {
+        &self.output
+    }
+
+    /// Prints a line to the console output
+    pub fn print<S: Into<String>>(&self, line: S) {
+        self.output.push(line);
+    }
+
+    /// Returns the input history, with the oldest entry first
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Sets the list of command names used for autocompletion
+    pub fn set_command_names<I, S>(&mut self, names: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.command_names = names.into_iter().map(|s| s.into()).collect();
+        self.command_names.push("help".to_string());
+        self.command_names.sort();
+    }
+
+    /// Submits a line of input, queueing it to be run by the main loop
+    pub fn submit(&mut self, line: String) {
+        let line = line.trim().to_string();
+
+        if line.is_empty() {
+            return;
+        }
+
+        self.print(format!("> {}", line));
+
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+
+            if self.history.len() > MAX_HISTORY_ENTRIES {
+                self.history.remove(0);
+            }
+        }
+
+        self.queued.push(line);
+    }
+
+    /// Removes and returns all queued lines of input
+    pub fn take_queued(&mut self) -> Vec<String> {
+        self.queued.drain(..).collect()
+    }
+
+    /// Returns the input with the command name completed
+    ///
+    /// If multiple command names match, the input is completed to their longest common prefix and
+    /// the candidates are printed. Returns `None` if no command names match, or if the input
+    /// already has arguments.
+    pub fn complete(&self, input: &str) -> Option<String> {
+        if input.is_empty() || input.contains(char::is_whitespace) {
+            return None;
+        }
+
+        let candidates = self
+            .command_names
+            .iter()
+            .filter(|name| name.starts_with(input))
+            .collect::<Vec<_>>();
+
+        match candidates.len() {
+            0 => None,
+            1 => Some(format!("{} ", candidates[0])),
+            _ => {
+                let prefix = candidates[1..]
+                    .iter()
+                    .fold(candidates[0].as_str(), |prefix, name| {
+                        common_prefix(prefix, name)
+                    });
+
+                let names = candidates.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+                self.print(names.join("  "));
+
+                Some(prefix.to_string())
+            }
+        }
+    }
+}
+
+/// Returns the longest common prefix of the two strings
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+    let len = a
+        .char_indices()
+        .zip(b.chars())
+        .take_while(|&((_, x), y)| x == y)
+        .last()
+        .map(|((i, c), _)| i + c.len_utf8())
+        .unwrap_or(0);
+
+    &a[..len]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> CommandRegistry {
+        let mut registry = CommandRegistry::new();
+
+        registry.register(
+            "echo",
+            "echo <text>",
+            "Prints its arguments",
+            Box::new(|_: &mut specs::World, args: &[&str]| Ok(Some(args.join(" ")))),
+        );
+        registry.register(
+            "fail",
+            "fail",
+            "Always fails",
+            Box::new(|_: &mut specs::World, _: &[&str]| Err("failed".to_string())),
+        );
+
+        registry
+    }
+
+    #[test]
+    fn test_execute() {
+        let registry = registry();
+        let mut world = specs::World::new();
+
+        assert_eq!(
+            registry.execute(&mut world, "  echo  a   b "),
+            Ok(Some("a b".to_string()))
+        );
+        assert_eq!(registry.execute(&mut world, "fail"), Err("failed".to_string()));
+        assert_eq!(registry.execute(&mut world, ""), Ok(None));
+        assert!(registry.execute(&mut world, "missing").is_err());
+    }
+
+    #[test]
+    fn test_complete() {
+        let mut console = Console::new(ConsoleLog::new());
+        console.set_command_names(vec!["reload_shaders", "set", "spawn"]);
+
+        assert_eq!(console.complete("rel"), Some("reload_shaders ".to_string()));
+        assert_eq!(console.complete("s"), Some("s".to_string()));
+        assert_eq!(console.complete("sp"), Some("spawn ".to_string()));
+        assert_eq!(console.complete("x"), None);
+        assert_eq!(console.complete("set a"), None);
+    }
+
+    #[test]
+    fn test_history() {
+        let mut console = Console::new(ConsoleLog::new());
+
+        console.submit("tp 0 0 0".to_string());
+        console.submit("tp 0 0 0".to_string());
+        console.submit("  ".to_string());
+        console.submit("kill_all".to_string());
+
+        assert_eq!(console.history(), &["tp 0 0 0", "kill_all"]);
+        assert_eq!(console.take_queued().len(), 3);
+        assert!(console.take_queued().is_empty());
+    }
+
+    #[test]
+    fn test_output_limit() {
+        let log = ConsoleLog::new();
+
+        for i in 0..MAX_OUTPUT_LINES + 10 {
+            log.push(i.to_string());
+        }
+
+        let lines = log.lines();
+        assert_eq!(lines.len(), MAX_OUTPUT_LINES);
+        assert_eq!(lines[0], "10");
+    }
+}
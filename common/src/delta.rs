@@ -21,6 +21,16 @@ impl Default for Delta {
     }
 }
 
+/// A multiplier applied to the delta time, used to speed up or slow down the simulation
+#[derive(Clone, Copy, Debug)]
+pub struct TimeScale(pub ::Float);
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        TimeScale(1.0)
+    }
+}
+
 /// A system to update the delta time
 pub struct System {
     last_update: Instant,
@@ -35,11 +45,14 @@ impl System {
 }
 
 impl<'a> specs::System<'a> for System {
-    type SystemData = specs::Write<'a, Delta>;
+    type SystemData = (specs::Write<'a, Delta>, specs::Read<'a, TimeScale>);
 
-    fn run(&mut self, mut data: Self::SystemData) {
-        data.0 = Duration::from_std(self.last_update.elapsed())
+    fn run(&mut self, (mut delta, time_scale): Self::SystemData) {
+        let elapsed = Duration::from_std(self.last_update.elapsed())
             .expect("Delta duration conversion failure");
+        let nanoseconds = elapsed.num_nanoseconds().expect("Delta time overflow");
+
+        delta.0 = Duration::nanoseconds((nanoseconds as ::Float * time_scale.0) as i64);
         self.last_update = Instant::now();
     }
 }
@@ -29,6 +29,7 @@ pub extern crate conrod;
 pub extern crate time;
 pub extern crate structopt;
 pub extern crate takeable_option;
+extern crate ron;
 extern crate serde;
 extern crate slog;
 #[macro_use]
@@ -38,6 +39,8 @@ extern crate quick_error;
 
 mod components;
 pub mod config;
+pub mod config_file;
+pub mod console;
 mod delta;
 mod resources;
 pub mod utils;
@@ -66,8 +69,35 @@ pub fn initialize<'a, 'b>(
     world.register::<components::physics::PhysicsTiedDirection>();
 
     world.add_resource(Delta::default());
+    world.add_resource(TimeScale::default());
 
     // NOTE: This system will be added to the graphics dispatcher, if other systems are added here
     //       in the future the main dispatcher must be added as an argument to this function
     dispatcher.with(System::new(), "delta-time", &[])
 }
+
+/// Registers console commands for the resources in this crate
+pub fn register_commands(commands: &mut console::CommandRegistry) {
+    commands.register(
+        "timescale",
+        "timescale <scale>",
+        "Sets the speed of the simulation (1.0 is normal speed)",
+        Box::new(|world: &mut specs::World, args: &[&str]| {
+            let scale = match args {
+                [scale] => scale
+                    .parse::<Float>()
+                    .map_err(|e| format!("Invalid time scale `{}`: {}", scale, e))?,
+                _ => return Err("Expected exactly one argument".to_string()),
+            };
+
+            // NaN fails the comparison, so it is rejected as well
+            if !(scale >= 0.0 && scale.is_finite()) {
+                return Err("Time scale must be a finite number that is not negative".to_string());
+            }
+
+            world.write_resource::<TimeScale>().0 = scale;
+
+            Ok(Some(format!("Time scale set to {}", scale)))
+        }),
+    );
+}
@@ -7,6 +7,8 @@
 // NOTE: This must be in the `common` crate to avoid cyclic dependencies
 #[derive(Clone, Copy, Debug)]
 pub enum UiState {
+    /// The loading screen, which is displayed while assets are loaded in the background
+    Loading,
     MainMenu,
     /// The in-game menu (normally includes displays for info like health, ability cooldowns, etc.)
     InGame,
@@ -14,6 +16,11 @@ pub enum UiState {
     PauseMenu,
     /// The options menu
     OptionsMenu,
+    /// The developer console, which is drawn over the paused game world
+    Console,
+    /// The game world is being rendered to detect which graphics preset to use, which is done
+    /// after loading on the first launch
+    Benchmark,
     /// This UI state is used to signal that the game should close
     Exit,
 }
@@ -28,6 +35,23 @@ impl UiState {
         }
     }
 
+    /// Returns whether assets are still being loaded
+    pub fn is_loading(&self) -> bool {
+        if let UiState::Loading = *self {
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns whether the game world should be drawn while in this UI state
+    pub fn shows_game_world(&self) -> bool {
+        match *self {
+            UiState::InGame | UiState::Console | UiState::Benchmark => true,
+            _ => false,
+        }
+    }
+
     /// Returns whether to close the game
     pub fn should_exit(&self) -> bool {
         if let UiState::Exit = *self {
@@ -37,3 +61,118 @@ impl UiState {
         }
     }
 }
+
+/// The progress of loading assets in the background, which is displayed on the loading screen
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LoadingProgress {
+    /// The number of assets that have finished loading
+    pub loaded: usize,
+    /// The total number of assets being loaded
+    pub total: usize,
+}
+
+impl LoadingProgress {
+    /// Returns the fraction of assets that have finished loading, from 0 to 1
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.loaded as f32 / self.total as f32
+        }
+    }
+
+    /// Returns whether all assets have finished loading
+    pub fn is_done(&self) -> bool {
+        self.loaded >= self.total
+    }
+}
+
+/// A debug cheat that can be enabled while testing
+///
+/// NOTE: `GodMode` and `InfiniteAmmo` can already be toggled, but have no effect until there are
+///       damage and weapon systems to check them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cheat {
+    /// The player flies in the direction of the camera, and passes through level geometry and
+    /// other bodies
+    Noclip,
+    /// The player ignores all damage
+    GodMode,
+    /// The player's weapons never run out of ammo
+    InfiniteAmmo,
+}
+
+quick_error! {
+    /// An error while enabling a cheat
+    #[derive(Debug)]
+    pub enum CheatError {
+        NotAllowed {
+            display("Cheats are disabled in release builds (run with `--enable_cheats` to allow them)")
+        }
+    }
+}
+
+/// The debug cheats that are currently enabled
+///
+/// Cheats can only be enabled if they are allowed, which is always the case for debug builds. In
+/// release builds, they must be allowed with a command-line flag.
+#[derive(Clone, Debug)]
+pub struct Cheats {
+    allowed: bool,
+    noclip: bool,
+    god_mode: bool,
+    infinite_ammo: bool,
+}
+
+impl Cheats {
+    /// Returns a new `Cheats` with all cheats disabled
+    ///
+    /// `allow_in_release` is ignored in debug builds, where cheats are always allowed.
+    pub fn new(allow_in_release: bool) -> Self {
+        Self {
+            allowed: cfg!(debug_assertions) || allow_in_release,
+            noclip: false,
+            god_mode: false,
+            infinite_ammo: false,
+        }
+    }
+
+    /// Returns whether cheats can be enabled
+    pub fn allowed(&self) -> bool {
+        self.allowed
+    }
+
+    /// Returns whether the provided cheat is enabled
+    pub fn is_enabled(&self, cheat: Cheat) -> bool {
+        self.allowed && *self.get(cheat)
+    }
+
+    /// Enables or disables the provided cheat
+    ///
+    /// Disabling a cheat always succeeds, but enabling one fails if cheats are not allowed.
+    pub fn set(&mut self, cheat: Cheat, enabled: bool) -> Result<(), CheatError> {
+        if enabled && !self.allowed {
+            return Err(CheatError::NotAllowed);
+        }
+
+        *self.get_mut(cheat) = enabled;
+
+        Ok(())
+    }
+
+    fn get(&self, cheat: Cheat) -> &bool {
+        match cheat {
+            Cheat::Noclip => &self.noclip,
+            Cheat::GodMode => &self.god_mode,
+            Cheat::InfiniteAmmo => &self.infinite_ammo,
+        }
+    }
+
+    fn get_mut(&mut self, cheat: Cheat) -> &mut bool {
+        match cheat {
+            Cheat::Noclip => &mut self.noclip,
+            Cheat::GodMode => &mut self.god_mode,
+            Cheat::InfiniteAmmo => &mut self.infinite_ammo,
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! Movement abilities that change how an entity walks: sprinting, crouching and dashing
+
+use common::cgmath::{self, InnerSpace, Zero};
+
+/// The parameters of sprinting
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SprintConfig {
+    /// The multiplier of the maximum speed while sprinting
+    pub speed_multiplier: ::Float,
+    /// The amount of stamina when it is full
+    pub max_stamina: ::Float,
+    /// The stamina used per second of sprinting
+    pub stamina_cost: ::Float,
+    /// The stamina regained per second while not sprinting
+    pub stamina_regen: ::Float,
+    /// The stamina needed to sprint again after running out of stamina
+    pub min_stamina_to_start: ::Float,
+}
+
+/// The parameters of crouching
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CrouchConfig {
+    /// The fraction of the spring length to use while crouching
+    pub spring_length_fraction: ::Float,
+    /// The multiplier of the maximum speed while crouching
+    pub speed_multiplier: ::Float,
+}
+
+/// The parameters of dashing
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DashConfig {
+    /// The horizontal speed of a dash
+    pub speed: ::Float,
+    /// How long a dash lasts (in seconds)
+    pub duration: ::Float,
+    /// How long it takes until the entity can dash again after starting a dash (in seconds)
+    pub cooldown: ::Float,
+}
+
+/// The parameters of all movement abilities
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AbilityConfig {
+    pub sprint: SprintConfig,
+    pub crouch: CrouchConfig,
+    pub dash: DashConfig,
+}
+
+/// The state of the movement abilities of an entity
+#[derive(Clone, Debug)]
+pub struct Abilities {
+    config: AbilityConfig,
+    stamina: ::Float,
+    /// Whether stamina ran out and hasn't regenerated to `min_stamina_to_start` yet
+    exhausted: bool,
+    /// The velocity of the current dash
+    dash_velocity: cgmath::Vector2<::Float>,
+    dash_time_left: ::Float,
+    dash_cooldown_left: ::Float,
+}
+
+impl Abilities {
+    /// Returns the abilities of an entity that has full stamina and can dash
+    pub fn new(config: AbilityConfig) -> Self {
+        Self {
+            config,
+            stamina: config.sprint.max_stamina,
+            exhausted: false,
+            dash_velocity: cgmath::Vector2::zero(),
+            dash_time_left: 0.0,
+            dash_cooldown_left: 0.0,
+        }
+    }
+
+    /// Returns the current stamina
+    pub fn stamina(&self) -> ::Float {
+        self.stamina
+    }
+
+    /// Returns the fraction of the spring length to use
+    pub fn spring_length_fraction(&self, crouching: bool) -> ::Float {
+        if crouching {
+            self.config.crouch.spring_length_fraction
+        } else {
+            1.0
+        }
+    }
+
+    /// Starts a dash in the provided horizontal direction, unless the dash is on cooldown
+    ///
+    /// Returns whether the dash started.
+    pub fn start_dash(&mut self, direction: cgmath::Vector2<::Float>) -> bool {
+        if self.dash_cooldown_left > 0.0 || direction.magnitude2() == 0.0 {
+            return false;
+        }
+
+        self.dash_velocity = direction.normalize() * self.config.dash.speed;
+        self.dash_time_left = self.config.dash.duration;
+        self.dash_cooldown_left = self.config.dash.cooldown;
+
+        true
+    }
+
+    /// Returns the horizontal velocity of the current dash, if the entity is dashing
+    pub fn dash_velocity(&self) -> Option<cgmath::Vector2<::Float>> {
+        if self.dash_time_left > 0.0 {
+            Some(self.dash_velocity)
+        } else {
+            None
+        }
+    }
+
+    /// Advances stamina and the dash timers by `delta` seconds
+    ///
+    /// An entity sprints if it wants to, is walking, isn't crouching and has stamina left. Once
+    /// stamina runs out, the entity can't sprint until it has regenerated to
+    /// `SprintConfig::min_stamina_to_start`. Returns the multiplier of the maximum speed.
+    pub fn update(
+        &mut self,
+        delta: ::Float,
+        sprint: bool,
+        crouching: bool,
+        walking: bool,
+    ) -> ::Float {
+        let sprinting = sprint && walking && !crouching && !self.exhausted;
+        let sprint_config = &self.config.sprint;
+
+        if sprinting {
+            self.stamina = (self.stamina - sprint_config.stamina_cost * delta).max(0.0);
+            self.exhausted = self.stamina == 0.0;
+        } else {
+            self.stamina =
+                (self.stamina + sprint_config.stamina_regen * delta).min(sprint_config.max_stamina);
+
+            if self.stamina >= sprint_config.min_stamina_to_start {
+                self.exhausted = false;
+            }
+        }
+
+        self.dash_time_left = (self.dash_time_left - delta).max(0.0);
+        self.dash_cooldown_left = (self.dash_cooldown_left - delta).max(0.0);
+
+        if crouching {
+            self.config.crouch.speed_multiplier
+        } else if sprinting {
+            sprint_config.speed_multiplier
+        } else {
+            1.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn abilities() -> Abilities {
+        Abilities::new(AbilityConfig {
+            sprint: SprintConfig {
+                speed_multiplier: 2.0,
+                max_stamina: 1.0,
+                stamina_cost: 1.0,
+                stamina_regen: 0.5,
+                min_stamina_to_start: 0.5,
+            },
+            crouch: CrouchConfig {
+                spring_length_fraction: 0.5,
+                speed_multiplier: 0.5,
+            },
+            dash: DashConfig {
+                speed: 10.0,
+                duration: 0.25,
+                cooldown: 1.0,
+            },
+        })
+    }
+
+    #[test]
+    fn test_sprint_stamina() {
+        let mut abilities = abilities();
+
+        // Sprinting only works while walking
+        assert_eq!(abilities.update(0.5, true, false, false), 1.0);
+        assert_eq!(abilities.update(0.5, true, false, true), 2.0);
+        assert_eq!(abilities.stamina(), 0.5);
+
+        assert_eq!(abilities.update(0.5, true, false, true), 2.0);
+        assert_eq!(abilities.stamina(), 0.0);
+
+        // Sprinting stops once stamina runs out, and doesn't start again until enough stamina has
+        // regenerated, even while still trying to sprint
+        assert_eq!(abilities.update(0.5, true, false, true), 1.0);
+        assert_eq!(abilities.stamina(), 0.25);
+        assert_eq!(abilities.update(0.25, true, false, true), 1.0);
+        assert_eq!(abilities.stamina(), 0.375);
+        assert_eq!(abilities.update(0.25, true, false, true), 1.0);
+        assert_eq!(abilities.stamina(), 0.5);
+        assert_eq!(abilities.update(0.5, true, false, true), 2.0);
+        assert_eq!(abilities.stamina(), 0.0);
+
+        // Crouching takes priority over sprinting
+        assert_eq!(abilities.update(0.5, true, true, true), 0.5);
+        assert_eq!(abilities.stamina(), 0.25);
+    }
+
+    #[test]
+    fn test_dash_cooldown() {
+        let mut abilities = abilities();
+
+        assert!(!abilities.start_dash(cgmath::Vector2::zero()));
+        assert!(abilities.start_dash(cgmath::Vector2::new(0.0, 2.0)));
+        assert_eq!(abilities.dash_velocity(), Some(cgmath::Vector2::new(0.0, 10.0)));
+
+        abilities.update(0.25, false, false, false);
+        assert_eq!(abilities.dash_velocity(), None);
+
+        // The dash can't be used again until the cooldown is over
+        assert!(!abilities.start_dash(cgmath::Vector2::new(1.0, 0.0)));
+        abilities.update(0.75, false, false, false);
+        assert!(abilities.start_dash(cgmath::Vector2::new(1.0, 0.0)));
+    }
+}
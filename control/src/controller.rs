@@ -9,6 +9,26 @@ use common::{cgmath, na};
 use movement;
 use spring;
 
+/// Parameters that control how an entity moves on the ground and in the air
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ControllerConfig {
+    /// The steepest slope that the entity can stand on
+    pub max_slope: cgmath::Rad<::Float>,
+    /// The force per radian of slope that pushes the entity down slopes that are too steep
+    pub slide_force: ::Float,
+    /// The fraction of the walking acceleration that is applied while in the air
+    pub air_acceleration: ::Float,
+    /// The factor that the horizontal velocity is multiplied by on each update while the entity is
+    /// on the ground and not walking
+    pub friction: ::Float,
+    /// The factor that the horizontal velocity is multiplied by on each update while the entity is
+    /// in the air and not walking
+    pub air_drag: ::Float,
+    /// The fastest the entity can turn towards the rotation set by `Control::set_rotation`, per
+    /// second
+    pub max_turn_rate: cgmath::Rad<::Float>,
+}
+
 /// The result of calling the `apply` function of a force generator
 pub enum ForceGeneratorResult {
     None,
@@ -8,31 +8,52 @@ extern crate common;
 extern crate shred_derive;
 extern crate math;
 
+mod abilities;
 mod controller;
 mod movement;
 mod spring;
 
-pub use self::movement::MovementForceGenerator;
+pub use self::abilities::{AbilityConfig, CrouchConfig, DashConfig, SprintConfig};
+pub use self::controller::ControllerConfig;
+pub use self::movement::{JumpTiming, MovementForceGenerator};
 pub use self::spring::Spring;
 
 use common::cgmath::InnerSpace;
-use common::cgmath::{self, Quaternion};
+use common::cgmath::{self, Quaternion, Rad};
 use common::ncollide3d::query::{self, RayCast};
+use common::ncollide3d::world::CollisionGroups;
 use common::nphysics3d::force_generator::ForceGeneratorHandle;
-use common::nphysics3d::object::{Body, RigidBody, BodyHandle, BodyPartHandle, ColliderDesc};
+use common::nphysics3d::object::{
+    Body, BodyHandle, BodyPartHandle, BodyStatus, ColliderDesc, ColliderHandle, RigidBody,
+};
 use common::nphysics3d::world::World;
 use common::specs::{self, DispatcherBuilder, Join};
-use common::{shred, na, physics, Float};
+use common::{console, shred, na, physics, Delta, Float};
 use math::convert;
 
+/// The speed of noclip movement, relative to the entity's maximum walking speed
+const NOCLIP_SPEED_MULTIPLIER: ::Float = 2.0;
+
 /// Controlled properties of an entity
 pub struct Control {
     force_generator: ForceGeneratorHandle,
     direction: Option<Quaternion<::Float>>,
     velocity: Option<VelocityModifier>,
     jump: bool,
-    friction: ::Float,
+    jump_released: bool,
+    sprint: bool,
+    crouch: bool,
+    /// The direction to dash in, if a dash was requested
+    dash: Option<cgmath::Vector2<::Float>>,
+    abilities: abilities::Abilities,
+    config: ControllerConfig,
+    /// Whether the entity was on the ground during the last update
+    grounded: bool,
     max_speed: ::Float,
+    noclip: bool,
+    /// The collision groups the entity's colliders had before noclip was enabled, or `None` if
+    /// their collisions aren't disabled
+    saved_collision_groups: Option<Vec<(ColliderHandle, CollisionGroups)>>,
 }
 
 /// A modifier to be applied to the velocity of an entity
@@ -40,15 +61,21 @@ pub struct Control {
 pub enum VelocityModifier {
     /// Walk horizontally in the provided direction, ignoring the vertical component
     WalkForward(cgmath::Vector2<::Float>),
+    /// Fly in the provided direction (only used while noclip is enabled)
+    Fly(cgmath::Vector3<::Float>),
 }
 
 impl Control {
     /// Returns a new `Control`
+    ///
+    /// The `ControllerConfig` sets how the entity moves on slopes and in the air, so different
+    /// kinds of entities can move differently.
     pub fn new(
         body_handle: BodyPartHandle,
         movement: movement::MovementForceGenerator,
         spring: spring::Spring,
-        friction: ::Float,
+        config: ControllerConfig,
+        abilities: AbilityConfig,
         world: &mut World<::Float>,
     ) -> Self {
         let max_speed = movement.max_speed();
@@ -60,29 +87,98 @@ impl Control {
 
         Self {
             force_generator,
-            friction,
+            config,
+            grounded: false,
             direction: None,
             velocity: None,
             jump: false,
+            jump_released: false,
+            sprint: false,
+            crouch: false,
+            dash: None,
+            abilities: abilities::Abilities::new(abilities),
             max_speed,
+            noclip: false,
+            saved_collision_groups: None,
         }
     }
 
-    /// Sets the direction of the entity to the provided quaternion
+    /// Turns the entity towards the provided rotation, at the maximum turn rate of its
+    /// `ControllerConfig`
+    ///
+    /// The entity's physics body is rotated, so the `Direction` of entities with
+    /// `PhysicsTiedDirection` follows it. The entity keeps turning until it reaches the rotation or
+    /// another rotation is set.
     pub fn set_rotation(&mut self, direction: Quaternion<::Float>) {
         self.direction = Some(direction);
     }
 
     /// Makes the entity walk horizontally in the provided direction, ignoring the vertical
     /// component
+    ///
+    /// The length of the direction is the fraction of the maximum speed to walk at, so analog input
+    /// can walk slowly. Lengths above 1 are treated as 1.
     pub fn walk_in_direction(&mut self, direction: cgmath::Vector2<::Float>) {
         self.velocity = Some(VelocityModifier::WalkForward(direction));
     }
 
+    /// Makes the entity fly in the provided direction
+    ///
+    /// This only has an effect while noclip is enabled.
+    pub fn fly_in_direction(&mut self, direction: cgmath::Vector3<::Float>) {
+        self.velocity = Some(VelocityModifier::Fly(direction));
+    }
+
     /// Makes the entity jump
+    ///
+    /// If the entity can't jump right now, it jumps if it becomes able to within the jump buffer
+    /// window of its `JumpTiming`.
     pub fn jump(&mut self) {
         self.jump = true;
     }
+
+    /// Releases the current jump, which makes it lower if the entity is still rising
+    pub fn release_jump(&mut self) {
+        self.jump_released = true;
+    }
+
+    /// Sets whether the entity wants to sprint
+    ///
+    /// The entity only sprints while it is walking, isn't crouching and has stamina left.
+    pub fn set_sprint(&mut self, sprint: bool) {
+        self.sprint = sprint;
+    }
+
+    /// Sets whether the entity is crouching
+    ///
+    /// Crouching shortens the entity's spring, which lowers it (and the camera, for the player).
+    pub fn set_crouch(&mut self, crouch: bool) {
+        self.crouch = crouch;
+    }
+
+    /// Makes the entity dash horizontally in the provided direction, unless dashing is on cooldown
+    pub fn dash(&mut self, direction: cgmath::Vector2<::Float>) {
+        self.dash = Some(direction);
+    }
+
+    /// Returns the entity's stamina, which is used by sprinting
+    pub fn stamina(&self) -> ::Float {
+        self.abilities.stamina()
+    }
+
+    /// Enables or disables noclip
+    ///
+    /// While noclip is enabled, the entity's physics body is made kinematic, so it ignores gravity,
+    /// and its colliders stop colliding with anything, so it passes through both static geometry
+    /// and other bodies. Its spring is disabled as well.
+    pub fn set_noclip(&mut self, noclip: bool) {
+        self.noclip = noclip;
+    }
+
+    /// Returns whether noclip is enabled
+    pub fn is_noclip(&self) -> bool {
+        self.noclip
+    }
 }
 
 impl specs::Component for Control {
@@ -115,35 +211,89 @@ pub struct Data<'a> {
     physics: specs::WriteStorage<'a, physics::Physics>,
     world: specs::WriteExpect<'a, World<::Float>>,
     floor_handle: specs::ReadExpect<'a, FloorHandle>,
+    delta: specs::ReadExpect<'a, Delta>,
 }
 
 impl<'a> specs::System<'a> for System {
     type SystemData = Data<'a>;
 
     fn run(&mut self, mut data: Self::SystemData) {
+        let delta = data.delta.to_float();
+
         for (c, p) in (&mut data.control, &mut data.physics).join() {
-            // FIXME: Implement this
-            if let Some(direction) = c.direction {
-                c.direction = None;
+            if let Some(target) = c.direction {
+                let max_angle = c.config.max_turn_rate * delta;
+
+                if turn_body(&mut data.world, p.get_root_handle(), target, max_angle) {
+                    c.direction = None;
+                }
+            }
+
+            if c.noclip {
+                if c.saved_collision_groups.is_none() {
+                    let groups = disable_collisions(&mut data.world, p.get_root_handle());
+                    c.saved_collision_groups = Some(groups);
+                }
+
+                update_noclip(c, p, &mut data.world);
+                continue;
+            }
+
+            // Restore the colliders if noclip was disabled
+            if let Some(groups) = c.saved_collision_groups.take() {
+                restore_collisions(&mut data.world, groups);
             }
 
-            let walk_dir = c.velocity.map(|direction| match direction {
-                VelocityModifier::WalkForward(direction) => direction.normalize(),
+            let walk_dir = c.velocity.and_then(|direction| match direction {
+                VelocityModifier::WalkForward(direction) if direction.magnitude2() > 1.0 => {
+                    Some(direction.normalize())
+                }
+                VelocityModifier::WalkForward(direction) => Some(direction),
+                VelocityModifier::Fly(_) => None,
             });
+            if let Some(direction) = c.dash.take() {
+                c.abilities.start_dash(direction);
+            }
+
+            let dash_velocity = c.abilities.dash_velocity();
+            let speed_multiplier =
+                c.abilities.update(delta, c.sprint, c.crouch, walk_dir.is_some());
+            // Walking slower than the maximum speed also limits the speed
+            let max_speed =
+                c.max_speed * speed_multiplier * walk_dir.map_or(1.0, |d| d.magnitude());
 
             if let Some(body_mut) = data.world.body_mut(p.get_root_handle()) {
                 // The `control` system only works for rigid bodies
                 // TODO: Maybe use a multibody for controlled entities to allow for joints
                 if let Some(body) = body_mut.downcast_mut::<RigidBody<::Float>>() {
+                    // Restore the body if noclip was disabled
+                    if body.status() != BodyStatus::Dynamic {
+                        body.set_status(BodyStatus::Dynamic);
+                    }
+
                     body.set_angular_velocity(na::zero());
 
-                    // Only apply friction is the entity is not trying to walk
-                    if c.velocity.is_none() {
+                    if let Some(dash_velocity) = dash_velocity {
+                        // Dashing overrides the horizontal velocity until the dash is over
                         let mut vel = body.velocity().linear;
 
+                        vel[0] = dash_velocity.x;
+                        vel[1] = dash_velocity.y;
+
+                        body.set_linear_velocity(vel);
+                        body.activate();
+                    } else if c.velocity.is_none() {
+                        // Only apply friction is the entity is not trying to walk
+                        let mut vel = body.velocity().linear;
+                        let friction = if c.grounded {
+                            c.config.friction
+                        } else {
+                            c.config.air_drag
+                        };
+
                         // Apply friction to the entity's horizontal velocity
-                        vel[0] *= c.friction;
-                        vel[1] *= c.friction;
+                        vel[0] *= friction;
+                        vel[1] *= friction;
 
                         body.set_linear_velocity(vel);
                     } else {
@@ -151,9 +301,9 @@ impl<'a> specs::System<'a> for System {
 
                         let magnitude = cgmath::Vector2::new(vel[0], vel[1]).magnitude();
 
-                        if magnitude > c.max_speed {
-                            vel[0] = vel[0] / magnitude * c.max_speed;
-                            vel[1] = vel[1] / magnitude * c.max_speed;
+                        if magnitude > max_speed {
+                            vel[0] = vel[0] / magnitude * max_speed;
+                            vel[1] = vel[1] / magnitude * max_speed;
                         }
 
                         body.set_linear_velocity(vel);
@@ -226,6 +376,10 @@ impl<'a> specs::System<'a> for System {
                     .downcast_mut::<controller::ControllerForceGenerator>()
                     .unwrap();
 
+                controller
+                    .spring
+                    .set_length_fraction(c.abilities.spring_length_fraction(c.crouch));
+
                 if let Some(length) = current_spring_length {
                     controller.spring.set_current_length(length);
                 } else {
@@ -236,6 +390,7 @@ impl<'a> specs::System<'a> for System {
                     ground_normal
                         .map(convert::to_cgmath_vector)
                         .unwrap_or(cgmath::Vector3::unit_z()),
+                    &c.config,
                 );
 
                 if is_ground_too_steep {
@@ -243,33 +398,51 @@ impl<'a> specs::System<'a> for System {
                 }
 
                 if let Some(walk_dir) = walk_dir {
-                    controller.movement.set_walk_direction(walk_dir);
+                    // Walking accelerates the entity less while it is in the air
+                    let acceleration = if controller.spring.is_enabled() {
+                        1.0
+                    } else {
+                        c.config.air_acceleration
+                    };
+
+                    controller.movement.set_walk_direction(walk_dir * acceleration);
                 } else {
                     controller.movement.reset_walk_direction();
                 }
 
-                let jump_velocity = if c.jump {
-                    c.jump = false;
-                    if controller.spring.is_enabled() {
-                        controller.spring.disable_until_reenable_threshold();
-                        Some(controller.movement.jump_strength())
-                    } else {
-                        None
-                    }
+                // Releasing is handled first, so a jump that is pressed and released on the same
+                // update still happens
+                let released_velocity = if c.jump_released {
+                    c.jump_released = false;
+                    controller.movement.release_jump(current_entity_velocity[2])
                 } else {
                     None
                 };
 
+                if c.jump {
+                    c.jump = false;
+                    controller.movement.request_jump();
+                }
+
+                let jump_velocity =
+                    controller.movement.update_jump(delta, controller.spring.is_enabled());
+
+                if jump_velocity.is_some() {
+                    controller.spring.disable_until_reenable_threshold();
+                }
+
                 // Update the velocity fields on the controller's force generators
                 controller.update_current_entity_velocity(current_entity_velocity);
 
                 (
                     is_ground_too_steep,
-                    jump_velocity,
+                    jump_velocity.or(released_velocity),
                     controller.spring.is_enabled(),
                 )
             };
 
+            c.grounded = spring_enabled;
+
             if let Some(body) = data.world.body_mut(p.get_root_handle()) {
                 if let Some(rb) = body.downcast_mut::<RigidBody<::Float>>() {
                     if is_ground_too_steep {
@@ -304,6 +477,132 @@ impl<'a> specs::System<'a> for System {
     }
 }
 
+/// Rotates a rigid body towards the provided rotation by at most `max_angle`
+///
+/// Returns whether the body has reached the rotation, or can't be turned because it isn't a rigid
+/// body or the rotation is a zero or non-finite quaternion.
+fn turn_body(
+    world: &mut World<::Float>,
+    handle: BodyHandle,
+    target: Quaternion<::Float>,
+    max_angle: Rad<::Float>,
+) -> bool {
+    if !target.magnitude2().is_normal() {
+        return true;
+    }
+
+    let rb = match world
+        .body_mut(handle)
+        .and_then(|b| b.downcast_mut::<RigidBody<::Float>>())
+    {
+        Some(rb) => rb,
+        None => return true,
+    };
+
+    let position = *rb.position();
+    let current = convert::to_cgmath_quaternion(position.rotation);
+    let (rotation, reached) = turn_towards(current, target, max_angle);
+
+    rb.set_position(na::Isometry3::from_parts(
+        position.translation,
+        convert::to_na_quaternion(rotation),
+    ));
+    rb.activate();
+
+    reached
+}
+
+/// Rotates `current` towards `target` by at most `max_angle`, taking the shortest way
+///
+/// Returns the new rotation, and whether it is the target rotation.
+fn turn_towards(
+    current: Quaternion<::Float>,
+    target: Quaternion<::Float>,
+    max_angle: Rad<::Float>,
+) -> (Quaternion<::Float>, bool) {
+    let (current, target) = (current.normalize(), target.normalize());
+
+    // `q` and `-q` are the same rotation, so use the one closest to the current rotation
+    let (target_near, dot) = {
+        let dot = current.dot(target);
+
+        if dot < 0.0 {
+            (-target, -dot)
+        } else {
+            (target, dot)
+        }
+    };
+
+    let angle = Rad(2.0 * dot.min(1.0).acos());
+
+    if angle <= max_angle {
+        (target, true)
+    } else {
+        (current.slerp(target_near, max_angle / angle), false)
+    }
+}
+
+/// Moves a noclipping entity in the direction it is flying, and disables its spring
+fn update_noclip(c: &mut Control, p: &physics::Physics, world: &mut World<::Float>) {
+    let velocity = match c.velocity.take() {
+        Some(VelocityModifier::Fly(direction)) if direction.magnitude2() > 0.0 => {
+            convert::to_na_vector(direction.normalize() * c.max_speed * NOCLIP_SPEED_MULTIPLIER)
+        }
+        _ => na::zero(),
+    };
+    c.jump = false;
+    c.jump_released = false;
+    c.dash = None;
+
+    if let Some(body) = world.body_mut(p.get_root_handle()) {
+        if let Some(rb) = body.downcast_mut::<RigidBody<::Float>>() {
+            rb.set_status(BodyStatus::Kinematic);
+            rb.set_angular_velocity(na::zero());
+            rb.set_linear_velocity(velocity);
+            rb.activate();
+        }
+    }
+
+    let controller = world
+        .force_generator_mut(c.force_generator)
+        .downcast_mut::<controller::ControllerForceGenerator>()
+        .unwrap();
+
+    controller.spring.reset_current_length();
+    controller.spring.disable_until_reenable_threshold();
+    controller.movement.reset_walk_direction();
+    controller.movement.reset_jump();
+}
+
+/// Stops the colliders of a body from colliding with anything, and returns their previous collision
+/// groups
+fn disable_collisions(
+    world: &mut World<::Float>,
+    body: BodyHandle,
+) -> Vec<(ColliderHandle, CollisionGroups)> {
+    let saved: Vec<_> = world
+        .collider_world()
+        .colliders()
+        .filter(|collider| collider.body() == body)
+        .map(|collider| (collider.handle(), *collider.collision_groups()))
+        .collect();
+    // A collider with an empty whitelist can't interact with any other collider
+    let no_collisions = CollisionGroups::new().with_whitelist(&[]);
+
+    for &(handle, _) in &saved {
+        world.collider_world_mut().set_collision_groups(handle, no_collisions);
+    }
+
+    saved
+}
+
+/// Restores the collision groups returned by `disable_collisions`
+fn restore_collisions(world: &mut World<::Float>, groups: Vec<(ColliderHandle, CollisionGroups)>) {
+    for (handle, groups) in groups {
+        world.collider_world_mut().set_collision_groups(handle, groups);
+    }
+}
+
 /// Initialization of control-related systems and components
 pub fn initialize<'a, 'b>(
     world: &mut specs::World,
@@ -318,3 +617,73 @@ pub fn initialize<'a, 'b>(
     // Add systems
     dispatcher.with(System, "control", &[])
 }
+
+/// Registers console commands for the systems in this crate
+pub fn register_commands(commands: &mut console::CommandRegistry) {
+    commands.register(
+        "kill_all",
+        "kill_all",
+        "Removes all controlled entities except the player",
+        Box::new(|world: &mut specs::World, _: &[&str]| {
+            let removed = {
+                let entities = world.entities();
+                let control = world.read_storage::<Control>();
+                let physics = world.read_storage::<physics::Physics>();
+                let player = world.read_storage::<common::Player>();
+                let mut phys_world = world.write_resource::<World<::Float>>();
+                let mut removed = 0;
+
+                for (e, c, p, _) in (&*entities, &control, &physics, !&player).join() {
+                    phys_world.remove_force_generator(c.force_generator);
+                    phys_world.remove_bodies(&[p.get_root_handle()]);
+                    phys_world.remove_bodies(p.get_child_handles());
+
+                    entities
+                        .delete(e)
+                        .map_err(|e| format!("Error removing entity: {:?}", e))?;
+
+                    removed += 1;
+                }
+
+                removed
+            };
+
+            world.maintain();
+
+            Ok(Some(format!("Removed {} entities", removed)))
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::cgmath::{Deg, Rotation3};
+
+    fn yaw(degrees: ::Float) -> Quaternion<::Float> {
+        Quaternion::from_angle_z(Deg(degrees))
+    }
+
+    fn assert_rotation_eq(a: Quaternion<::Float>, b: Quaternion<::Float>) {
+        assert!(a.dot(b).abs() > 1.0 - 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_turn_towards() {
+        let max_angle = Deg(30.0).into();
+
+        let (rotation, reached) = turn_towards(yaw(0.0), yaw(90.0), max_angle);
+        assert_rotation_eq(rotation, yaw(30.0));
+        assert!(!reached);
+
+        let (rotation, reached) = turn_towards(rotation, yaw(50.0), max_angle);
+        assert_rotation_eq(rotation, yaw(50.0));
+        assert!(reached);
+
+        // Turning takes the shortest way around
+        let (rotation, _) = turn_towards(yaw(170.0), yaw(-170.0), Deg(10.0).into());
+        assert_rotation_eq(rotation, yaw(180.0));
+        let (rotation, _) = turn_towards(yaw(0.0), -yaw(-90.0), max_angle);
+        assert_rotation_eq(rotation, yaw(-30.0));
+    }
+}
@@ -3,28 +3,67 @@
 use common::cgmath::{self, InnerSpace, Zero};
 use common::na;
 
-use controller::ForceGeneratorResult;
+use controller::{ControllerConfig, ForceGeneratorResult};
 
-const GROUND_STEEPNESS_FORCE: ::Float = 5000.0;
+/// Timing windows that make jumps more forgiving
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JumpTiming {
+    /// How long a jump that was requested while unable to jump is remembered (in seconds), so it
+    /// happens if the entity lands soon after
+    pub buffer: ::Float,
+    /// How long the entity can still jump after leaving the ground (in seconds)
+    pub coyote_time: ::Float,
+    /// The fraction of the upwards velocity that is kept when a jump is released while rising
+    pub release_velocity_fraction: ::Float,
+}
+
+impl Default for JumpTiming {
+    fn default() -> Self {
+        Self {
+            buffer: 0.15,
+            coyote_time: 0.1,
+            release_velocity_fraction: 0.5,
+        }
+    }
+}
 
 pub struct MovementForceGenerator {
     horizontal_velocity: cgmath::Vector2<::Float>,
     acceleration: ::Float,
     max_speed: ::Float,
     jump_strength: ::Float,
+    jump_timing: JumpTiming,
+    /// The time left until a requested jump is forgotten, if a jump was requested
+    buffered_jump: Option<::Float>,
+    /// The time left until the entity can no longer jump, if it can jump
+    coyote_time_left: Option<::Float>,
+    /// Whether the entity is rising from a jump that hasn't been released yet
+    jumping: bool,
     walk_dir: Option<cgmath::Vector2<::Float>>,
     ground_normal: cgmath::Vector3<::Float>,
+    /// The force that makes the entity slide down the ground, if it is too steep to stand on
+    slide_force: Option<na::Vector3<::Float>>,
 }
 
 impl MovementForceGenerator {
-    pub fn new(acceleration: ::Float, max_speed: ::Float, jump_strength: ::Float) -> Self {
+    pub fn new(
+        acceleration: ::Float,
+        max_speed: ::Float,
+        jump_strength: ::Float,
+        jump_timing: JumpTiming,
+    ) -> Self {
         Self {
             horizontal_velocity: cgmath::Vector2::zero(),
             acceleration,
             max_speed,
             jump_strength,
+            jump_timing,
+            buffered_jump: None,
+            coyote_time_left: None,
+            jumping: false,
             walk_dir: None,
             ground_normal: cgmath::Vector3::unit_z(),
+            slide_force: None,
         }
     }
 
@@ -40,26 +79,34 @@ impl MovementForceGenerator {
 
     /// Updates the normal vector of the ground directly beneath the entity that this force
     /// generator is applying to
-
+    ///
     /// Returns whether the ground steepness force will be applied (if the ground is too steep to
-    /// stand on)
-    pub fn update_ground_normal(&mut self, new_normal: cgmath::Vector3<::Float>) -> bool {
+    /// stand on according to the provided config)
+    pub fn update_ground_normal(
+        &mut self,
+        new_normal: cgmath::Vector3<::Float>,
+        config: &ControllerConfig,
+    ) -> bool {
         self.ground_normal = new_normal;
-        self.get_ground_steepness_force().is_some()
+        self.slide_force = self.get_ground_steepness_force(config);
+        self.slide_force.is_some()
     }
 
     /// If the ground beneath the entity that this force generator is applying to is too steep to
     /// stand on, returns `Some` with the force that should be applied to cause the entity to slide
     /// down the hill
-    pub fn get_ground_steepness_force(&self) -> Option<na::Vector3<::Float>> {
+    pub fn get_ground_steepness_force(
+        &self,
+        config: &ControllerConfig,
+    ) -> Option<na::Vector3<::Float>> {
         let ground_angle = self.ground_normal.angle(cgmath::Vector3::unit_z());
 
-        if ground_angle > cgmath::Rad(3.141 / 4.0) {
+        if ground_angle > config.max_slope {
             // Apply the force in the direction of the normal, ignoring the vertical component
             let force_dir =
                 na::Vector3::new(self.ground_normal.x, self.ground_normal.y, 0.0).normalize();
 
-            Some(force_dir * ground_angle.0 * GROUND_STEEPNESS_FORCE)
+            Some(force_dir * ground_angle.0 * config.slide_force)
         } else {
             None
         }
@@ -79,11 +126,75 @@ impl MovementForceGenerator {
     pub fn jump_strength(&self) -> ::Float {
         self.jump_strength
     }
+
+    /// Requests a jump, which happens as soon as the entity can jump if that is within the jump
+    /// buffer window
+    pub fn request_jump(&mut self) {
+        self.buffered_jump = Some(self.jump_timing.buffer);
+    }
+
+    /// Advances the jump timers by `delta` seconds
+    ///
+    /// `grounded` is whether the entity is standing on the ground. Returns the vertical velocity
+    /// to jump with if a requested jump happens during this update.
+    pub fn update_jump(&mut self, delta: ::Float, grounded: bool) -> Option<::Float> {
+        if grounded {
+            self.coyote_time_left = Some(self.jump_timing.coyote_time);
+            self.jumping = false;
+        }
+
+        if self.buffered_jump.is_some() && self.coyote_time_left.is_some() {
+            self.buffered_jump = None;
+            self.coyote_time_left = None;
+            self.jumping = true;
+
+            return Some(self.jump_strength);
+        }
+
+        // The timers are counted down after checking them, so a jump requested on the same update
+        // that the entity leaves the ground can still happen, even if the windows are 0
+        self.buffered_jump = count_down(self.buffered_jump, delta);
+
+        if !grounded {
+            self.coyote_time_left = count_down(self.coyote_time_left, delta);
+        }
+
+        None
+    }
+
+    /// Releases the current jump, which makes it lower if the entity is still rising from it
+    ///
+    /// Returns the reduced vertical velocity to apply, if any.
+    pub fn release_jump(&mut self, vertical_velocity: ::Float) -> Option<::Float> {
+        if !self.jumping {
+            return None;
+        }
+
+        self.jumping = false;
+
+        if vertical_velocity > 0.0 {
+            Some(vertical_velocity * self.jump_timing.release_velocity_fraction)
+        } else {
+            None
+        }
+    }
+
+    /// Forgets requested jumps and the time left to jump after leaving the ground
+    pub fn reset_jump(&mut self) {
+        self.buffered_jump = None;
+        self.coyote_time_left = None;
+        self.jumping = false;
+    }
+}
+
+/// Subtracts `delta` from a timer, returning `None` once it has run out
+fn count_down(timer: Option<::Float>, delta: ::Float) -> Option<::Float> {
+    timer.map(|t| t - delta).filter(|&t| t > 0.0)
 }
 
 impl MovementForceGenerator {
     pub fn apply(&mut self) -> ForceGeneratorResult {
-        let ground_steepness_force = self.get_ground_steepness_force();
+        let ground_steepness_force = self.slide_force;
 
         let walk_force = self.walk_dir.map(|dir| {
             let dir = na::Vector3::new(dir.x, dir.y, 0.0);
@@ -107,3 +218,164 @@ impl MovementForceGenerator {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spring::Spring;
+
+    const DELTA: ::Float = 0.05;
+
+    fn movement() -> MovementForceGenerator {
+        let timing = JumpTiming {
+            buffer: 0.1,
+            coyote_time: 0.1,
+            release_velocity_fraction: 0.5,
+        };
+
+        MovementForceGenerator::new(1.0, 1.0, 10.0, timing)
+    }
+
+    fn controller_config() -> ControllerConfig {
+        ControllerConfig {
+            max_slope: cgmath::Rad(0.5),
+            slide_force: 10.0,
+            air_acceleration: 0.5,
+            friction: 0.5,
+            air_drag: 0.9,
+            max_turn_rate: cgmath::Rad(1.0),
+        }
+    }
+
+    /// Returns a spring resting on the ground
+    fn grounded_spring() -> Spring {
+        let mut spring = Spring::new(1.0, 1.0, 1.0);
+        spring.set_current_length(1.0);
+        spring.apply();
+
+        spring
+    }
+
+    #[test]
+    fn test_jump_on_ground() {
+        let mut movement = movement();
+        let spring = grounded_spring();
+
+        assert_eq!(movement.update_jump(DELTA, spring.is_enabled()), None);
+
+        movement.request_jump();
+        assert_eq!(movement.update_jump(DELTA, spring.is_enabled()), Some(10.0));
+
+        // The request is used up by the jump
+        assert_eq!(movement.update_jump(DELTA, spring.is_enabled()), None);
+    }
+
+    #[test]
+    fn test_jump_buffer() {
+        let mut movement = movement();
+        let mut spring = grounded_spring();
+
+        // Jump, and request another jump just before landing
+        movement.request_jump();
+        assert!(movement.update_jump(DELTA, spring.is_enabled()).is_some());
+        spring.disable_until_reenable_threshold();
+
+        spring.set_current_length(1.5);
+        spring.apply();
+        assert_eq!(movement.update_jump(DELTA, spring.is_enabled()), None);
+
+        movement.request_jump();
+        assert_eq!(movement.update_jump(DELTA, spring.is_enabled()), None);
+
+        // Landing within the buffer window jumps again
+        spring.set_current_length(0.8);
+        spring.apply();
+        assert!(spring.is_enabled());
+        assert_eq!(movement.update_jump(DELTA, spring.is_enabled()), Some(10.0));
+
+        // Landing after the buffer window doesn't
+        movement.request_jump();
+        assert_eq!(movement.update_jump(DELTA, false), None);
+        assert_eq!(movement.update_jump(DELTA, false), None);
+        assert_eq!(movement.update_jump(DELTA, false), None);
+        assert_eq!(movement.update_jump(DELTA, true), None);
+    }
+
+    #[test]
+    fn test_coyote_time() {
+        let mut movement = movement();
+        let mut spring = grounded_spring();
+
+        assert_eq!(movement.update_jump(DELTA, spring.is_enabled()), None);
+
+        // Walking off a ledge disables the spring
+        spring.set_current_length(2.0);
+        assert!(!spring.is_enabled());
+
+        assert_eq!(movement.update_jump(DELTA, spring.is_enabled()), None);
+
+        movement.request_jump();
+        assert_eq!(movement.update_jump(DELTA, spring.is_enabled()), Some(10.0));
+
+        // Coyote time doesn't allow a second jump in the air
+        movement.request_jump();
+        assert_eq!(movement.update_jump(DELTA, false), None);
+
+        // Jumping too long after leaving the ground doesn't work
+        let mut movement = self::movement();
+        assert_eq!(movement.update_jump(DELTA, true), None);
+        assert_eq!(movement.update_jump(DELTA, false), None);
+        assert_eq!(movement.update_jump(DELTA, false), None);
+
+        movement.request_jump();
+        assert_eq!(movement.update_jump(DELTA, false), None);
+    }
+
+    #[test]
+    fn test_slope_limit() {
+        let mut movement = movement();
+        let config = controller_config();
+
+        // A gentle slope can be walked on
+        let gentle = cgmath::Vector3::new(0.3, 0.0, 1.0).normalize();
+        assert!(!movement.update_ground_normal(gentle, &config));
+        assert_eq!(movement.get_ground_steepness_force(&config), None);
+
+        // A steep slope pushes the entity downhill, and the walking force is ignored
+        let steep = cgmath::Vector3::new(1.0, 0.0, 1.0).normalize();
+        assert!(movement.update_ground_normal(steep, &config));
+
+        movement.set_walk_direction(cgmath::Vector2::new(0.0, 1.0));
+
+        match movement.apply() {
+            ForceGeneratorResult::Some(force) => {
+                let expected = ::std::f64::consts::FRAC_PI_4 as ::Float * 10.0;
+
+                assert!((force.x - expected).abs() < 1e-6);
+                assert_eq!(force.y, 0.0);
+                assert_eq!(force.z, 0.0);
+            }
+            _ => panic!("Expected a slide force"),
+        }
+    }
+
+    #[test]
+    fn test_release_jump() {
+        let mut movement = movement();
+
+        // Releasing without jumping does nothing
+        assert_eq!(movement.release_jump(5.0), None);
+
+        movement.request_jump();
+        assert!(movement.update_jump(DELTA, true).is_some());
+        assert_eq!(movement.release_jump(8.0), Some(4.0));
+
+        // A jump can only be released once
+        assert_eq!(movement.release_jump(4.0), None);
+
+        // Releasing while falling doesn't change the velocity
+        movement.request_jump();
+        assert!(movement.update_jump(DELTA, true).is_some());
+        assert_eq!(movement.release_jump(-1.0), None);
+    }
+}
@@ -11,7 +11,10 @@ const SPRING_LENGTH_RE_ENABLE_PERCENTAGE: ::Float = 0.9;
 pub struct Spring {
     pub current_length: Option<::Float>,
     pub current_velocity: ::Float,
+    /// The resting length
     length: ::Float,
+    /// The resting length that the spring was created with
+    full_length: ::Float,
     stiffness: ::Float,
     friction: ::Float,
     /// The first field is whether the spring is enabled.
@@ -26,6 +29,7 @@ impl Spring {
             current_length: None,
             current_velocity: 0.0,
             length,
+            full_length: length,
             stiffness,
             friction,
             enabled: (true, false),
@@ -37,6 +41,11 @@ impl Spring {
         self.length
     }
 
+    /// Sets the resting length of this `Spring` to a fraction of the length it was created with
+    pub fn set_length_fraction(&mut self, fraction: ::Float) {
+        self.length = self.full_length * fraction;
+    }
+
     /// Sets the current length of this `Spring`
     ///
     /// Disables this `Spring` if the length changes by more than
@@ -119,3 +128,51 @@ impl Spring {
         ForceGeneratorResult::Some(total_force)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disable_on_length_change() {
+        let mut spring = Spring::new(1.0, 1.0, 1.0);
+
+        spring.set_current_length(1.0);
+        spring.set_current_length(1.2);
+        assert!(spring.is_enabled());
+
+        // Changing length by too much at once (such as when walking off a ledge) disables it
+        spring.set_current_length(2.0);
+        assert!(!spring.is_enabled());
+
+        // It is re-enabled once the ground is close again
+        spring.set_current_length(1.7);
+        spring.apply();
+        assert!(!spring.is_enabled());
+
+        spring.set_current_length(1.4);
+        spring.set_current_length(1.1);
+        spring.set_current_length(0.85);
+        spring.apply();
+        assert!(spring.is_enabled());
+    }
+
+    #[test]
+    fn test_reenable_threshold() {
+        let mut spring = Spring::new(1.0, 1.0, 1.0);
+        spring.set_current_length(0.8);
+        spring.disable_until_reenable_threshold();
+
+        // The spring isn't re-enabled until it has been extended past the threshold once
+        spring.apply();
+        assert!(!spring.is_enabled());
+
+        spring.set_current_length(0.95);
+        spring.apply();
+        assert!(!spring.is_enabled());
+
+        spring.set_current_length(0.85);
+        spring.apply();
+        assert!(spring.is_enabled());
+    }
+}
@@ -1,9 +1,11 @@
 //! Error types for this crate
 
+use assets::shader::CompilerMessage;
 use gfx;
 use glutin;
 use image_utils;
 
+use std::path::PathBuf;
 use std::{error, fmt, io};
 
 use builder::PassOutputError;
@@ -101,6 +103,9 @@ pub enum BuildError<S> {
     Image(image_utils::ImageError),
     /// Program linking error
     Program(gfx::shade::ProgramError),
+    /// Shader compilation error, with the path of the shader (relative to the shaders directory)
+    /// and the messages from the compiler, mapped back to the files they refer to
+    ShaderCompilation(PathBuf, Vec<CompilerMessage>),
     /// An I/O error, and the path of the file being accessed
     Io(io::Error, String),
     /// A string variant for convenience
@@ -127,6 +132,15 @@ impl<S: fmt::Debug + fmt::Display> fmt::Display for BuildError<S> {
             ),
             Image(e) => writeln!(fmt, "Image error: {}", e),
             Program(e) => writeln!(fmt, "Program linking error: {}", e),
+            ShaderCompilation(path, messages) => {
+                writeln!(fmt, "Error compiling shader `{}`:", path.display())?;
+
+                for message in messages {
+                    writeln!(fmt, "    {}", message)?;
+                }
+
+                Ok(())
+            }
             Io(e, path) => writeln!(fmt, "Io error (at path `{}`): {}", path, e),
             String(e) => writeln!(fmt, "Error: {}", e),
             Custom(e) => writeln!(fmt, "Custom `BuildError`: {}", e),
@@ -199,6 +213,7 @@ impl<S> From<image_utils::TextureError> for BuildError<S> {
         match e {
             image_utils::TextureError::Image(e) => BuildError::Image(e),
             image_utils::TextureError::Creation(e) => BuildError::Creation(e),
+            e @ image_utils::TextureError::CubemapFaceSize(..) => BuildError::Custom(Box::new(e)),
         }
     }
 }
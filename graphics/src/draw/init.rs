@@ -18,16 +18,18 @@ use animation;
 use gfx_device_gl;
 
 /// Initializes rendering-related components and systems
+///
+/// Returns a copy of the factory used by the draw system, along with the window and event loop.
 pub fn initialize<'a, 'b, 'c, 'd>(
     world: &mut specs::World,
     dispatcher: DispatcherBuilder<'a, 'b>,
     dispatcher_graphics: DispatcherBuilder<'c, 'd>,
-    init_test_entities: Box<Fn(&mut specs::World, &mut gfx_device_gl::Factory)>,
 ) -> (
     DispatcherBuilder<'a, 'b>,
     DispatcherBuilder<'c, 'd>,
     window::Window,
     EventsLoop,
+    gfx_device_gl::Factory,
 ) {
     // Initialize window settings
     let events = EventsLoop::new();
@@ -52,7 +54,7 @@ pub fn initialize<'a, 'b, 'c, 'd>(
     };
 
     // Initialize gfx structs
-    let (window, device, mut factory, main_color, main_depth) = {
+    let (window, device, factory, main_color, main_depth) = {
         let log = world.read_resource::<slog::Logger>();
 
         let (window, device, factory, main_color, main_depth) =
@@ -88,18 +90,13 @@ pub fn initialize<'a, 'b, 'c, 'd>(
     let dispatcher = param::init(world, dispatcher);
     let dispatcher = lighting_data::init(world, dispatcher);
 
-    // Add test entities
-    // TODO: Remove this when the game has a better initialization system
-    // NOTE: This line must come after registering all required components; move it around to
-    //       satisfy this as needed
-    init_test_entities(world, &mut factory);
-
     // Initialize systems
     let animation_system = animation::System::new(&factory);
     let create_new_window_views = |window: &glutin::WindowedContext<glutin::PossiblyCurrent>| {
         gfx_window_glutin::new_views(window)
     };
     let create_new_window_views = Box::new(create_new_window_views);
+    let factory_copy = factory.clone();
     let draw = draw::System::new(
         factory,
         window.get_window(),
@@ -127,7 +124,7 @@ pub fn initialize<'a, 'b, 'c, 'd>(
 
     let dispatcher_graphics = dispatcher_graphics.with_thread_local(draw);
 
-    (dispatcher, dispatcher_graphics, window, events)
+    (dispatcher, dispatcher_graphics, window, events, factory_copy)
 }
 
 /// A hack to register components with a `gfx::Resource` type parameter
@@ -19,6 +19,7 @@ pub use self::init::initialize;
 
 // TODO: Remove these re-exports when higher-level functionality is exposed
 pub use self::passes::shadow::{DirShadowSource, LightSpaceMatrix};
+pub use self::passes::skybox::{decode_skybox, upload_skybox, SKYBOX_DIR, SKYBOX_FACES};
 pub use self::types::{ColorFormat, DepthFormat};
 
 use assets;
@@ -26,7 +27,7 @@ use common::graphics::{Drawable, DrawableSkeletal, ParticleSource};
 use common::{self, glutin, config, conrod, shred, specs};
 use gfx::{self, handle};
 
-use rendergraph::error::Error;
+use rendergraph::error::{BuildError, Error, ErrorKind};
 use rendergraph::resources::TemporaryResources;
 use rendergraph::{builder, module, pass, RenderGraph};
 use slog;
@@ -143,7 +144,7 @@ where
 
             for (module, name) in modules {
                 module.setup_passes(&mut builder).unwrap_or_else(|e| {
-                    error!(log, "Error setting up `{}` module: {}", name, e);
+                    log_build_error(&log, &format!("Error setting up `{}` module", name), &e);
                     panic!(common::CRASH_MSG);
                 });
             }
@@ -223,7 +224,7 @@ where
                 window_event::Event::ReloadShaders => {
                     self.reload_shaders(&data.assets, &log)
                         .unwrap_or_else(|e| {
-                            error!(log, "Error reloading shaders: {}", e;);
+                            log_graph_error(&log, "Error reloading shaders", &e);
                         });
                 }
                 window_event::Event::WindowResized(new_size) => {
@@ -253,7 +254,7 @@ where
                     self.graph
                         .apply_config(&data.config.graphics, &mut self.factory, &data.assets)
                         .unwrap_or_else(|e| {
-                            error!(log, "Error apply graphics configuration: {}", e;);
+                            log_graph_error(&log, "Error applying graphics configuration", &e);
                         });
                 }
                 _ => {}
@@ -262,8 +263,8 @@ where
 
         self.graph.clear_targets();
 
-        // Only run the main graphics pipeline if a menu is not open
-        if data.ui_state.is_in_game() {
+        // Only run the main graphics pipeline if the game world is visible
+        if data.ui_state.shows_game_world() {
             let temporary_resources = TemporaryResources::new(
                 &data.drawable,
                 &data.drawable_skeletal,
@@ -303,3 +304,35 @@ where
         }
     }
 }
+
+/// Logs an error from the render graph
+fn log_graph_error(log: &slog::Logger, context: &str, e: &Error<String>) {
+    match *e.error_kind() {
+        ErrorKind::Build(ref err) => {
+            log_build_error(log, &format!("{} (in pass `{}`)", context, e.pass_name()), err)
+        }
+        ErrorKind::Run(_) => error!(log, "{}: {}", context, e;),
+    }
+}
+
+/// Logs an error from building a part of the render graph
+///
+/// Shader compilation errors are logged with one record per compiler message, so each message is
+/// shown with its file and line in the logs and the developer console.
+fn log_build_error(log: &slog::Logger, context: &str, e: &BuildError<String>) {
+    if let BuildError::ShaderCompilation(ref path, ref messages) = *e {
+        error!(log, "{}: error compiling shader `{}`", context, path.display(););
+
+        for message in messages {
+            match message.location {
+                Some(ref location) => error!(log, "{}", message; o!(
+                    "file" => location.file.to_string_lossy().into_owned(),
+                    "line" => location.line
+                )),
+                None => error!(log, "{}", message;),
+            }
+        }
+    } else {
+        error!(log, "{}: {}", context, e;);
+    }
+}
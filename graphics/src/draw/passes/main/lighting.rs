@@ -169,7 +169,8 @@ impl SpotLight {
 
 pub struct LightingPass<R: gfx::Resources> {
     bundle: gfx::Bundle<R, pipe::Data<R>>,
-    shadows: bool,
+    /// The variants with shadows enabled and disabled
+    variants: passes::PsoVariants<R, pipe::Meta>,
     shadow_map_size: texture::Size,
 }
 
@@ -187,7 +188,8 @@ impl<R: gfx::Resources> LightingPass<R> {
     where
         F: gfx::Factory<R>,
     {
-        let pso = Self::load_pso(factory, assets, shadows_enabled)?;
+        let (variants, pso) =
+            passes::PsoVariants::load(shadows_enabled, |e| Self::load_pso(factory, assets, e))?;
 
         // Create a screen quad
         let vertices = utils::create_screen_quad(|pos, uv| Vertex::new(pos, uv));
@@ -225,7 +227,7 @@ impl<R: gfx::Resources> LightingPass<R> {
 
         let pass = LightingPass {
             bundle: gfx::Bundle::new(slice, pso, data),
-            shadows: shadows_enabled,
+            variants,
             shadow_map_size,
         };
 
@@ -373,9 +375,7 @@ where
         factory: &mut F,
         assets: &assets::Assets,
     ) -> Result<(), BuildError<String>> {
-        self.bundle.pso = Self::load_pso(factory, assets, self.shadows)?;
-
-        Ok(())
+        self.variants.reload(&mut self.bundle.pso, |e| Self::load_pso(factory, assets, e))
     }
 
     fn handle_window_resize(
@@ -412,18 +412,16 @@ where
         &mut self,
         config: &config::GraphicsConfig,
         framebuffers: &mut Framebuffers<R, types::ColorFormat, types::DepthFormat>,
-        factory: &mut F,
-        assets: &assets::Assets,
+        _: &mut F,
+        _: &assets::Assets,
     ) -> Result<(), BuildError<String>> {
         let mut update_shadow_map = false;
         // If the shadows setting was changed, reload the shadow map (will be a dummy texture if
-        // shadows were disabled) and reload the shaders with the new shadows setting applied
-        if config.shadows != self.shadows {
-            self.shadows = config.shadows;
+        // shadows were disabled) and switch to the shaders with the new shadows setting applied
+        if config.shadows != self.variants.enabled() {
+            self.variants.select(config.shadows, &mut self.bundle.pso);
 
             update_shadow_map = true;
-
-            Pass::<R, C, F, _, _>::reload_shaders(self, factory, assets)?;
         }
 
         // If the shadow map size setting was changed and shadows are enabled, reload the resized
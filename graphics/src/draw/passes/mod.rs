@@ -8,16 +8,23 @@ pub mod shadow;
 pub mod skybox;
 
 use assets;
+use gfx::shade::{CreateShaderError, ProgramError};
 use gfx::traits::FactoryExt;
 use gfx::{self, pso};
 use rendergraph::error::BuildError;
 
-use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::mem;
 use std::path::Path;
 
-use assets::shader;
+use assets::{shader, CacheKey};
 
 /// Loads shaders from the provided paths, and returns a PSO built from the shaders and pipeline
+///
+/// The linked program is stored in the asset cache, so loading the same shaders with the same
+/// defines again (such as when reloading shaders that didn't change) doesn't compile them again.
 pub fn load_pso<R, F, P, I>(
     assets: &assets::Assets,
     factory: &mut F,
@@ -34,16 +41,184 @@ where
     P: AsRef<Path>,
     I: pso::PipelineInit,
 {
-    let vs = shader::load_shader_file(assets, vs_path, &defines)
+    let vs = shader::load_shader_file(assets, &vs_path, &defines)
         .map_err(|e| BuildError::Custom(e.into()))?;
-    let fs = shader::load_shader_file(assets, fs_path, &defines)
+    let fs = shader::load_shader_file(assets, &fs_path, &defines)
         .map_err(|e| BuildError::Custom(e.into()))?;
 
-    // NOTE: If create_pipeline_from_program is used here, the ProgramInfo can be printed, which may
-    //       be useful for debugging
-    let set = factory.create_shader_set(&vs, &fs)?;
+    let key = program_cache_key(
+        vs_path.as_ref(),
+        fs_path.as_ref(),
+        &vs.code,
+        &fs.code,
+        &defines,
+    );
+    let program = assets.cache().get_or_load(key, || {
+        factory
+            .link_program(&vs.code, &fs.code)
+            .map_err(|e| map_compilation_error::<String>(e, &vs, &fs))
+    })?;
 
+    // NOTE: The ProgramInfo of the program can be printed here, which may be useful for debugging
     factory
-        .create_pipeline_state(&set, primitive, rasterizer, init)
-        .map_err(|e| e.into())
+        .create_pipeline_from_program(&program, primitive, rasterizer, init)
+        .map_err(|e| BuildError::PipelineState(into_owned_error(e)))
+}
+
+/// Returns the key used to cache the program linked from the provided shaders
+///
+/// The key contains a hash of the preprocessed code and the defines, so the program is linked again
+/// when one of the shaders or a file they include changes. Programs are only cached in memory, so
+/// the key doesn't need to identify the driver.
+fn program_cache_key(
+    vs_path: &Path,
+    fs_path: &Path,
+    vs_code: &[u8],
+    fs_code: &[u8],
+    defines: &HashMap<String, String>,
+) -> CacheKey {
+    let mut hasher = DefaultHasher::new();
+    vs_code.hash(&mut hasher);
+    fs_code.hash(&mut hasher);
+    // Sort the defines, because the iteration order of a `HashMap` isn't stable
+    defines.iter().collect::<BTreeMap<_, _>>().hash(&mut hasher);
+
+    CacheKey::new(
+        vs_path,
+        format!("program {} {:016x}", fs_path.display(), hasher.finish()),
+    )
+}
+
+/// Converts a `PipelineStateError` that borrows the names of shader parameters into one that owns
+/// them
+fn into_owned_error(error: gfx::PipelineStateError<&str>) -> gfx::PipelineStateError<String> {
+    use gfx::PipelineStateError::*;
+
+    match error {
+        Program(e) => Program(e),
+        DescriptorInit(e) => DescriptorInit(e.into()),
+        DeviceCreate(e) => DeviceCreate(e),
+    }
+}
+
+/// The PSOs of the two variants of a pass that depends on an on/off graphics option (such as
+/// shadows)
+///
+/// Both variants are compiled when the pass is created and when shaders are reloaded, so toggling
+/// the option only swaps the PSO in use instead of stalling to compile shaders. Reloading shaders
+/// that didn't change is cheap, because `load_pso` caches linked programs.
+///
+/// NOTE: Linked programs are only cached in memory, not on disk between runs. The `gfx` GL backend
+///       only creates programs from shader source and keeps creating them from GL program names
+///       private, so a program loaded with `glProgramBinary` can't be turned into a PSO.
+pub struct PsoVariants<R: gfx::Resources, M> {
+    enabled: bool,
+    /// The PSO of the variant that isn't in use
+    inactive: pso::PipelineState<R, M>,
+}
+
+impl<R: gfx::Resources, M> PsoVariants<R, M> {
+    /// Loads both variants with `load`, and returns them along with the PSO of the selected variant
+    pub fn load<L>(
+        enabled: bool,
+        mut load: L,
+    ) -> Result<(Self, pso::PipelineState<R, M>), BuildError<String>>
+    where
+        L: FnMut(bool) -> Result<pso::PipelineState<R, M>, BuildError<String>>,
+    {
+        let active = load(enabled)?;
+        let inactive = load(!enabled)?;
+
+        Ok((Self { enabled, inactive }, active))
+    }
+
+    /// Returns whether the enabled variant is selected
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Reloads both variants with `load`, replacing `active` (the PSO in use) with the new PSO of
+    /// the selected variant
+    ///
+    /// Nothing is replaced if either variant fails to load.
+    pub fn reload<L>(
+        &mut self,
+        active: &mut pso::PipelineState<R, M>,
+        load: L,
+    ) -> Result<(), BuildError<String>>
+    where
+        L: FnMut(bool) -> Result<pso::PipelineState<R, M>, BuildError<String>>,
+    {
+        let (variants, pso) = Self::load(self.enabled, load)?;
+        *self = variants;
+        *active = pso;
+
+        Ok(())
+    }
+
+    /// Selects a variant, swapping `active` (the PSO in use) with the PSO of the other variant if
+    /// the selection changed
+    pub fn select(&mut self, enabled: bool, active: &mut pso::PipelineState<R, M>) {
+        if enabled != self.enabled {
+            mem::swap(active, &mut self.inactive);
+            self.enabled = enabled;
+        }
+    }
+}
+
+/// Converts a shader compilation error into a `BuildError`, with the compiler messages mapped back
+/// to the original shader files
+///
+/// The driver reports locations in the preprocessed code, which includes all `#include`d files.
+fn map_compilation_error<S>(
+    err: ProgramError,
+    vs: &shader::ShaderSource,
+    fs: &shader::ShaderSource,
+) -> BuildError<S> {
+    let (log, source) = match err {
+        ProgramError::Vertex(CreateShaderError::CompilationFailed(log)) => (log, vs),
+        ProgramError::Pixel(CreateShaderError::CompilationFailed(log)) => (log, fs),
+        err => return err.into(),
+    };
+
+    let source_map = &source.source_map;
+    let path = source_map.files().first().cloned().unwrap_or_default();
+
+    BuildError::ShaderCompilation(path, source_map.map_compiler_log(&log))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_program_cache_key() {
+        let to_map = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs.iter().map(|&(k, v)| (k.into(), v.into())).collect()
+        };
+        let key = |vs: &str, fs: &str, defines: &HashMap<String, String>| {
+            program_cache_key(
+                Path::new("a_vertex.glsl"),
+                Path::new("a_fragment.glsl"),
+                vs.as_bytes(),
+                fs.as_bytes(),
+                defines,
+            )
+        };
+
+        let both = to_map(&[("A", "1"), ("B", "2")]);
+        let base = key("vs", "fs", &both);
+
+        // The order the defines were inserted in doesn't matter
+        let mut reversed = HashMap::new();
+        reversed.insert("B".to_string(), "2".to_string());
+        reversed.insert("A".to_string(), "1".to_string());
+        assert_eq!(key("vs", "fs", &reversed), base);
+
+        // Changing the code or the defines invalidates the cached program
+        assert_ne!(key("vs2", "fs", &both), base);
+        assert_ne!(key("vs", "fs2", &both), base);
+        assert_ne!(key("vs", "fs", &to_map(&[("A", "1")])), base);
+        assert_ne!(key("vs", "fs", &to_map(&[("A", "1"), ("B", "3")])), base);
+    }
 }
@@ -37,7 +37,7 @@ impl Vertex {
 
 pub struct PostPass<R: gfx::Resources> {
     bundle: gfx::Bundle<R, pipe::Data<R>>,
-    enabled: bool,
+    variants: passes::PsoVariants<R, pipe::Meta>,
 }
 
 impl<R: gfx::Resources> PostPass<R> {
@@ -51,7 +51,8 @@ impl<R: gfx::Resources> PostPass<R> {
     where
         F: gfx::Factory<R>,
     {
-        let pso = Self::load_pso(factory, assets, enabled)?;
+        let (variants, pso) =
+            passes::PsoVariants::load(enabled, |e| Self::load_pso(factory, assets, e))?;
         // Create a screen quad to render to
         let vertices = utils::create_screen_quad(|pos, uv| Vertex::new(pos, uv));
         let (vbuf, slice) = factory.create_vertex_buffer_with_slice(&vertices, ());
@@ -68,7 +69,7 @@ impl<R: gfx::Resources> PostPass<R> {
 
         Ok(PostPass {
             bundle: gfx::Bundle::new(slice, pso, data),
-            enabled,
+            variants,
         })
     }
 
@@ -153,8 +154,7 @@ where
         factory: &mut F,
         assets: &assets::Assets,
     ) -> Result<(), BuildError<String>> {
-        self.bundle.pso = Self::load_pso(factory, assets, self.enabled)?;
-        Ok(())
+        self.variants.reload(&mut self.bundle.pso, |e| Self::load_pso(factory, assets, e))
     }
 
     fn handle_window_resize(
@@ -179,11 +179,10 @@ where
         &mut self,
         config: &config::GraphicsConfig,
         _: &mut Framebuffers<R, types::ColorFormat, types::DepthFormat>,
-        factory: &mut F,
-        assets: &assets::Assets,
+        _: &mut F,
+        _: &assets::Assets,
     ) -> Result<(), BuildError<String>> {
-        self.bundle.pso = Self::load_pso(factory, assets, config.postprocessing)?;
-        self.enabled = config.postprocessing;
+        self.variants.select(config.postprocessing, &mut self.bundle.pso);
         Ok(())
     }
 }
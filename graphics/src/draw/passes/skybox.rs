@@ -1,6 +1,6 @@
 //! Skybox pass
 
-use assets::{self, read_bytes};
+use assets::{self, CacheKey, Handle};
 use common::config;
 use gfx::traits::FactoryExt;
 use gfx::{self, format, handle, state};
@@ -18,6 +18,7 @@ use camera::Camera;
 use draw::glsl::{Mat4, Vec2, Vec4};
 use draw::passes::resource_pass;
 use draw::{passes, types};
+use obj_loading::{IoError, ObjError};
 
 gfx_defines! {
     vertex Vertex {
@@ -39,6 +40,26 @@ gfx_defines! {
     }
 }
 
+/// The directory containing the skybox images, relative to the assets directory
+pub const SKYBOX_DIR: &str = "skybox";
+
+/// The skybox image files, in the order of the faces of `image_utils::CubemapData` (right, left,
+/// up, down, back, front)
+pub const SKYBOX_FACES: [&str; 6] = [
+    "right.jpg",
+    "left.jpg",
+    "top.jpg",
+    "bottom.jpg",
+    "back.jpg",
+    "front.jpg",
+];
+
+/// The color of the placeholder skybox, which is drawn until the skybox has been loaded
+const PLACEHOLDER_COLOR: [u8; 4] = [0x40, 0x4c, 0x60, 0xff];
+
+/// The skybox cubemap, as it is stored in the asset cache
+type Cubemap<R> = handle::ShaderResourceView<R, Vec4>;
+
 impl Vertex {
     pub fn new(pos: [f32; 2]) -> Self {
         Self { pos }
@@ -47,6 +68,8 @@ impl Vertex {
 
 pub struct SkyboxPass<R: gfx::Resources> {
     bundle: gfx::Bundle<R, pipe::Data<R>>,
+    /// The loaded skybox cubemap, or `None` while the placeholder is drawn
+    cubemap: Option<Handle<Cubemap<R>>>,
 }
 
 impl<R: gfx::Resources> SkyboxPass<R> {
@@ -73,33 +96,20 @@ impl<R: gfx::Resources> SkyboxPass<R> {
 
         let (vbuf, slice) = factory.create_vertex_buffer_with_slice(&vertices, &indices[..]);
 
-        // Create the skybox
-        // TODO: load this from individual map files
-        let skybox_dir = assets.get_assets_dir().join("skybox");
-        let path = |p| skybox_dir.join(p);
-
-        let read_image = |s| {
-            let p = path(s);
-            read_bytes(&p).map_err(|e| {
-                let p = p
-                    .to_str()
-                    .expect("Skybox image path contained invalid unicode")
-                    .to_string();
-                BuildError::Io(e, p)
-            })
+        // The skybox is loaded in the background by `AssetLoader`, so a placeholder is drawn until
+        // it is in the cache
+        let cubemap = cached_cubemap(assets);
+        let view = match cubemap {
+            Some(ref c) => (**c).clone(),
+            None => {
+                let face = image_utils::DecodedImage {
+                    size: [1, 1],
+                    data: PLACEHOLDER_COLOR.to_vec(),
+                };
+
+                image_utils::upload_cubemap::<_, _, image_utils::Srgba8>(factory, &vec![face; 6])?
+            }
         };
-        let cubemap = image_utils::load_cubemap::<_, _, image_utils::Srgba8>(
-            factory,
-            image_utils::CubemapData {
-                up: &read_image("top.jpg")?,
-                down: &read_image("bottom.jpg")?,
-                front: &read_image("front.jpg")?,
-                back: &read_image("back.jpg")?,
-                left: &read_image("left.jpg")?,
-                right: &read_image("right.jpg")?,
-            },
-            image_utils::JPEG,
-        )?;
 
         let sampler_info = gfx::texture::SamplerInfo::new(
             gfx::texture::FilterMethod::Bilinear,
@@ -108,7 +118,7 @@ impl<R: gfx::Resources> SkyboxPass<R> {
 
         let data = pipe::Data {
             vbuf,
-            skybox: (cubemap, factory.create_sampler(sampler_info)),
+            skybox: (view, factory.create_sampler(sampler_info)),
             locals: factory.create_constant_buffer(1),
             out_color: rtv.clone(),
             depth: dsv,
@@ -116,6 +126,7 @@ impl<R: gfx::Resources> SkyboxPass<R> {
 
         Ok(SkyboxPass {
             bundle: gfx::Bundle::new(slice, pso, data),
+            cubemap,
         })
     }
 
@@ -136,6 +147,58 @@ impl<R: gfx::Resources> SkyboxPass<R> {
     }
 }
 
+/// Returns the key used to cache the skybox cubemap
+fn cubemap_cache_key(assets: &assets::Assets) -> CacheKey {
+    CacheKey::new(assets.get_assets_dir().join(SKYBOX_DIR), "cubemap")
+}
+
+/// Returns the skybox cubemap, or `None` if it hasn't been loaded yet
+fn cached_cubemap<R: gfx::Resources>(assets: &assets::Assets) -> Option<Handle<Cubemap<R>>> {
+    assets.cache().get(&cubemap_cache_key(assets))
+}
+
+/// Reads and decodes the skybox faces, in the order of `SKYBOX_FACES`
+///
+/// This doesn't create any GPU resources, so it can be called from any thread.
+pub fn decode_skybox(
+    assets: &assets::Assets,
+) -> Result<Vec<image_utils::DecodedImage>, ObjError> {
+    let skybox_dir = assets.get_assets_dir().join(SKYBOX_DIR);
+
+    let faces = SKYBOX_FACES
+        .iter()
+        .map(|face| {
+            let path = skybox_dir.join(face);
+            let data = assets
+                .read_bytes(&path)
+                .map_err(|e| ObjError::Io(IoError(path, e)))?;
+
+            image_utils::decode_image(&data, image_utils::JPEG).map_err(ObjError::from)
+        }).collect::<Result<Vec<_>, _>>()?;
+
+    image_utils::check_cubemap_faces(&faces)?;
+
+    Ok(faces)
+}
+
+/// Creates the skybox cubemap from the faces decoded by `decode_skybox`, and adds it to the cache,
+/// where the skybox pass finds it
+pub fn upload_skybox<R, F>(
+    assets: &assets::Assets,
+    factory: &mut F,
+    faces: &[image_utils::DecodedImage],
+) -> Result<(), ObjError>
+where
+    R: gfx::Resources,
+    F: gfx::Factory<R>,
+{
+    assets.cache().get_or_load(cubemap_cache_key(assets), || {
+        image_utils::upload_cubemap::<_, _, image_utils::Srgba8>(factory, faces)
+    })?;
+
+    Ok(())
+}
+
 pub fn setup_pass<R, C, F>(
     builder: &mut types::GraphBuilder<R, C, F>,
 ) -> Result<(), BuildError<String>>
@@ -173,6 +236,16 @@ where
         resources: &mut Resources,
         _: TemporaryResources<R>,
     ) -> Result<(), RunError> {
+        // Replace the placeholder once the skybox has been loaded
+        if self.cubemap.is_none() {
+            let assets = resources.fetch::<Arc<assets::Assets>>();
+
+            if let Some(cubemap) = cached_cubemap(&assets) {
+                self.bundle.data.skybox.0 = (*cubemap).clone();
+                self.cubemap = Some(cubemap);
+            }
+        }
+
         let camera = resources.fetch::<Arc<Mutex<Camera>>>();
         let camera = camera.lock().unwrap();
         let locals = Locals {
@@ -21,34 +21,38 @@ extern crate assets;
 mod camera;
 mod animation;
 pub mod draw;
+pub mod loading;
 pub mod obj_loading;
 pub mod particles;
 
 use common::specs::{self, DispatcherBuilder};
-use common::{cgmath, config, gfx_device_gl, gfx_window_glutin, glutin, shred, Float};
+use common::{cgmath, config, console, gfx_device_gl, gfx_window_glutin, glutin, shred, Float};
+use window::window_event;
 
 use std::sync::{Arc, Mutex};
 
 /// Initializes graphics-related components and systems
+///
+/// A copy of the factory is returned so entities can be created after initialization (for example,
+/// once their assets have been loaded, or by console commands).
 pub fn initialize<'a, 'b, 'c, 'd>(
     world: &mut specs::World,
     dispatcher: DispatcherBuilder<'a, 'b>,
     dispatcher_graphics: DispatcherBuilder<'c, 'd>,
-    init_test_entities: Box<Fn(&mut specs::World, &mut gfx_device_gl::Factory)>,
 ) -> (
     DispatcherBuilder<'a, 'b>,
     DispatcherBuilder<'c, 'd>,
     glutin::EventsLoop,
+    gfx_device_gl::Factory,
 ) {
     // The camera resource must exist before calling draw::initialize
     world.add_resource(Arc::new(Mutex::new(camera::Camera::new_default(1.0, 45.0))));
 
-    // This must be initialized before init_test_entities is called
     let dispatcher = particles::initialize::<gfx_device_gl::Resources>(world, dispatcher);
 
     // Initialize subsystems
-    let (dispatcher, dispatcher_graphics, window, events) =
-        draw::initialize(world, dispatcher, dispatcher_graphics, init_test_entities);
+    let (dispatcher, dispatcher_graphics, window, events, factory) =
+        draw::initialize(world, dispatcher, dispatcher_graphics);
 
     // Add resources
     {
@@ -72,5 +76,21 @@ pub fn initialize<'a, 'b, 'c, 'd>(
         //       frame behind. This should be fine because the window shouldn't be resized often.
         .with(camera::System, "camera", &[]);
 
-    (dispatcher, dispatcher_graphics, events)
+    (dispatcher, dispatcher_graphics, events, factory)
+}
+
+/// Registers console commands for the systems in this crate
+pub fn register_commands(commands: &mut console::CommandRegistry) {
+    commands.register(
+        "reload_shaders",
+        "reload_shaders",
+        "Reloads all shaders from the assets directory",
+        Box::new(|world: &mut specs::World, _: &[&str]| {
+            world
+                .write_resource::<window_event::EventChannel>()
+                .single_write(window_event::Event::ReloadShaders);
+
+            Ok(None)
+        }),
+    );
 }
@@ -0,0 +1,169 @@
+//! Loading of assets on worker threads
+//!
+//! Files are read and decoded on worker threads, and only the GPU resources are created on the
+//! thread that owns the factory.
+
+use assets::Assets;
+use common::graphics::Material;
+use common::{self, LoadingProgress};
+use gfx;
+use image_utils;
+use slog;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use draw;
+use obj_loading::{self, ObjData, ObjError};
+
+/// The number of worker threads used to load assets
+const WORKER_THREADS: usize = 4;
+
+/// An asset to be loaded by an `AssetLoader`
+#[derive(Clone, Debug)]
+pub enum LoadRequest {
+    /// The model with the provided name, as loaded by `obj_loading::load_obj`
+    Model { name: String, material: Material },
+    /// The PNG texture at the provided path, as loaded by `obj_loading::load_texture` in the
+    /// `Srgba8` format
+    Texture(PathBuf),
+    /// The skybox cubemap, which the skybox pass draws once it is loaded
+    Skybox,
+}
+
+/// An asset that has been decoded on a worker thread, but not yet uploaded to the GPU
+enum Decoded {
+    Model(ObjData, Material),
+    Texture(PathBuf, image_utils::DecodedImage),
+    Skybox(Vec<image_utils::DecodedImage>),
+}
+
+/// The result of decoding an asset, or `Err` if the worker thread panicked
+type WorkerResult = thread::Result<Result<Decoded, ObjError>>;
+
+/// Loads assets in the background
+///
+/// Requested assets are decoded on worker threads, then uploaded to the GPU and added to the asset
+/// cache by `upload_finished`. Once an asset is loaded, the regular loading functions return it from
+/// the cache without blocking.
+pub struct AssetLoader {
+    assets: Arc<Assets>,
+    log: slog::Logger,
+    sender: mpsc::Sender<LoadRequest>,
+    receiver: mpsc::Receiver<(LoadRequest, WorkerResult)>,
+    progress: LoadingProgress,
+}
+
+impl AssetLoader {
+    /// Starts the worker threads
+    ///
+    /// The worker threads stop when the `AssetLoader` is dropped.
+    pub fn new(assets: Arc<Assets>, log: slog::Logger) -> Self {
+        let (sender, requests) = mpsc::channel::<LoadRequest>();
+        let (results, receiver) = mpsc::channel();
+        let requests = Arc::new(Mutex::new(requests));
+
+        for _ in 0..WORKER_THREADS {
+            let requests = requests.clone();
+            let results = results.clone();
+            let assets = assets.clone();
+
+            thread::spawn(move || loop {
+                // NOTE: The lock is released before decoding, so the other workers can take
+                //       requests in the meantime
+                let request = requests.lock().unwrap().recv();
+                let request = match request {
+                    Ok(r) => r,
+                    // The loader was dropped
+                    Err(_) => break,
+                };
+
+                let result = panic::catch_unwind(AssertUnwindSafe(|| decode(&assets, &request)));
+
+                if results.send((request, result)).is_err() {
+                    break;
+                }
+            });
+        }
+
+        Self {
+            assets,
+            log,
+            sender,
+            receiver,
+            progress: LoadingProgress::default(),
+        }
+    }
+
+    /// Requests that an asset is loaded in the background
+    pub fn request(&mut self, request: LoadRequest) {
+        self.progress.total += 1;
+        self.sender
+            .send(request)
+            .expect("Asset loader worker threads stopped");
+    }
+
+    /// Uploads the assets that have finished decoding to the GPU, and returns the current progress
+    ///
+    /// This must be called on the thread that owns the factory. Assets that fail to load are
+    /// logged and counted as loaded, so loading always finishes.
+    pub fn upload_finished<R, F>(&mut self, factory: &mut F) -> LoadingProgress
+    where
+        R: gfx::Resources,
+        F: gfx::Factory<R>,
+    {
+        for (request, result) in self.receiver.try_iter() {
+            let result = match result {
+                Ok(r) => r,
+                Err(_) => {
+                    error!(self.log, "Worker thread panicked while loading {:?}", request;);
+                    panic!(common::CRASH_MSG);
+                }
+            };
+
+            let result = result.and_then(|decoded| match decoded {
+                Decoded::Model(data, material) => {
+                    obj_loading::upload_obj(&self.assets, factory, data, material).map(|_| ())
+                }
+                Decoded::Texture(path, image) => {
+                    obj_loading::upload_texture::<image_utils::Srgba8, _, _>(
+                        &self.assets,
+                        factory,
+                        &path,
+                        &image,
+                    ).map(|_| ())
+                }
+                Decoded::Skybox(faces) => draw::upload_skybox(&self.assets, factory, &faces),
+            });
+
+            if let Err(e) = result {
+                error!(self.log, "Error loading {:?}: {}", request, e;);
+            }
+
+            self.progress.loaded += 1;
+        }
+
+        self.progress
+    }
+
+    /// Returns the current progress
+    pub fn progress(&self) -> LoadingProgress {
+        self.progress
+    }
+}
+
+/// Reads and decodes the requested asset
+fn decode(assets: &Assets, request: &LoadRequest) -> Result<Decoded, ObjError> {
+    match *request {
+        LoadRequest::Model { ref name, material } => {
+            obj_loading::decode_obj(assets, name).map(|data| Decoded::Model(data, material))
+        }
+        LoadRequest::Texture(ref path) => {
+            obj_loading::decode_texture(assets, path)
+                .map(|image| Decoded::Texture(path.clone(), image))
+        }
+        LoadRequest::Skybox => draw::decode_skybox(assets).map(Decoded::Skybox),
+    }
+}
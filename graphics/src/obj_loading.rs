@@ -1,38 +1,310 @@
 //! OBJ loading
 
 use assets;
-use common::ncollide3d::shape;
-use common::{self, na};
+use assets::{CacheKey, Handle};
+use common::ncollide3d::shape::{self, ShapeHandle};
+use common::na;
 use genmesh::{self, Triangulate};
 use gfx::traits::FactoryExt;
 use gfx::{self, format, handle};
 use image_utils;
 use obj;
-use slog;
 
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::io::{self, BufReader};
 use std::path::{Path, PathBuf};
 
-use common::graphics::{Drawable, Material, Vertex};
+use common::graphics::{Drawable, Material, TextureView, Vertex};
 
 type Polygon = genmesh::Polygon<obj::IndexTuple>;
 
+/// A model loaded from an OBJ file
+pub struct Model<R: gfx::Resources> {
+    objects: Vec<(Drawable<R>, ShapeHandle<::Float>)>,
+    // NOTE: These are stored so the cached textures are not evicted while the model is in use
+    _textures: Vec<Handle<TextureView<R>>>,
+}
+
+impl<R: gfx::Resources> Model<R> {
+    /// Returns a `Drawable` and collision shape for each object in the model
+    pub fn objects(&self) -> &[(Drawable<R>, ShapeHandle<::Float>)] {
+        &self.objects
+    }
+}
+
+/// Returns the key used to cache the model with the provided name and material
+pub fn model_cache_key(assets: &assets::Assets, name: &str, material: Material) -> CacheKey {
+    CacheKey::new(
+        assets.get_model_path(name.to_owned() + ".obj"),
+        format!("shininess={}", material.shininess),
+    )
+}
+
+/// The data of a model decoded from its OBJ, MTL, and texture files, before any GPU resources are
+/// created for it
+///
+/// Decoding a model doesn't need a factory, so this can be created on a worker thread with
+/// `decode_obj` and uploaded to the GPU later on the render thread with `upload_obj`.
+pub struct ObjData {
+    name: String,
+    objects: Vec<ObjectData>,
+    textures: Vec<(PathBuf, image_utils::DecodedImage)>,
+}
+
+impl ObjData {
+    /// Returns the name of the model
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// The data of a single object in a model
+struct ObjectData {
+    vertices: Vec<Vertex>,
+    diffuse_path: PathBuf,
+    specular_path: PathBuf,
+    shape: ShapeHandle<::Float>,
+}
+
 /// Loads an OBJ file from the provided path, and creates a `Drawable` and `TriMesh` for each object
 /// from it
+///
+/// The model is cached, so loading it again returns a handle to the same model instead of creating
+/// new GPU resources.
 pub fn load_obj<R, F>(
     assets: &assets::Assets,
     factory: &mut F,
     name: &str,
     material: Material,
-    log: &slog::Logger,
-) -> Result<Vec<(Drawable<R>, shape::TriMesh<::Float>)>, ObjError>
+) -> Result<Handle<Model<R>>, ObjError>
+where
+    R: gfx::Resources,
+    F: gfx::Factory<R>,
+{
+    assets
+        .cache()
+        .get_or_load(model_cache_key(assets, name, material), || {
+            let data = decode_obj(assets, name)?;
+            create_model(assets, factory, data, material)
+        })
+}
+
+/// Creates the GPU resources of a model decoded by `decode_obj`, and adds it to the cache
+///
+/// If the model is already cached, the cached model is returned and the decoded data is discarded.
+pub fn upload_obj<R, F>(
+    assets: &assets::Assets,
+    factory: &mut F,
+    data: ObjData,
+    material: Material,
+) -> Result<Handle<Model<R>>, ObjError>
+where
+    R: gfx::Resources,
+    F: gfx::Factory<R>,
+{
+    let key = model_cache_key(assets, &data.name, material);
+
+    assets
+        .cache()
+        .get_or_load(key, || create_model(assets, factory, data, material))
+}
+
+/// Reads and decodes the model with the provided name, including its textures and collision
+/// shapes
+///
+/// This doesn't create any GPU resources, so it can be called from any thread.
+pub fn decode_obj(assets: &assets::Assets, name: &str) -> Result<ObjData, ObjError> {
+    let obj = read_obj(assets, name)?;
+    let mut textures: Vec<(PathBuf, image_utils::DecodedImage)> = Vec::new();
+    let mut objects = Vec::new();
+
+    let mut meshes = Vec::new();
+    for object in &obj.objects {
+        meshes.extend(load_object(&obj, object)?);
+    }
+
+    for (vertices, diffuse_tex_path) in meshes {
+        let (diffuse_path, specular_path) = texture_paths(assets, &diffuse_tex_path);
+
+        // Decode each texture only once, even if several objects use it
+        for path in &[&diffuse_path, &specular_path] {
+            if !textures.iter().any(|(p, _)| p == *path) {
+                textures.push(((*path).clone(), decode_texture(assets, path)?));
+            }
+        }
+
+        let shape = {
+            // Collect vertices of the mesh
+            let mesh_vertices = vertices
+                .iter()
+                .map(|v| {
+                    na::Point3::new(
+                        v.pos[0] as ::Float,
+                        v.pos[1] as ::Float,
+                        v.pos[2] as ::Float,
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            if mesh_vertices.is_empty() {
+                return Err(ObjError::EmptyObj);
+            }
+
+            // Collect indices of the mesh
+            let mut mesh_indices = Vec::new();
+            let mut i = 0;
+
+            while i < mesh_vertices.len() - 1 {
+                mesh_indices.push(na::Point3::new(i, i + 1, i + 2));
+                i += 3;
+            }
+
+            ShapeHandle::new(shape::TriMesh::new(mesh_vertices, mesh_indices, None))
+        };
+
+        objects.push(ObjectData {
+            vertices,
+            diffuse_path,
+            specular_path,
+            shape,
+        });
+    }
+
+    Ok(ObjData {
+        name: name.to_owned(),
+        objects,
+        textures,
+    })
+}
+
+/// Creates the GPU resources of a decoded model
+fn create_model<R, F>(
+    assets: &assets::Assets,
+    factory: &mut F,
+    data: ObjData,
+    material: Material,
+) -> Result<Model<R>, ObjError>
 where
     R: gfx::Resources,
     F: gfx::Factory<R>,
 {
+    let textures = data
+        .textures
+        .iter()
+        .map(|(path, image)| {
+            upload_texture::<image_utils::Srgba8, _, _>(assets, factory, path, image)
+                .map(|t| (path.clone(), t))
+        }).collect::<Result<HashMap<_, _>, _>>()?;
+
+    let objects = data
+        .objects
+        .into_iter()
+        .map(|o| {
+            let (vbuf, slice) = factory.create_vertex_buffer_with_slice(&o.vertices, ());
+            let drawable = Drawable::new(
+                vbuf,
+                slice,
+                (*textures[&o.diffuse_path]).clone(),
+                (*textures[&o.specular_path]).clone(),
+                material,
+            );
+
+            (drawable, o.shape)
+        }).collect();
+
+    Ok(Model {
+        objects,
+        _textures: textures.into_iter().map(|(_, t)| t).collect(),
+    })
+}
+
+/// Returns the paths of all files that the model with the provided name is loaded from (the OBJ
+/// file, its MTL files, and its textures)
+///
+/// This is used to find which models must be reloaded when a file changes.
+pub fn model_dependencies(assets: &assets::Assets, name: &str) -> Result<Vec<PathBuf>, ObjError> {
+    let obj = read_obj(assets, name)?;
+
+    let mut paths = vec![assets.get_model_path(name.to_owned() + ".obj")];
+    paths.extend(obj.material_libs.iter().map(PathBuf::from));
+
+    for object in &obj.objects {
+        for group in &object.groups {
+            let map_kd = group.material.as_ref().and_then(|m| m.map_kd.as_ref());
+            let (diffuse, specular) = match map_kd {
+                Some(p) => texture_paths(assets, p),
+                None => continue,
+            };
+
+            paths.push(diffuse);
+            paths.push(specular);
+        }
+    }
+
+    paths.sort();
+    paths.dedup();
+
+    Ok(paths)
+}
+
+/// Returns the paths of the diffuse and specular textures of a material, given the diffuse texture
+/// path from its MTL file
+///
+/// Only the diffuse texture is specified in MTL files, so the specular texture is found by
+/// replacing the `_diffuse.png` suffix with `_specular.png`.
+fn texture_paths(assets: &assets::Assets, map_kd: &str) -> (PathBuf, PathBuf) {
+    let tex_path = map_kd.replace("_diffuse.png", "");
+
+    (
+        assets.get_model_path(tex_path.clone() + "_diffuse.png"),
+        assets.get_model_path(tex_path + "_specular.png"),
+    )
+}
+
+/// Reads the MTL file at the provided path, and returns the paths of the diffuse and specular
+/// textures used by its materials
+///
+/// Returns an error if a material has no diffuse texture.
+pub fn mtl_textures<P: AsRef<Path>>(
+    assets: &assets::Assets,
+    path: P,
+) -> Result<Vec<PathBuf>, ObjError> {
+    let path = path.as_ref();
+    let data = assets.read_bytes(path).map_err(|err| {
+        ObjError::MtlError(MtlError {
+            path: path.to_string_lossy().into_owned(),
+            err,
+        })
+    })?;
+
+    let mtl = obj::Mtl::load(&mut BufReader::new(data.as_slice()));
+    let mut paths = Vec::new();
+
+    for material in &mtl.materials {
+        let map_kd = material
+            .map_kd
+            .as_ref()
+            .ok_or_else(|| ObjError::MissingDiffuseTexture(material.name.clone()))?;
+        let (diffuse, specular) = texture_paths(assets, map_kd);
+
+        paths.push(diffuse);
+        paths.push(specular);
+    }
+
+    paths.sort();
+    paths.dedup();
+
+    Ok(paths)
+}
+
+/// Reads the OBJ file of the model with the provided name, and loads its MTL files
+fn read_obj(assets: &assets::Assets, name: &str) -> Result<obj::Obj<'static, Polygon>, ObjError> {
     // Read data from the file
     let path = assets.get_model_path(name.to_owned() + ".obj");
-    let data = assets::read_bytes(&path).map_err(|e| ObjError::Io(IoError(path.clone(), e)))?;
+    let data = assets
+        .read_bytes(&path)
+        .map_err(|e| ObjError::Io(IoError(path.clone(), e)))?;
 
     let mut buf_reader = BufReader::new(data.as_slice());
     let mut obj =
@@ -42,76 +314,48 @@ where
         *path = assets.get_model_path(&path).to_str().unwrap().to_string();
     }
 
-    obj.load_mtls().map_err(|e| {
-        let first_error = e.into_iter().next().unwrap();
-        ObjError::MtlError(MtlError {
-            path: first_error.0,
-            err: first_error.1,
-        })
-    })?;
+    load_mtls(assets, &mut obj)?;
 
-    obj.objects
-        .iter()
-        .flat_map(|o| {
-            load_object(&obj, o, log)
-                .into_iter()
-                .map(|(vertices, diffuse_tex_path)| {
-                    let tex_path = diffuse_tex_path.replace("_diffuse.png", "").to_owned();
-
-                    let (vbuf, slice) = factory.create_vertex_buffer_with_slice(&vertices, ());
-
-                    let diffuse = load_texture::<_, image_utils::Srgba8, _, _>(
-                        factory,
-                        &assets.get_model_path(tex_path.clone() + "_diffuse.png"),
-                    )?;
-
-                    let specular = load_texture::<_, image_utils::Srgba8, _, _>(
-                        factory,
-                        &assets.get_model_path(tex_path + "_specular.png"),
-                    )?;
-
-                    let drawable = Drawable::new(vbuf, slice, diffuse, specular, material);
-
-                    let mesh = {
-                        // Collect vertices of the mesh
-                        let mesh_vertices = vertices
-                            .iter()
-                            .map(|v| {
-                                na::Point3::new(
-                                    v.pos[0] as ::Float,
-                                    v.pos[1] as ::Float,
-                                    v.pos[2] as ::Float,
-                                )
-                            })
-                            .collect::<Vec<_>>();
-
-                        if mesh_vertices.is_empty() {
-                            return Err(ObjError::EmptyObj);
-                        }
-
-                        // Collect indices of the mesh
-                        let mut mesh_indices = Vec::new();
-                        let mut i = 0;
-
-                        while i < mesh_vertices.len() - 1 {
-                            mesh_indices.push(na::Point3::new(i, i + 1, i + 2));
-                            i += 3;
-                        }
-
-                        shape::TriMesh::new(
-                            mesh_vertices,
-                            mesh_indices,
-                            None,
-                        )
-                    };
-
-                    Ok((drawable, mesh))
-                })
-                // Collecting into a vector then immediately consuming it is necessary to avoid a
-                // re-borrow lifetime error
-                .collect::<Vec<_>>()
-                .into_iter()
-        }).collect()
+    Ok(obj)
+}
+
+/// Loads the MTL files of the provided `Obj`, and replaces the materials of its groups with the
+/// loaded ones
+///
+/// This does the same as `Obj::load_mtls`, but reads the files through `Assets` so they can be
+/// loaded from an archive.
+fn load_mtls(
+    assets: &assets::Assets,
+    obj: &mut obj::Obj<'static, Polygon>,
+) -> Result<(), ObjError> {
+    let mut materials = HashMap::new();
+
+    for path in &obj.material_libs {
+        let data = assets.read_bytes(path).map_err(|err| {
+            ObjError::MtlError(MtlError {
+                path: path.clone(),
+                err,
+            })
+        })?;
+
+        let mtl = obj::Mtl::load(&mut BufReader::new(data.as_slice()));
+
+        for material in mtl.materials {
+            materials.insert(material.name.clone(), material);
+        }
+    }
+
+    for object in &mut obj.objects {
+        for group in &mut object.groups {
+            if let Some(ref mut material) = group.material {
+                if let Some(loaded) = materials.get(&material.name) {
+                    *material = Cow::Owned(loaded.clone());
+                }
+            }
+        }
+    }
+
+    Ok(())
 }
 
 // TODO: Use indices instead of cloning data to save memory
@@ -120,8 +364,7 @@ where
 fn load_object<'a>(
     obj: &obj::Obj<'a, Polygon>,
     object: &obj::Object<'a, Polygon>,
-    log: &slog::Logger,
-) -> Vec<(Vec<Vertex>, String)> {
+) -> Result<Vec<(Vec<Vertex>, String)>, ObjError> {
     let mut objects = Vec::new();
 
     // Create an object per group
@@ -131,9 +374,15 @@ fn load_object<'a>(
         for tri in group.polys.iter().cloned().triangulate() {
             // Create vertices from the triangles
             for v in &[tri.x, tri.y, tri.z] {
-                let pos = obj.position[v.0];
-                let uv = v.1.map(|i| obj.texture[i]).unwrap_or([0.0; 2]);
-                let normal = v.2.map(|i| obj.normal[i]).unwrap_or([1.0; 3]);
+                let pos = *obj.position.get(v.0).ok_or(ObjError::InvalidIndex)?;
+                let uv = match v.1 {
+                    Some(i) => *obj.texture.get(i).ok_or(ObjError::InvalidIndex)?,
+                    None => [0.0; 2],
+                };
+                let normal = match v.2 {
+                    Some(i) => *obj.normal.get(i).ok_or(ObjError::InvalidIndex)?,
+                    None => [1.0; 3],
+                };
 
                 let pos = transform_coords(pos);
                 let normal = transform_coords(normal);
@@ -142,23 +391,20 @@ fn load_object<'a>(
             }
         }
 
-        let material = group.material.as_ref().unwrap_or_else(|| {
-            error!(log, "Missing material for group `{}`", group.name;);
-            // TODO: Just use a default material instead of crashing here
-            panic!(common::CRASH_MSG);
-        });
-
-        objects.push((
-            vertices,
-            material.map_kd.clone().unwrap_or_else(|| {
-                error!(log, "Material `{}` has no diffuse texture", material.name;);
-                // TODO: Just use a default texture instead of crashing here
-                panic!(common::CRASH_MSG);
-            }),
-        ));
+        // TODO: Use a default material and texture instead of failing here
+        let material = group
+            .material
+            .as_ref()
+            .ok_or_else(|| ObjError::MissingMaterial(group.name.clone()))?;
+        let map_kd = material
+            .map_kd
+            .clone()
+            .ok_or_else(|| ObjError::MissingDiffuseTexture(material.name.clone()))?;
+
+        objects.push((vertices, map_kd));
     }
 
-    objects
+    Ok(objects)
 }
 
 /// Applies a transformation to the coordinates to make the in-game model match the view in Blender
@@ -202,19 +448,74 @@ quick_error! {
         EmptyObj {
             display("OBJ with no vertices")
         }
+        InvalidIndex {
+            display("OBJ with a face that refers to a missing vertex, texture coordinate or normal")
+        }
+        MissingMaterial(group: String) {
+            display("Missing material for group `{}`", group)
+        }
+        MissingDiffuseTexture(material: String) {
+            display("Material `{}` has no diffuse texture", material)
+        }
         MtlError(err: MtlError) {
             display("Error loading material at path `{}`: {}", err.path, err.err)
         }
     }
 }
 
+/// Returns the key used to cache the texture at the provided path in the format `CF`
+fn texture_cache_key<CF: format::Formatted>(path: &Path) -> CacheKey {
+    CacheKey::new(path, format!("{:?}", CF::get_format()))
+}
+
+/// Reads and decodes the texture at the provided path
+///
+/// The file should be in the PNG format. This doesn't create any GPU resources, so it can be called
+/// from any thread.
+pub fn decode_texture<P: AsRef<Path>>(
+    assets: &assets::Assets,
+    path: P,
+) -> Result<image_utils::DecodedImage, ObjError> {
+    let path = path.as_ref();
+    let data = assets
+        .read_bytes(path)
+        .map_err(|e| ObjError::Io(IoError(path.to_owned(), e)))?;
+
+    image_utils::decode_image(&data, image_utils::PNG).map_err(|e| e.into())
+}
+
+/// Creates a texture from an image decoded by `decode_texture`, and adds it to the cache
+///
+/// If the texture is already cached, the cached texture is returned instead.
+pub fn upload_texture<CF, R, F>(
+    assets: &assets::Assets,
+    factory: &mut F,
+    path: &Path,
+    image: &image_utils::DecodedImage,
+) -> Result<Handle<handle::ShaderResourceView<R, CF::View>>, ObjError>
+where
+    R: gfx::Resources,
+    F: gfx::Factory<R>,
+    CF: format::Formatted,
+    CF::Channel: format::TextureChannel,
+    CF::Surface: format::TextureSurface,
+    CF::View: Send + Sync + 'static,
+{
+    assets.cache().get_or_load(texture_cache_key::<CF>(path), || {
+        image_utils::load_texture_raw::<_, _, CF>(factory, image.size, &image.data)
+            .map_err(|e| ObjError::Texture(e.into()))
+    })
+}
+
 /// Loads a texture from the file at the provided path
 ///
-/// The file should be in the PNG format.
-fn load_texture<P, CF, R, F>(
+/// The file should be in the PNG format. The texture is cached, so loading it again returns a handle
+/// to the same texture.
+pub fn load_texture<P, CF, R, F>(
+    assets: &assets::Assets,
     factory: &mut F,
     path: P,
-) -> Result<handle::ShaderResourceView<R, CF::View>, ObjError>
+) -> Result<Handle<handle::ShaderResourceView<R, CF::View>>, ObjError>
 where
     R: gfx::Resources,
     F: gfx::Factory<R>,
@@ -222,9 +523,14 @@ where
     CF: format::Formatted,
     CF::Channel: format::TextureChannel,
     CF::Surface: format::TextureSurface,
+    CF::View: Send + Sync + 'static,
 {
-    let data = assets::read_bytes(&path)
-        .map_err(|e| ObjError::Io(IoError(path.as_ref().to_owned(), e)))?;
+    let path = path.as_ref();
+
+    assets.cache().get_or_load(texture_cache_key::<CF>(path), || {
+        let image = decode_texture(assets, path)?;
 
-    image_utils::load_texture::<_, _, CF>(factory, &data, image_utils::PNG).map_err(|e| e.into())
+        image_utils::load_texture_raw::<_, _, CF>(factory, image.size, &image.data)
+            .map_err(|e| ObjError::Texture(e.into()))
+    })
 }
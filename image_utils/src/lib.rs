@@ -13,10 +13,34 @@ use gfx::handle::ShaderResourceView;
 use gfx::{format, texture};
 
 use std::io::Cursor;
+use std::thread;
 
 pub use format::{Rgba8, Srgba8};
 pub use image::{ImageFormat, ImageError, JPEG, PNG};
 
+/// An image decoded into RGBA pixels, ready to be uploaded to the GPU
+///
+/// Decoding doesn't need a factory, so it can be done on any thread.
+#[derive(Clone, Debug)]
+pub struct DecodedImage {
+    pub size: [u32; 2],
+    pub data: Vec<u8>,
+}
+
+/// Decodes an image from the provided data
+pub fn decode_image(
+    data: &[u8],
+    format: image::ImageFormat,
+) -> Result<DecodedImage, TextureError> {
+    let img = image::load(Cursor::new(data), format)?.to_rgba();
+    let (w, h) = img.dimensions();
+
+    Ok(DecodedImage {
+        size: [w, h],
+        data: img.into_vec(),
+    })
+}
+
 /// Loads a texture from the provided data
 pub fn load_texture<F, R, CF>(
     factory: &mut F,
@@ -30,9 +54,8 @@ where
     CF::Channel: format::TextureChannel,
     CF::Surface: format::TextureSurface,
 {
-    let img = image::load(Cursor::new(data), format)?.to_rgba();
-    let (w, h) = img.dimensions();
-    load_texture_raw::<_, _, CF>(factory, [w, h], &img.into_vec()).map_err(|e| e.into())
+    let img = decode_image(data, format)?;
+    load_texture_raw::<_, _, CF>(factory, img.size, &img.data).map_err(|e| e.into())
 }
 
 /// Loads a texture from the data, assuming it is the given size and in the format specified by
@@ -70,19 +93,49 @@ pub struct CubemapData<'a> {
 }
 
 impl<'a> CubemapData<'a> {
-    fn as_array(self) -> [&'a [u8]; 6] {
-        [
-            self.right.into(),
-            self.left.into(),
-            self.up.into(),
-            self.down.into(),
-            self.back.into(),
-            self.front.into(),
-        ]
+    fn as_array(&self) -> [&'a [u8]; 6] {
+        [self.right, self.left, self.up, self.down, self.back, self.front]
+    }
+}
+
+/// Decodes the six faces of a cubemap from the provided data, each on its own thread
+pub fn decode_cubemap(
+    data: &CubemapData,
+    format: image::ImageFormat,
+) -> Result<Vec<DecodedImage>, TextureError> {
+    let threads = data
+        .as_array()
+        .iter()
+        .map(|d| {
+            let d = d.to_vec();
+            thread::spawn(move || decode_image(&d, format))
+        }).collect::<Vec<_>>();
+
+    threads
+        .into_iter()
+        .map(|t| t.join().expect("Cubemap decoding thread panicked"))
+        .collect()
+}
+
+/// Checks that the decoded faces of a cubemap are square and all the same size
+pub fn check_cubemap_faces(faces: &[DecodedImage]) -> Result<(), TextureError> {
+    let expected = match faces.first() {
+        Some(face) => face.size[0],
+        None => return Ok(()),
+    };
+
+    for (i, face) in faces.iter().enumerate() {
+        if face.size != [expected, expected] {
+            return Err(TextureError::CubemapFaceSize(i, face.size, expected));
+        }
     }
+
+    Ok(())
 }
 
 /// Loads a cubemap from the provided data
+///
+/// The faces are decoded in parallel, and only uploaded to the GPU on the calling thread.
 pub fn load_cubemap<F, R, CF>(
     factory: &mut F,
     data: CubemapData,
@@ -95,17 +148,35 @@ where
     CF::Channel: format::TextureChannel,
     CF::Surface: format::TextureSurface,
 {
-    let images = data
-        .as_array()
-        .into_iter()
-        .map(|d| image::load(Cursor::new(d), format).map(|i| i.to_rgba()))
-        .collect::<Result<Vec<_>, _>>()?;
+    let images = decode_cubemap(&data, format)?;
+
+    upload_cubemap::<_, _, CF>(factory, &images)
+}
+
+/// Creates a cubemap from its six decoded faces, in the order of the faces of `CubemapData`
+pub fn upload_cubemap<F, R, CF>(
+    factory: &mut F,
+    images: &[DecodedImage],
+) -> Result<gfx::handle::ShaderResourceView<R, CF::View>, TextureError>
+where
+    R: gfx::Resources,
+    F: gfx::Factory<R>,
+    CF: format::Formatted,
+    CF::Channel: format::TextureChannel,
+    CF::Surface: format::TextureSurface,
+{
+    check_cubemap_faces(images)?;
 
     let data: [&[u8]; 6] = [
-        &images[0], &images[1], &images[2], &images[3], &images[4], &images[5],
+        &images[0].data,
+        &images[1].data,
+        &images[2].data,
+        &images[3].data,
+        &images[4].data,
+        &images[5].data,
     ];
 
-    let kind = texture::Kind::Cube(images[0].dimensions().0 as u16);
+    let kind = texture::Kind::Cube(images[0].size[0] as u16);
 
     let texture =
         factory.create_texture_immutable_u8::<CF>(kind, texture::Mipmap::Allocated, &data[..])?;
@@ -126,5 +197,10 @@ quick_error! {
             display("Texture creation error: {}", err)
             from()
         }
+        // A face of a cubemap is not square, or is a different size than the first face
+        CubemapFaceSize(face: usize, size: [u32; 2], expected: u32) {
+            display("Cubemap face {} is {}x{}, expected {}x{}",
+                    face, size[0], size[1], expected, expected)
+        }
     }
 }
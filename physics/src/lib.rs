@@ -16,7 +16,9 @@ pub use init::initialize;
 
 #[allow(unused_imports)]
 use common::shred::{self, SystemData, ResourceId, Resources};
-use common::{specs, physics, na, ncollide3d, nphysics3d, Delta, Float};
+use common::specs::Join;
+use common::{console, specs, physics, na, ncollide3d, nphysics3d, Delta, Float};
+use nphysics3d::object::{BodyHandle, RigidBody};
 
 pub struct System;
 
@@ -46,3 +48,62 @@ impl System {
     // TODO: Implement this
     fn remove_dead_bodies(&mut self) {}
 }
+
+/// Moves a rigid body to the provided position, keeping its rotation and resetting its linear
+/// velocity
+///
+/// Returns `false` if the body does not exist or is not a rigid body.
+pub fn teleport(
+    world: &mut nphysics3d::world::World<::Float>,
+    handle: BodyHandle,
+    position: na::Vector3<::Float>,
+) -> bool {
+    if let Some(body) = world.body_mut(handle) {
+        if let Some(rb) = body.downcast_mut::<RigidBody<::Float>>() {
+            let rotation = rb.position().rotation;
+            rb.set_position(na::Isometry3::from_parts(position.into(), rotation));
+            rb.set_linear_velocity(na::zero());
+            rb.activate();
+
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Registers console commands for the systems in this crate
+pub fn register_commands(commands: &mut console::CommandRegistry) {
+    commands.register(
+        "tp",
+        "tp <x> <y> <z>",
+        "Teleports the player to the provided position",
+        Box::new(|world: &mut specs::World, args: &[&str]| {
+            if args.len() != 3 {
+                return Err("Expected exactly three arguments".to_string());
+            }
+
+            let coords = args
+                .iter()
+                .map(|a| a.parse::<::Float>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Invalid coordinate: {}", e))?;
+            let position = na::Vector3::new(coords[0], coords[1], coords[2]);
+
+            let player = world.read_storage::<common::Player>();
+            let physics = world.read_storage::<physics::Physics>();
+            let mut phys_world = world.write_resource::<nphysics3d::world::World<::Float>>();
+
+            for (p, _) in (&physics, &player).join() {
+                if teleport(&mut phys_world, p.get_root_handle(), position) {
+                    return Ok(Some(format!(
+                        "Teleported to ({}, {}, {})",
+                        coords[0], coords[1], coords[2]
+                    )));
+                }
+            }
+
+            Err("No player physics body found".to_string())
+        }),
+    );
+}
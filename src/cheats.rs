@@ -0,0 +1,121 @@
+//! Console commands for debug cheats
+
+use common::cgmath::{self, EuclideanSpace};
+use common::console::{CommandRegistry, CommandResult};
+use common::ncollide3d::query::Ray;
+use common::ncollide3d::world::CollisionGroups;
+use common::nphysics3d::world::World;
+use common::physics::Physics;
+use common::specs::{self, Join};
+use common::{na, Cheat, Cheats, Direction, Player, Position};
+use math::convert;
+use physics;
+
+use std::cmp::Ordering;
+
+use player::PLAYER_COLLIDER_RADIUS;
+
+/// The maximum distance of the raycast used by `tp_crosshair`
+const TELEPORT_MAX_DISTANCE: ::Float = 1000.0;
+
+/// The distance from the hit surface at which `tp_crosshair` places the player, so the player
+/// doesn't end up inside the surface
+const TELEPORT_SURFACE_OFFSET: ::Float = PLAYER_COLLIDER_RADIUS * 2.0;
+
+/// Registers console commands for toggling cheats
+pub fn register_commands(commands: &mut CommandRegistry) {
+    let toggles = [
+        ("noclip", "Fly through level geometry", Cheat::Noclip),
+        ("god", "Ignore all damage", Cheat::GodMode),
+        ("infinite_ammo", "Never run out of ammo", Cheat::InfiniteAmmo),
+    ];
+
+    for &(name, description, cheat) in &toggles {
+        commands.register(
+            name,
+            format!("{} [on|off]", name),
+            description,
+            Box::new(move |world: &mut specs::World, args: &[&str]| {
+                toggle_cheat(world, args, cheat)
+            }),
+        );
+    }
+
+    commands.register(
+        "tp_crosshair",
+        "tp_crosshair",
+        "Teleports the player to the surface under the crosshair",
+        Box::new(|world: &mut specs::World, _: &[&str]| {
+            if !world.read_resource::<Cheats>().allowed() {
+                return Err(common::CheatError::NotAllowed.to_string());
+            }
+
+            teleport_to_crosshair(world)
+        }),
+    );
+}
+
+/// Enables, disables, or toggles (if no argument is provided) the provided cheat
+fn toggle_cheat(world: &mut specs::World, args: &[&str], cheat: Cheat) -> CommandResult {
+    let mut cheats = world.write_resource::<Cheats>();
+
+    let enabled = match args {
+        [] => !cheats.is_enabled(cheat),
+        ["on"] => true,
+        ["off"] => false,
+        _ => return Err("Expected either `on`, `off`, or no argument".to_string()),
+    };
+
+    cheats.set(cheat, enabled).map_err(|e| e.to_string())?;
+
+    Ok(Some(format!(
+        "{:?} {}",
+        cheat,
+        if enabled { "enabled" } else { "disabled" }
+    )))
+}
+
+/// Casts a ray in the direction the player is looking, and moves the player to the closest surface
+/// that it hits
+fn teleport_to_crosshair(world: &mut specs::World) -> CommandResult {
+    let player = world.read_storage::<Player>();
+    let position = world.read_storage::<Position>();
+    let direction = world.read_storage::<Direction>();
+    let physics = world.read_storage::<Physics>();
+    let mut phys_world = world.write_resource::<World<::Float>>();
+
+    let (pos, dir, handle) = match (&position, &direction, &physics, &player).join().next() {
+        Some((pos, dir, p, _)) => (pos.0, dir.0, p.get_root_handle()),
+        None => return Err("No player entity found".to_string()),
+    };
+
+    // The camera looks along its negative z axis
+    let dir = convert::to_na_vector(dir * -cgmath::Vector3::unit_z());
+    let ray = Ray::new(na::Point3::from(convert::to_na_vector(pos.to_vec())), dir);
+    let groups = CollisionGroups::new();
+
+    // Find the closest surface hit by the ray, ignoring the player's own collider
+    let hit = phys_world
+        .collider_world()
+        .interferences_with_ray(&ray, &groups)
+        .filter(|(collider, _)| collider.body() != handle)
+        .map(|(_, intersection)| intersection)
+        .filter(|intersection| intersection.toi <= TELEPORT_MAX_DISTANCE)
+        .min_by(|a, b| a.toi.partial_cmp(&b.toi).unwrap_or(Ordering::Equal));
+
+    let hit = match hit {
+        Some(hit) => hit,
+        None => return Err("Nothing is under the crosshair".to_string()),
+    };
+
+    let target = ray.origin.coords + dir * hit.toi + hit.normal * TELEPORT_SURFACE_OFFSET;
+
+    if physics::teleport(&mut phys_world, handle, target) {
+        Ok(Some(format!(
+            "Teleported to ({:.2}, {:.2}, {:.2})",
+            target[0], target[1], target[2]
+        )))
+    } else {
+        Err("No player physics body found".to_string())
+    }
+}
@@ -0,0 +1,245 @@
+//! Validation of asset files, used by the `check-assets` command
+//!
+//! Every asset is loaded the same way the game loads it (without uploading anything to the GPU),
+//! and all problems are collected instead of stopping at the first one.
+
+use assets::{shader, Assets};
+use graphics::draw::{SKYBOX_DIR, SKYBOX_FACES};
+use graphics::obj_loading;
+use image_utils;
+use ron;
+use slog;
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A problem with an asset file
+#[derive(Debug)]
+pub struct Problem {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl Problem {
+    fn new<P: Into<PathBuf>, M: ToString>(path: P, message: M) -> Self {
+        Self {
+            path: path.into(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+/// Opens the provided asset roots and checks all of their files
+///
+/// The roots are ordered from lowest to highest priority, as in `Assets::new`.
+pub fn check_asset_roots(log: &slog::Logger, roots: Vec<PathBuf>) -> Vec<Problem> {
+    let root = roots.first().cloned().unwrap_or_default();
+
+    match Assets::new(log, roots) {
+        Ok(assets) => check_assets(&assets),
+        Err(e) => vec![Problem::new(root, e)],
+    }
+}
+
+/// Checks all asset files, returning every problem that was found
+///
+/// The checks are:
+///
+/// * Shaders are loaded with all of their `#include` directives resolved
+/// * OBJ files are parsed along with their MTL files and textures
+/// * MTL files are parsed, and the diffuse and specular textures of their materials are decoded
+/// * The skybox faces are decoded, and must be square and all the same size
+/// * Scene and wave files are parsed as RON
+pub fn check_assets(assets: &Assets) -> Vec<Problem> {
+    let files = match assets.files() {
+        Ok(f) => f,
+        Err(e) => return vec![Problem::new(assets.get_assets_dir(), e)],
+    };
+
+    let shaders_dir = assets.get_shader_path("");
+    let scenes_dir = assets.get_scene_path("");
+    let waves_dir = assets.get_wave_path("");
+
+    let mut problems = Vec::new();
+    let mut textures = BTreeSet::new();
+
+    for path in &files {
+        let extension = path.extension().and_then(|e| e.to_str());
+
+        match extension {
+            Some("glsl") if path.starts_with(&shaders_dir) => {
+                problems.extend(check_shader(assets, &shaders_dir, path))
+            }
+            Some("obj") if path.starts_with(assets.get_models_dir()) => {
+                problems.extend(check_obj(assets, path))
+            }
+            Some("mtl") => match obj_loading::mtl_textures(assets, path) {
+                Ok(paths) => textures.extend(paths),
+                Err(e) => problems.push(Problem::new(path.clone(), e)),
+            },
+            Some("ron") if path.starts_with(&scenes_dir) || path.starts_with(&waves_dir) => {
+                problems.extend(check_ron(assets, path))
+            }
+            _ => {}
+        }
+    }
+
+    for path in textures {
+        if let Err(e) = obj_loading::decode_texture(assets, &path) {
+            problems.push(Problem::new(path, e));
+        }
+    }
+
+    problems.extend(check_skybox(assets));
+
+    problems
+}
+
+/// Loads the shader at the provided path, resolving all `#include` directives
+fn check_shader(assets: &Assets, shaders_dir: &Path, path: &Path) -> Option<Problem> {
+    let relative = path.strip_prefix(shaders_dir).ok()?;
+
+    shader::load_shader_file(assets, relative, &HashMap::new())
+        .err()
+        .map(|e| Problem::new(path, e))
+}
+
+/// Loads the model in the provided OBJ file, including its materials and textures
+fn check_obj(assets: &Assets, path: &Path) -> Option<Problem> {
+    // Models are loaded by their path relative to the models directory, without the extension
+    let name = path
+        .strip_prefix(assets.get_models_dir())
+        .ok()?
+        .with_extension("");
+    let name = match name.to_str() {
+        Some(n) => n,
+        None => return Some(Problem::new(path, "Path contains invalid unicode")),
+    };
+
+    obj_loading::decode_obj(assets, name)
+        .err()
+        .map(|e| Problem::new(path, e))
+}
+
+/// Parses the provided scene or wave file as RON
+///
+/// NOTE: Scene and wave files don't have a defined format yet, so only their syntax is checked
+fn check_ron(assets: &Assets, path: &Path) -> Option<Problem> {
+    let data = match assets.read_bytes(path) {
+        Ok(d) => d,
+        Err(e) => return Some(Problem::new(path, e)),
+    };
+    let data = match String::from_utf8(data) {
+        Ok(d) => d,
+        Err(e) => return Some(Problem::new(path, e)),
+    };
+
+    ron::de::from_str::<ron::value::Value>(&data)
+        .err()
+        .map(|e| Problem::new(path, e))
+}
+
+/// Decodes the skybox faces, and checks that they can form a cubemap
+fn check_skybox(assets: &Assets) -> Vec<Problem> {
+    let skybox_dir = assets.get_assets_dir().join(SKYBOX_DIR);
+    let mut problems = Vec::new();
+    let mut faces = Vec::new();
+
+    for face in SKYBOX_FACES.iter() {
+        let path = skybox_dir.join(face);
+        let image = assets
+            .read_bytes(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| {
+                image_utils::decode_image(&data, image_utils::JPEG).map_err(|e| e.to_string())
+            });
+
+        match image {
+            Ok(image) => faces.push(image),
+            Err(e) => problems.push(Problem::new(path, e)),
+        }
+    }
+
+    // The sizes can only be compared if every face was decoded
+    if problems.is_empty() {
+        if let Err(e) = image_utils::check_cubemap_faces(&faces) {
+            problems.push(Problem::new(skybox_dir, e));
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env, fs, process};
+
+    /// Writes the provided files to a new temporary asset directory, and returns its path
+    fn write_assets(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = env::temp_dir().join(format!("horde_survival_{}_{}", name, process::id()));
+
+        for &(path, data) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, data).unwrap();
+        }
+
+        // Asset roots are canonicalized, so problem paths are relative to the canonical path
+        root.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn test_check_assets() {
+        let root = write_assets(
+            "check_assets",
+            &[
+                ("shaders/main.glsl", "#version 150\n#include \"missing.glsl\"\n"),
+                ("shaders/valid.glsl", "#version 150\nvoid main() {}\n"),
+                ("models/crate.mtl", "newmtl wood\nmap_Kd wood_diffuse.png\n"),
+                ("scenes/broken.ron", "(waves: [1, 2"),
+            ],
+        );
+        let log = slog::Logger::root(slog::Discard, o!());
+
+        let problems = check_asset_roots(&log, vec![root.clone()]);
+        let paths = problems
+            .iter()
+            .map(|p| p.path.strip_prefix(&root).unwrap().to_owned())
+            .collect::<BTreeSet<_>>();
+
+        fs::remove_dir_all(&root).unwrap();
+
+        // The shader includes a missing file, the material's textures are missing, the scene has a
+        // syntax error, and there is no skybox
+        let mut expected = vec![
+            PathBuf::from("shaders/main.glsl"),
+            PathBuf::from("models/wood_diffuse.png"),
+            PathBuf::from("models/wood_specular.png"),
+            PathBuf::from("scenes/broken.ron"),
+        ];
+        expected.extend(SKYBOX_FACES.iter().map(|f| Path::new(SKYBOX_DIR).join(f)));
+
+        assert_eq!(paths, expected.into_iter().collect());
+        assert_eq!(problems.len(), paths.len());
+    }
+
+    #[test]
+    fn test_missing_root() {
+        let log = slog::Logger::root(slog::Discard, o!());
+        let root = env::temp_dir().join(format!("horde_survival_missing_{}", process::id()));
+
+        let problems = check_asset_roots(&log, vec![root.clone()]);
+
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].path, root);
+    }
+}
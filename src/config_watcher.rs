@@ -0,0 +1,178 @@
+//! Live reloading of the configuration file when it is edited externally
+
+use common::{config, config_file, specs};
+use notify::{self, DebouncedEvent, RecursiveMode, Watcher};
+use slog;
+use window::window_event;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// The delay used to debounce file events (editors often write a file several times when saving)
+const WATCHER_DELAY_MS: u64 = 200;
+
+quick_error! {
+    /// An error while reloading the configuration file
+    #[derive(Debug)]
+    pub enum ReloadError {
+        Io(e: io::Error) {
+            display("Error reading configuration file: {}", e)
+            from()
+        }
+        ConfigFile(e: config_file::ConfigFileError) {
+            display("{}", e)
+            from()
+        }
+    }
+}
+
+/// Watches the configuration file for changes
+pub struct ConfigWatcher {
+    /// The canonical path of the configuration file, which event paths are compared to
+    path: PathBuf,
+    // NOTE: The watcher stops when dropped, so it must be stored here
+    _watcher: notify::RecommendedWatcher,
+    receiver: mpsc::Receiver<DebouncedEvent>,
+}
+
+impl ConfigWatcher {
+    /// Starts watching the configuration file at the provided path
+    pub fn new(path: PathBuf) -> notify::Result<Self> {
+        // Event paths are canonical, so a relative or symlinked path would never match them
+        let path = canonicalize(&path)?;
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::watcher(sender, Duration::from_millis(WATCHER_DELAY_MS))?;
+
+        // The directory is watched instead of the file, because editors often save by replacing
+        // the file, which would stop a watcher on the file itself
+        let dir = path.parent().map(|p| p.to_owned()).unwrap_or_else(|| path.clone());
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+
+        Ok(Self {
+            path,
+            _watcher: watcher,
+            receiver,
+        })
+    }
+
+    /// Returns whether the configuration file has changed since the last call to this function
+    pub fn poll(&self) -> bool {
+        let mut changed = false;
+
+        for event in self.receiver.try_iter() {
+            match event {
+                DebouncedEvent::Write(ref p)
+                | DebouncedEvent::Create(ref p)
+                | DebouncedEvent::Rename(_, ref p) => {
+                    if canonicalize(p).ok().as_ref() == Some(&self.path) {
+                        changed = true;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        changed
+    }
+
+    /// Reads, migrates and validates the configuration file
+    pub fn load(&self) -> Result<config_file::LoadedConfig, ReloadError> {
+        let data = fs::read_to_string(&self.path)?;
+
+        Ok(config_file::load(&data)?)
+    }
+}
+
+/// Returns the canonical form of a path to a file, which doesn't have to exist
+///
+/// If the file doesn't exist, its directory is canonicalized instead.
+fn canonicalize(path: &Path) -> io::Result<PathBuf> {
+    if let Ok(path) = path.canonicalize() {
+        return Ok(path);
+    }
+
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Path has no file name"))?;
+    // A relative path with a single component has an empty parent
+    let dir = match path.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+
+    Ok(dir.canonicalize()?.join(name))
+}
+
+/// Reloads the configuration file if it has changed, replacing the `Config` resource and sending
+/// `ConfigChanged` events for each section that changed
+///
+/// If the file can't be loaded, the error is logged and the current configuration is kept.
+pub fn reload_if_changed(watcher: &ConfigWatcher, world: &mut specs::World) {
+    if !watcher.poll() {
+        return;
+    }
+
+    let log = world.read_resource::<slog::Logger>().clone();
+
+    let loaded = match watcher.load() {
+        Ok(c) => c,
+        Err(e) => {
+            error!(log, "Error reloading configuration file: {}", e;);
+            return;
+        }
+    };
+
+    for warning in &loaded.warnings {
+        warn!(log, "Problem in configuration file: {}", warning;);
+    }
+
+    let new_config = loaded.config;
+
+    let changed = {
+        let mut config = world.write_resource::<config::Config>();
+        let changed = config.changed_sections(&new_config);
+        *config = new_config;
+
+        changed
+    };
+
+    if changed.is_empty() {
+        return;
+    }
+
+    info!(log, "Reloaded configuration file"; "changed" => format!("{:?}", changed));
+
+    let mut channel = world.write_resource::<window_event::EventChannel>();
+
+    for section in changed {
+        channel.single_write(window_event::Event::ConfigChanged(section.into()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env, process};
+
+    #[test]
+    fn test_canonicalize() {
+        let dir = env::temp_dir().join(format!("horde_survival_config_{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dir = dir.canonicalize().unwrap();
+        let path = dir.join("..").join(dir.file_name().unwrap()).join("config.ron");
+
+        // Paths are canonicalized whether or not the file exists
+        let missing = canonicalize(&path);
+        fs::write(&path, "()").unwrap();
+        let existing = canonicalize(&path);
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(missing.unwrap(), dir.join("config.ron"));
+        assert_eq!(existing.unwrap(), dir.join("config.ron"));
+    }
+}
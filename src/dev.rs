@@ -1,7 +1,7 @@
 //! Temporary hacks to set the game up for testing
 //! Will be removed when no longer needed
 
-use assets::Assets;
+use assets::{AnyHandle, Assets, CacheKey, Handle};
 use common::cgmath::*;
 use common::gfx::{self, handle, format};
 use common::graphics::{Material, Particle, ParticleSource, ShaderParam, SpawnParticleFn};
@@ -12,21 +12,130 @@ use common::nphysics3d::object::{BodyHandle, BodyStatus, ColliderDesc, RigidBody
 use common::nphysics3d::world::World;
 use common::nphysics3d::material;
 use common::physics::*;
-use common::specs::{self, Builder};
+use common::specs::{self, Builder, Join};
 use common::*;
+use common::console::CommandRegistry;
 use control::FloorHandle;
 use graphics::draw::components::*;
 use graphics::draw::LightSpaceMatrix;
+use graphics::loading::LoadRequest;
 use graphics::obj_loading;
+use hot_reload::ModelSource;
 use math::convert;
 use math::functions::dir_vec_to_quaternion;
-use physics::scale::Scale as ScaleTrait;
+use physics::scale::scale_shape;
 use player::{self, COLLIDER_MARGIN};
-use slog;
-use image_utils;
 
 use std::sync::Arc;
 
+/// The offset from the player at which entities are spawned if no position is provided
+const SPAWN_OFFSET: [::Float; 3] = [0.0, 0.0, 5.0];
+
+/// The texture used by the test particle source
+const PARTICLE_TEXTURE: &str = "test_particle.png";
+
+/// Returns the assets used by the test entities, so they can be loaded in the background before
+/// `add_test_entities` is called
+pub fn test_assets(assets: &Assets) -> Vec<LoadRequest> {
+    let model = |name: &str, shininess| LoadRequest::Model {
+        name: name.to_owned(),
+        material: Material::new(shininess),
+    };
+
+    vec![
+        model("player_controller_playground", 32.0),
+        model("box", 32.0),
+        model("light", 0.0),
+        LoadRequest::Texture(assets.get_model_path(PARTICLE_TEXTURE)),
+    ]
+}
+
+/// Registers console commands for spawning test entities
+pub fn register_commands(commands: &mut CommandRegistry, factory: gfx_device_gl::Factory) {
+    commands.register(
+        "spawn",
+        "spawn <box|point_light> [x y z]",
+        "Spawns a test entity at the provided position, or above the player",
+        Box::new(move |world: &mut specs::World, args: &[&str]| {
+            let (prefab, pos) = match args.split_first() {
+                Some((prefab, pos)) => (*prefab, pos),
+                None => return Err("Expected a prefab name".to_string()),
+            };
+
+            let pos = match pos.len() {
+                0 => spawn_position(world)?,
+                3 => {
+                    let coords = pos
+                        .iter()
+                        .map(|a| a.parse::<::Float>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|e| format!("Invalid coordinate: {}", e))?;
+
+                    [coords[0], coords[1], coords[2]]
+                }
+                _ => return Err("Expected either zero or three coordinates".to_string()),
+            };
+
+            let mut factory = factory.clone();
+
+            match prefab {
+                "box" => {
+                    let _ = create_test_entity(
+                        world,
+                        &mut factory,
+                        "box",
+                        pos,
+                        Direction::default(),
+                        1.0,
+                        Material::new(32.0),
+                        Some(material::BasicMaterial::new(0.0, 0.0)),
+                        Box::new(|e| e),
+                    );
+                }
+                "point_light" => {
+                    let light_color = LightColor::new(
+                        [0.1, 0.1, 0.1, 1.0],
+                        [1.0, 1.0, 1.0, 1.0],
+                        [0.5, 0.5, 0.5, 1.0],
+                    );
+
+                    create_point_light(
+                        world,
+                        &mut factory,
+                        pos,
+                        light_color,
+                        LightAttenuation::new(1.0, 0.14, 0.07),
+                        Box::new(|e| e),
+                    );
+                }
+                _ => return Err(format!("Unknown prefab `{}`", prefab)),
+            }
+
+            Ok(Some(format!(
+                "Spawned `{}` at ({}, {}, {})",
+                prefab, pos[0], pos[1], pos[2]
+            )))
+        }),
+    );
+}
+
+/// Returns the position above the player where entities are spawned by default
+fn spawn_position(world: &specs::World) -> Result<[::Float; 3], String> {
+    let player = world.read_storage::<Player>();
+    let position = world.read_storage::<Position>();
+
+    (&position, &player)
+        .join()
+        .next()
+        .map(|(p, _)| {
+            [
+                p.0.x + SPAWN_OFFSET[0],
+                p.0.y + SPAWN_OFFSET[1],
+                p.0.z + SPAWN_OFFSET[2],
+            ]
+        }).ok_or_else(|| "No player position found".to_string())
+}
+
 pub fn add_test_entities<R, F>(world: &mut specs::World, factory: &mut F)
 where
     R: gfx::Resources,
@@ -91,12 +200,7 @@ where
                     3.0,
                 )
             }) as SpawnParticleFn,
-            load_texture(
-                factory,
-                &world.read_resource::<Arc<Assets>>(),
-                "test_particle.png",
-                image_utils::PNG,
-            ),
+            load_particle_texture(factory, &world.read_resource::<Arc<Assets>>()),
         ).unwrap();
 
         world
@@ -200,18 +304,16 @@ where
     let pos = pos.into();
     let space = pos.map(|p| Position(Point3::new(p[0], p[1], p[2])));
     let scale = Scale::new(scale);
-    let objects = obj_loading::load_obj(
-        &world.read_resource::<Arc<Assets>>(),
-        factory,
-        name,
-        material,
-        &world.read_resource::<slog::Logger>(),
-    ).unwrap();
+    let assets = world.read_resource::<Arc<Assets>>().clone();
+    let model = obj_loading::load_obj(&assets, factory, name, material).unwrap();
     let shader_param = ShaderParam::default();
 
     let mut body_handles = Vec::new();
 
-    for (drawable, mesh) in objects {
+    for (index, (drawable, shape)) in model.objects().iter().enumerate() {
+        // Keep the model cached while this entity exists
+        let mut handles = vec![model.clone() as AnyHandle];
+
         let physics = properties.clone().map(|props| {
             let mut phys_world = world.write_resource::<World<::Float>>();
 
@@ -222,37 +324,25 @@ where
             };
 
             let dir = convert::to_na_quaternion(dir.0);
+            let isometry = Isometry::from_parts(Translation3::from(pos_vec), dir);
 
-            let scaled_mesh = mesh
-                .scale(scale.get().into())
+            let shape = load_collision_shape(&assets, name, index, shape, scale.get())
                 .expect(&format!("Failed to scale mesh for entity: `{}`", name));
 
-            let shape_handle = ShapeHandle::new(scaled_mesh);
-            let make_collider_desc = || ColliderDesc::new(shape_handle.clone())
-                .margin(COLLIDER_MARGIN)
-                .material(material::MaterialHandle::new(props));
-
-            let collider_desc = make_collider_desc();
-
-            let isometry = Isometry::from_parts(Translation3::from(pos_vec), dir);
-            let rb = RigidBodyDesc::new()
-                .collider(&collider_desc)
-                .position(isometry)
-                .local_inertia(Inertia::zero())
-                .set_status(BodyStatus::Static)
-                .build(&mut phys_world);
-
-            let handle = rb.handle();
+            let (handle, collider_desc, physics) =
+                create_static_body(&mut phys_world, (*shape).clone(), isometry, props);
 
             body_handles.push((handle, collider_desc));
+            handles.push(shape);
 
-            Physics::new(handle, Vec::new(), Some(make_collider_desc()), Vec::new())
+            physics
         });
 
         let mut entity = world
             .create_entity()
             .with(scale)
-            .with(drawable)
+            .with(drawable.clone())
+            .with(ModelSource::new(name, index, material, properties.clone(), handles))
             .with(shader_param)
             .with(dir);
 
@@ -273,6 +363,58 @@ where
     body_handles
 }
 
+/// Returns the collision shape of an object in a model, scaled by the provided amount
+///
+/// The scaled shape is cached, so entities with the same model and scale share it. Returns `None` if
+/// the shape could not be scaled.
+pub fn load_collision_shape(
+    assets: &Assets,
+    name: &str,
+    object_index: usize,
+    shape: &ShapeHandle<::Float>,
+    scale: f32,
+) -> Option<Handle<ShapeHandle<::Float>>> {
+    let key = CacheKey::new(
+        assets.get_model_path(name.to_owned() + ".obj"),
+        format!("object={},scale={}", object_index, scale),
+    );
+
+    assets
+        .cache()
+        .get_or_load(key, || scale_shape(shape, scale.into()).ok_or(()))
+        .ok()
+}
+
+/// Creates a static rigid body with a collider of the provided shape
+///
+/// Returns the handle of the body, a descriptor of its collider, and a `Physics` component for it.
+pub fn create_static_body(
+    phys_world: &mut World<::Float>,
+    shape: ShapeHandle<::Float>,
+    isometry: Isometry<::Float>,
+    properties: material::BasicMaterial<::Float>,
+) -> (BodyHandle, ColliderDesc<::Float>, Physics) {
+    let make_collider_desc = || {
+        ColliderDesc::new(shape.clone())
+            .margin(COLLIDER_MARGIN)
+            .material(material::MaterialHandle::new(properties.clone()))
+    };
+
+    let collider_desc = make_collider_desc();
+
+    let rb = RigidBodyDesc::new()
+        .collider(&collider_desc)
+        .position(isometry)
+        .local_inertia(Inertia::zero())
+        .set_status(BodyStatus::Static)
+        .build(phys_world);
+
+    let handle = rb.handle();
+    let physics = Physics::new(handle, Vec::new(), Some(make_collider_desc()), Vec::new());
+
+    (handle, collider_desc, physics)
+}
+
 fn create_dir_light<'a>(
     world: &'a mut specs::World,
     direction: [::Float; 3],
@@ -349,13 +491,16 @@ fn create_spot_light<'a, R, F>(
     );
 }
 
-fn load_texture<R: gfx::Resources, F: gfx::Factory<R>>(
+/// Loads the texture used by the test particle source
+fn load_particle_texture<R: gfx::Resources, F: gfx::Factory<R>>(
     factory: &mut F,
     assets: &Assets,
-    path: &str,
-    format: image_utils::ImageFormat,
 ) -> handle::ShaderResourceView<R, [f32; 4]> {
-    let texture_path = assets.get_model_path(path);
-    let data = assets::read_bytes(texture_path).unwrap();
-    image_utils::load_texture::<_, _, format::Srgba8>(factory, &data, format).unwrap()
+    let texture = obj_loading::load_texture::<_, format::Srgba8, _, _>(
+        assets,
+        factory,
+        assets.get_model_path(PARTICLE_TEXTURE),
+    ).unwrap();
+
+    (*texture).clone()
 }
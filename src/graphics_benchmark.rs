@@ -0,0 +1,102 @@
+//! Detection of the graphics preset to use on the first launch
+//!
+//! The test scene is rendered with each preset from the highest quality to the lowest, and the
+//! first preset that renders fast enough is used.
+
+use common::config::{self, GraphicsPreset};
+use common::specs;
+use slog;
+use window::window_event;
+
+use std::time::{Duration, Instant};
+
+/// The number of frames to render after changing the preset before measuring, so the time spent
+/// recompiling shaders and recreating render targets isn't measured
+const WARMUP_FRAMES: u32 = 10;
+/// The number of frames to measure with each preset
+const MEASURED_FRAMES: u32 = 60;
+/// The highest average frame time that is accepted for a preset (a bit more than 60 FPS, so
+/// v-sync doesn't make presets fail)
+const MAX_FRAME_TIME_MS: u64 = 20;
+
+/// Renders frames with each graphics preset until one is fast enough
+pub struct GraphicsBenchmark {
+    /// The index of the preset being measured in `GraphicsPreset::ALL`
+    index: usize,
+    /// The number of frames rendered with the current preset
+    frames: u32,
+    /// The time measuring started, once the warmup frames have been rendered
+    started: Option<Instant>,
+}
+
+impl GraphicsBenchmark {
+    /// Starts the benchmark with the highest quality preset
+    pub fn new(world: &mut specs::World) -> Self {
+        let index = GraphicsPreset::ALL.len() - 1;
+        apply_preset(world, GraphicsPreset::ALL[index]);
+
+        Self {
+            index,
+            frames: 0,
+            started: None,
+        }
+    }
+
+    /// Counts a rendered frame, and switches to the next preset once the current one has been
+    /// measured
+    ///
+    /// Returns the detected preset once the benchmark has finished, which has already been applied
+    /// to the `Config` resource.
+    pub fn update(&mut self, world: &mut specs::World) -> Option<GraphicsPreset> {
+        self.frames += 1;
+
+        if self.frames == WARMUP_FRAMES {
+            self.started = Some(Instant::now());
+        }
+
+        let started = match self.started {
+            Some(s) if self.frames >= WARMUP_FRAMES + MEASURED_FRAMES => s,
+            _ => return None,
+        };
+
+        let preset = GraphicsPreset::ALL[self.index];
+        let frame_time = started.elapsed() / MEASURED_FRAMES;
+        let fast_enough = frame_time <= Duration::from_millis(MAX_FRAME_TIME_MS);
+
+        {
+            let log = world.read_resource::<slog::Logger>();
+            info!(log, "Measured graphics preset"; o!(
+                "preset" => preset.name(),
+                "frame_time_us" => duration_micros(frame_time)
+            ));
+        }
+
+        if fast_enough || self.index == 0 {
+            return Some(preset);
+        }
+
+        self.index -= 1;
+        self.frames = 0;
+        self.started = None;
+        apply_preset(world, GraphicsPreset::ALL[self.index]);
+
+        None
+    }
+}
+
+/// Replaces the graphics settings with those of the provided preset
+fn apply_preset(world: &mut specs::World, preset: GraphicsPreset) {
+    if let Some(settings) = preset.settings() {
+        world.write_resource::<config::Config>().graphics = settings;
+        world
+            .write_resource::<window_event::EventChannel>()
+            .single_write(window_event::Event::ConfigChanged(
+                window_event::ChangedConfig::Graphics,
+            ));
+    }
+}
+
+/// Returns the provided duration in microseconds
+fn duration_micros(duration: Duration) -> u64 {
+    duration.as_secs() * 1_000_000 + u64::from(duration.subsec_micros())
+}
@@ -0,0 +1,237 @@
+//! Hot reloading of models and textures when they are changed on disk
+
+use assets::watcher::AssetWatcher;
+use assets::{AnyHandle, Assets};
+use common::graphics::{Drawable, Material};
+use common::gfx;
+use common::nphysics3d::material;
+use common::nphysics3d::object::{BodyStatus, RigidBody};
+use common::nphysics3d::world::World;
+use common::physics::Physics;
+use common::specs::{self, Join};
+use common::Scale;
+use control::FloorHandle;
+use graphics::obj_loading;
+use slog;
+
+use std::ffi::OsStr;
+use std::sync::Arc;
+
+use dev;
+
+/// The file extensions of files that models are loaded from
+const MODEL_FILE_EXTENSIONS: &[&str] = &["obj", "mtl", "png"];
+
+/// The model that an entity's `Drawable` was loaded from
+///
+/// Entities with this component have their `Drawable` (and their physics body, if it is static)
+/// rebuilt when the model changes.
+#[derive(Clone, Debug)]
+pub struct ModelSource {
+    name: String,
+    object_index: usize,
+    material: Material,
+    physics_material: Option<material::BasicMaterial<::Float>>,
+    // NOTE: These are stored so the cached assets used by this entity are not evicted while it
+    //       exists
+    handles: Vec<AnyHandle>,
+}
+
+impl ModelSource {
+    /// Returns a new `ModelSource`
+    ///
+    /// `object_index` is the index of the object in the model's list of objects, and `handles` are
+    /// handles to the cached assets that the entity uses.
+    pub fn new<S: Into<String>>(
+        name: S,
+        object_index: usize,
+        material: Material,
+        physics_material: Option<material::BasicMaterial<::Float>>,
+        handles: Vec<AnyHandle>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            object_index,
+            material,
+            physics_material,
+            handles,
+        }
+    }
+
+    /// Returns whether this entity uses the model with the provided name and material
+    fn uses(&self, name: &str, material: Material) -> bool {
+        self.name == name && self.material.shininess == material.shininess
+    }
+}
+
+impl specs::Component for ModelSource {
+    type Storage = specs::DenseVecStorage<Self>;
+}
+
+/// Registers the components used for hot reloading
+pub fn initialize(world: &mut specs::World) {
+    world.register::<ModelSource>();
+}
+
+/// Starts watching the models directory of each asset layer for changes
+pub fn watch_models(world: &specs::World) -> Option<AssetWatcher> {
+    let assets = world.read_resource::<Arc<Assets>>();
+    let log = world.read_resource::<slog::Logger>();
+
+    // Archives can't be edited in place, so only layers that are directories are watched
+    let dirs = assets.get_layer_dirs(assets.get_models_dir());
+    if dirs.is_empty() {
+        return None;
+    }
+
+    AssetWatcher::new(dirs)
+        .map_err(|e| error!(log, "Error watching models directory: {}", e;))
+        .ok()
+}
+
+/// Reloads all models that depend on files that have changed, rebuilding the `Drawable`s of all
+/// entities that use them in place
+pub fn reload_changed_models<R, F>(
+    watcher: &AssetWatcher,
+    world: &mut specs::World,
+    factory: &mut F,
+) where
+    R: gfx::Resources,
+    F: gfx::Factory<R>,
+{
+    let assets = world.read_resource::<Arc<Assets>>().clone();
+
+    // The watcher reports paths inside the layer that changed, which are converted to asset paths
+    let changed = watcher
+        .changed_paths()
+        .into_iter()
+        .filter(|p| {
+            p.extension()
+                .and_then(OsStr::to_str)
+                .map(|ext| MODEL_FILE_EXTENSIONS.contains(&ext))
+                .unwrap_or(false)
+        }).filter_map(|p| assets.asset_path_of(p))
+        .collect::<Vec<_>>();
+
+    if changed.is_empty() {
+        return;
+    }
+
+    let log = world.read_resource::<slog::Logger>().clone();
+
+    // Find the models that depend on the changed files
+    let models = {
+        let sources = world.read_storage::<ModelSource>();
+        let mut models: Vec<(String, Material)> = Vec::new();
+
+        for source in sources.join() {
+            if models.iter().any(|(name, material)| source.uses(name, *material)) {
+                continue;
+            }
+
+            match obj_loading::model_dependencies(&assets, &source.name) {
+                Ok(deps) => {
+                    if deps.iter().any(|d| changed.contains(d)) {
+                        models.push((source.name.clone(), source.material));
+                    }
+                }
+                Err(e) => error!(log, "Error reading model `{}`: {}", source.name, e;),
+            }
+        }
+
+        models
+    };
+
+    // Remove the changed files from the asset cache, along with the models and collision shapes
+    // that depend on them, so they are loaded again
+    for path in &changed {
+        assets.cache().invalidate(path);
+    }
+
+    for (name, _) in &models {
+        assets
+            .cache()
+            .invalidate(assets.get_model_path(name.to_owned() + ".obj"));
+    }
+
+    for (name, material) in models {
+        let model = match obj_loading::load_obj(&assets, factory, &name, material) {
+            Ok(o) => o,
+            Err(e) => {
+                error!(log, "Error reloading model `{}`: {}", name, e;);
+                continue;
+            }
+        };
+
+        info!(log, "Reloading model `{}`", name;);
+
+        let entities = world.entities();
+        let mut sources = world.write_storage::<ModelSource>();
+        let scale = world.read_storage::<Scale>();
+        let mut drawable = world.write_storage::<Drawable<R>>();
+        let mut physics = world.write_storage::<Physics>();
+        let mut phys_world = world.write_resource::<World<::Float>>();
+        let mut floor_handle = world.write_resource::<FloorHandle>();
+
+        for (e, source, d) in (&*entities, &mut sources, &mut drawable).join() {
+            if !source.uses(&name, material) {
+                continue;
+            }
+
+            let (new_drawable, shape) = match model.objects().get(source.object_index) {
+                Some(o) => o.clone(),
+                None => {
+                    error!(log, "Model `{}` no longer has object {}", name, source.object_index;);
+                    continue;
+                }
+            };
+
+            // Keep the shader parameters, which are calculated each frame
+            let param = *d.param();
+            *d = new_drawable;
+            d.set_shader_param(param);
+            source.handles = vec![model.clone() as AnyHandle];
+
+            // Rebuild the physics body if it is static
+            let (p, props) = match (physics.get_mut(e), source.physics_material.clone()) {
+                (Some(p), Some(props)) => (p, props),
+                _ => continue,
+            };
+
+            let old_handle = p.get_root_handle();
+            let isometry = match phys_world
+                .body(old_handle)
+                .and_then(|b| b.downcast_ref::<RigidBody<::Float>>())
+            {
+                Some(rb) if rb.status() == BodyStatus::Static => *rb.position(),
+                _ => continue,
+            };
+
+            let entity_scale = scale.get(e).map(|s| s.get()).unwrap_or(1.0);
+            let shape = match dev::load_collision_shape(
+                &assets,
+                &name,
+                source.object_index,
+                &shape,
+                entity_scale,
+            ) {
+                Some(s) => s,
+                None => {
+                    error!(log, "Failed to scale mesh for model `{}`", name;);
+                    continue;
+                }
+            };
+
+            let (handle, collider_desc, new_physics) =
+                dev::create_static_body(&mut phys_world, (*shape).clone(), isometry, props);
+            phys_world.remove_bodies(&[old_handle]);
+
+            if floor_handle.get_handle() == Some(old_handle) {
+                floor_handle.set_floor(handle, collider_desc);
+            }
+
+            *p = new_physics;
+            source.handles.push(shape);
+        }
+    }
+}
@@ -1,5 +1,7 @@
 // TODO: Crate docs
 
+#[macro_use]
+extern crate quick_error;
 #[macro_use]
 extern crate shred_derive;
 #[macro_use]
@@ -10,7 +12,9 @@ extern crate control;
 extern crate graphics;
 extern crate image_utils;
 extern crate math;
+extern crate notify;
 extern crate physics;
+extern crate ron;
 extern crate slog_async;
 extern crate slog_term;
 extern crate ui;
@@ -19,13 +23,21 @@ extern crate window;
 // TODO: Remove when no longer needed
 mod dev;
 
+pub mod check_assets;
+
+mod cheats;
+mod config_watcher;
+mod graphics_benchmark;
+mod hot_reload;
 mod player;
 mod player_control;
 
 use common::shred;
-use common::{config, glutin, specs, Float};
+use common::{config, console, glutin, specs, Float};
+use graphics::loading::{AssetLoader, LoadRequest};
 use window::window_event;
 
+use std::path::PathBuf;
 use std::sync::{mpsc, Arc};
 
 // TODO: Docs
@@ -34,6 +46,9 @@ pub fn run(
     config: config::Config,
     cli_config: config::CommandLineConfig,
     logger: slog::Logger,
+    console_log: console::ConsoleLog,
+    config_path: Option<PathBuf>,
+    detect_graphics: bool,
 ) -> config::Config {
     // Create world
     let mut world = specs::World::new();
@@ -45,15 +60,27 @@ pub fn run(
     let dispatcher_graphics = specs::DispatcherBuilder::new();
 
     // Add assets manager resource
-    let assets = assets::Assets::new(&logger, cli_config.assets_path()).unwrap_or_else(|e| {
-        error!(logger, "Error building asset manager: {}", e;);
-        panic!(common::CRASH_MSG);
-    });
+    let assets = assets::Assets::new(&logger, cli_config.asset_roots(&config)).unwrap_or_else(
+        |e| {
+            error!(logger, "Error building asset manager: {}", e;);
+            panic!(common::CRASH_MSG);
+        },
+    );
     world.add_resource(Arc::new(assets));
+    // Watch the configuration file for changes
+    let config_watcher = config_path.and_then(|path| {
+        config_watcher::ConfigWatcher::new(path)
+            .map_err(|e| error!(logger, "Error watching configuration file: {}", e;))
+            .ok()
+    });
+    // Start reading gamepad input
+    let mut gamepads = window::gamepad::Gamepads::new(&logger);
     // Add logger resource
     world.add_resource(logger);
     // Add config resource
     world.add_resource(config);
+    // Add cheats resource
+    world.add_resource(common::Cheats::new(cli_config.enable_cheats()));
 
     // Call initialization functions (initializes their components and systems)
     let dispatcher_graphics = common::initialize(&mut world, dispatcher_graphics);
@@ -64,12 +91,9 @@ pub fn run(
     let dispatcher = control::initialize(&mut world, dispatcher);
     let dispatcher = physics::initialize(&mut world, dispatcher);
     ui::add_resources(&mut world);
-    let (dispatcher, dispatcher_graphics, mut events) = graphics::initialize(
-        &mut world,
-        dispatcher,
-        dispatcher_graphics,
-        Box::new(dev::add_test_entities),
-    );
+    hot_reload::initialize(&mut world);
+    let (dispatcher, dispatcher_graphics, mut events, mut factory) =
+        graphics::initialize(&mut world, dispatcher, dispatcher_graphics);
     let physical_window_size = {
         let window = world.read_resource::<window::Window>();
         let window = window.get_window();
@@ -79,6 +103,25 @@ pub fn run(
             .to_physical(window.get_hidpi_factor())
             .into()
     };
+
+    // Register console commands
+    let mut commands = console::CommandRegistry::new();
+    common::register_commands(&mut commands);
+    assets::register_commands(&mut commands);
+    window::register_commands(&mut commands);
+    control::register_commands(&mut commands);
+    physics::register_commands(&mut commands);
+    graphics::register_commands(&mut commands);
+    cheats::register_commands(&mut commands);
+    dev::register_commands(&mut commands, factory.clone());
+
+    // The console resource must exist before calling ui::initialize
+    {
+        let mut console = console::Console::new(console_log);
+        console.set_command_names(commands.names());
+        world.add_resource(console);
+    }
+
     let (ui_event_sender, ui_event_receiver) = mpsc::channel();
     let dispatcher_graphics = ui::initialize(
         &mut world,
@@ -87,6 +130,26 @@ pub fn run(
         ui_event_receiver,
     );
 
+    // Start loading the skybox and the assets used by the test entities in the background
+    let mut loader = {
+        let assets = world.read_resource::<Arc<assets::Assets>>().clone();
+        let log = world.read_resource::<slog::Logger>().clone();
+        let mut loader = AssetLoader::new(assets.clone(), log);
+        loader.request(LoadRequest::Skybox);
+
+        for request in dev::test_assets(&assets) {
+            loader.request(request);
+        }
+
+        Some(loader)
+    };
+
+    // Watch the models directory for changes
+    let model_watcher = hot_reload::watch_models(&world);
+
+    // The graphics benchmark is run once the test entities have been created
+    let mut benchmark = None;
+
     // Build the dispatchers
     let mut dispatcher = dispatcher.build();
     let mut dispatcher_graphics = dispatcher_graphics.build();
@@ -98,13 +161,48 @@ pub fn run(
             break;
         }
 
+        // Upload assets that have finished loading, and create the test entities once all of them
+        // have been loaded
+        let finished_loading = match loader {
+            Some(ref mut loader) => {
+                let progress = loader.upload_finished(&mut factory);
+                *world.write_resource::<common::LoadingProgress>() = progress;
+
+                progress.is_done()
+            }
+            None => false,
+        };
+
+        if finished_loading {
+            loader = None;
+            dev::add_test_entities(&mut world, &mut factory);
+
+            let ui_state = if detect_graphics {
+                benchmark = Some(graphics_benchmark::GraphicsBenchmark::new(&mut world));
+                common::UiState::Benchmark
+            } else {
+                common::UiState::MainMenu
+            };
+            *world.write_resource::<common::UiState>() = ui_state;
+        }
+
+        if let Some(ref watcher) = config_watcher {
+            config_watcher::reload_if_changed(watcher, &mut world);
+        }
+
+        if let Some(ref watcher) = model_watcher {
+            hot_reload::reload_changed_models(watcher, &mut world, &mut factory);
+        }
+
         {
             let config = world.read_resource::<config::Config>();
             let window = world.read_resource::<window::Window>();
             let window = window.get_window();
             let mut channel = world.write_resource::<window_event::EventChannel>();
-
-            let mut latest_mouse_move = None;
+            let mut toggled = world.write_resource::<window::input::ToggledActions>();
+            let mut held = world.write_resource::<window::input::HeldActions>();
+            let mut mouse_motion = world.write_resource::<window::input::MouseMotion>();
+            let mut focus = world.write_resource::<window::input::WindowFocus>();
 
             events.poll_events(|e| {
                 ui_event_sender
@@ -116,7 +214,30 @@ pub fn run(
                         let mut ui_state = world.write_resource::<common::UiState>();
                         let log = world.read_resource::<slog::Logger>();
 
+                        if let glutin::WindowEvent::Focused(focused) = event {
+                            window_event::process_focus_change(
+                                &config,
+                                &mut channel,
+                                &mut toggled,
+                                &mut held,
+                                &mut mouse_motion,
+                                &mut focus,
+                                &window,
+                                &mut ui_state,
+                                focused,
+                            );
+
+                            // The player's analog movement is reset along with the rest of the
+                            // input when focus is lost
+                            if let Some(ref mut gamepads) = gamepads {
+                                if !focused {
+                                    gamepads.reset();
+                                }
+                            }
+                        }
+
                         window_event::process_window_event_graphics(
+                            &config,
                             &mut channel,
                             &window,
                             &event,
@@ -124,27 +245,57 @@ pub fn run(
                             &log,
                         );
 
-                        // If the game isn't running, only call process_window_event_graphics
+                        // If the game isn't running, only call process_window_event_graphics. Binds
+                        // released meanwhile aren't seen, so forget which ones are held.
                         if !ui_state.is_in_game() {
+                            held.clear();
                             return;
                         }
 
-                        // Collect the latest mouse event
-                        if let glutin::WindowEvent::CursorMoved { .. } = event {
-                            latest_mouse_move = Some(event);
-                            return;
+                        window_event::process_window_event(
+                            &config,
+                            &mut channel,
+                            &mut toggled,
+                            &mut held,
+                            &event,
+                        );
+                    }
+                    glutin::Event::DeviceEvent {
+                        event: glutin::DeviceEvent::MouseMotion { delta },
+                        ..
+                    } => {
+                        // Raw mouse motion is used to look around, since it isn't limited by the
+                        // edges of the screen or affected by the DPI. It is also received while
+                        // another window is focused, so it is ignored then.
+                        if focus.0 {
+                            mouse_motion.add(delta);
                         }
-
-                        window_event::process_window_event(&config, &mut channel, &window, &event);
                     }
                     _ => {}
                 }
             });
 
-            // Only process the latest mouse movement event
-            // NOTE: This won't be run if !ui_state.is_in_game() because of the above code
-            if let Some(event) = latest_mouse_move {
-                window_event::process_window_event(&config, &mut channel, &window, &event);
+            // Mouse motion is applied once per frame, so it can be smoothed
+            if focus.0 && world.read_resource::<common::UiState>().is_in_game() {
+                window_event::process_mouse_motion(&config, &mut channel, &mut mouse_motion);
+            } else {
+                mouse_motion.reset();
+            }
+
+            if let Some(ref mut gamepads) = gamepads {
+                let mut ui_state = world.write_resource::<common::UiState>();
+                let log = world.read_resource::<slog::Logger>();
+
+                gamepads.poll(
+                    &config,
+                    &mut channel,
+                    &mut toggled,
+                    &mut held,
+                    &window,
+                    &mut ui_state,
+                    focus.0,
+                    &log,
+                );
             }
         }
 
@@ -156,8 +307,29 @@ pub fn run(
         // Run graphics systems regardless of the UI state
         dispatcher_graphics.dispatch(&mut world.res);
 
+        if let Some(preset) = benchmark.as_mut().and_then(|b| b.update(&mut world)) {
+            benchmark = None;
+
+            let log = world.read_resource::<slog::Logger>();
+            info!(log, "Detected graphics preset"; o!("preset" => preset.name()));
+            *world.write_resource::<common::UiState>() = common::UiState::MainMenu;
+        }
+
         // NOTE: Running this after dispatch may be a problem (but so is running it before dispatch)
         world.maintain();
+
+        // Run commands submitted to the console
+        let queued = world.write_resource::<console::Console>().take_queued();
+        for line in queued {
+            let result = commands.execute(&mut world, &line);
+            let console = world.read_resource::<console::Console>();
+
+            match result {
+                Ok(Some(output)) => console.print(output),
+                Ok(None) => {}
+                Err(e) => console.print(format!("Error: {}", e)),
+            }
+        }
     }
 
     // Return the config so it can be written to the config file
@@ -14,9 +14,10 @@ use slog::Drain;
 
 use std::fs;
 use std::io::{self, Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process;
 
-use common::config;
+use common::{config, config_file, console};
 
 const CONFIG_FILE_NAME: &str = "settings.ron";
 
@@ -28,8 +29,8 @@ quick_error! {
             display("Error deserializing `Config`: {}", e)
             from()
         }
-        Serialize(e: ron::de::Error) {
-            display("Error serializing `Config`: {}", e)
+        ConfigFile(e: config_file::ConfigFileError) {
+            display("{}", e)
             from()
         }
         Io(e: (io::Error, String)) {
@@ -44,9 +45,13 @@ quick_error! {
 }
 
 /// Initializes the logger used by horde_survival
-fn init_logger() -> slog::Logger {
+///
+/// Log records are also written to the provided `ConsoleLog`, so they are shown in the developer
+/// console.
+fn init_logger(console_log: console::ConsoleLog) -> slog::Logger {
     let decorator = slog_term::TermDecorator::new().build();
     let drain = slog_term::CompactFormat::new(decorator).build().fuse();
+    let drain = slog::Duplicate::new(drain, console::ConsoleDrain::new(console_log)).fuse();
     let drain = slog_async::Async::new(drain).build().fuse();
 
     slog::Logger::root(drain, o!())
@@ -93,9 +98,12 @@ fn get_default_assets_path() -> Result<PathBuf, Error> {
     get_project_dir_path(|dirs| dirs.data_dir().join("assets"))
 }
 
-/// Loads a `Config` from the configuration file
-fn load_config() -> Result<config::Config, Error> {
-    let config_file_path = get_config_dir_path()?.join(CONFIG_FILE_NAME);
+/// Loads a `Config` from the configuration file at the provided path
+///
+/// Problems with individual fields are logged, and those fields are reset to their defaults. If the
+/// file was written by an older version of the game, it is backed up and replaced with the migrated
+/// configuration.
+fn load_config(log: &slog::Logger, config_file_path: &Path) -> Result<config::Config, Error> {
     let config_file_path_str = config_file_path
         .to_str()
         .expect("Config file path contained invalid unicode");
@@ -107,51 +115,202 @@ fn load_config() -> Result<config::Config, Error> {
     file.read_to_string(&mut data)
         .map_err(|e| Error::Io((e, config_file_path_str.to_string())))?;
 
-    let config = ron::de::from_str(&data)?;
+    let loaded = config_file::load(&data)?;
 
-    Ok(config)
+    for warning in &loaded.warnings {
+        warn!(log, "Problem in configuration file: {}", warning;);
+    }
+
+    if loaded.migrated() {
+        let backup_path =
+            backup_config_file(config_file_path, &format!("v{}", loaded.version))?;
+
+        info!(log, "Migrating configuration file"; o!(
+            "from_version" => loaded.version,
+            "to_version" => config_file::CONFIG_VERSION,
+            "backup" => backup_path.display().to_string()
+        ));
+
+        save_config(&loaded.config, config_file_path)?;
+    } else if !loaded.warnings.is_empty() {
+        // The fields with problems would be lost when the configuration is saved
+        let backup_path = backup_config_file(config_file_path, "invalid")?;
+
+        info!(log, "Backed up configuration file"; o!(
+            "backup" => backup_path.display().to_string()
+        ));
+    }
+
+    Ok(loaded.config)
+}
+
+/// Copies the configuration file to a backup file next to it (such as `settings.ron.v0.bak` for
+/// the `v0` tag), and returns the path of the backup
+fn backup_config_file(config_file_path: &Path, tag: &str) -> Result<PathBuf, Error> {
+    let backup_path = config_file_path.with_extension(format!("ron.{}.bak", tag));
+
+    fs::copy(config_file_path, &backup_path)
+        .map_err(|e| Error::Io((e, backup_path.to_string_lossy().into_owned())))?;
+
+    Ok(backup_path)
 }
 
-/// Writes the provided `Config` to the configuration file
-fn save_config(config: config::Config) -> Result<(), Error> {
-    let serialized = ron::ser::to_string_pretty(&config, ron::ser::PrettyConfig::default())?;
+/// Writes the provided `Config` to the configuration file at the provided path
+fn save_config(config: &config::Config, config_file_path: &Path) -> Result<(), Error> {
+    let serialized = ron::ser::to_string_pretty(config, ron::ser::PrettyConfig::default())?;
 
-    let config_file_path = get_config_dir_path()?.join(CONFIG_FILE_NAME);
     let config_file_path_str = config_file_path
         .to_str()
         .expect("Config file path contained invalid unicode");
 
-    let mut file = fs::File::create(&config_file_path)
+    let mut file = fs::File::create(config_file_path)
         .map_err(|e| Error::Io((e, config_file_path_str.to_string())))?;
 
     file.write_all(serialized.as_bytes())
         .map_err(|e| Error::Io((e, config_file_path_str.to_string())))
 }
 
-/// Attempts to load a `Config` from the configuration file, returning `Default::default()` if an
-/// error occurs
-fn load_config_or_default(log: &slog::Logger) -> config::Config {
-    match load_config() {
+/// Returns the path of the configuration file, which is the one given by the `config` option if it
+/// was specified
+fn get_config_file_path(cli_config: &config::CommandLineConfig) -> Result<PathBuf, Error> {
+    match cli_config.config_path() {
+        Some(p) => Ok(p.to_owned()),
+        None => Ok(get_config_dir_path()?.join(CONFIG_FILE_NAME)),
+    }
+}
+
+/// Attempts to load a `Config` from the configuration file at the provided path, returning
+/// `Default::default()` if an error occurs
+///
+/// If the file exists but can't be loaded, it is backed up, because it is overwritten with the
+/// default configuration on exit.
+fn load_config_or_default(log: &slog::Logger, config_file_path: &Path) -> config::Config {
+    match load_config(log, config_file_path) {
         Ok(c) => c,
         Err(e) => {
             error!(log, "Error loading configuration file: {}", e;);
+            backup_existing_config_file(log, config_file_path, "invalid");
+
             get_default_config(log)
         }
     }
 }
 
+/// Backs up the configuration file with `backup_config_file` if it exists, and logs the result
+fn backup_existing_config_file(log: &slog::Logger, config_file_path: &Path, tag: &str) {
+    if !config_file_path.exists() {
+        return;
+    }
+
+    match backup_config_file(config_file_path, tag) {
+        Ok(p) => info!(log, "Backed up configuration file"; o!(
+            "backup" => p.display().to_string()
+        )),
+        Err(e) => error!(log, "Error backing up configuration file: {}", e;),
+    }
+}
+
+/// Checks all asset files, prints the problems that were found, and returns the exit code
+///
+/// The exit code is nonzero if there were any problems.
+fn check_assets(
+    log: &slog::Logger,
+    config: &config::Config,
+    cli_config: &config::CommandLineConfig,
+) -> i32 {
+    let roots = cli_config.asset_roots(config);
+    let problems = horde_survival::check_assets::check_asset_roots(log, roots);
+
+    for problem in &problems {
+        println!("{}", problem);
+    }
+
+    if problems.is_empty() {
+        println!("No problems found");
+        0
+    } else {
+        println!("Found {} problems", problems.len());
+        1
+    }
+}
+
 fn main() {
-    let logger = init_logger();
-    let config = load_config_or_default(&logger);
+    let console_log = console::ConsoleLog::new();
+    let logger = init_logger(console_log.clone());
     let cli_config =
         config::CommandLineConfig::new(get_default_assets_path().unwrap_or_else(|e| {
             error!(logger, "Error loading default assets path: {}", e;);
             panic!(common::CRASH_MSG);
         }));
 
-    let new_config = horde_survival::run(config, cli_config, logger.clone());
+    let config_path = get_config_file_path(&cli_config)
+        .map_err(|e| error!(logger, "Error getting configuration file path: {}", e;))
+        .ok();
+
+    // The graphics preset is detected if there is no configuration file yet, unless graphics
+    // settings were given on the command line
+    let detect_graphics = config_path.as_ref().map_or(false, |p| !p.exists())
+        && !cli_config
+            .overrides()
+            .iter()
+            .any(|o| o.path.starts_with("graphics."));
+
+    let mut config = if cli_config.reset_config() {
+        info!(logger, "Resetting configuration to the defaults";);
+
+        // The configuration file is overwritten with the defaults on exit
+        if let Some(ref p) = config_path {
+            backup_existing_config_file(&logger, p, "reset");
+        }
+
+        Default::default()
+    } else {
+        match config_path {
+            Some(ref p) => load_config_or_default(&logger, p),
+            None => get_default_config(&logger),
+        }
+    };
 
-    save_config(new_config).unwrap_or_else(|e| {
-        error!(logger, "Error writing to configuration file: {}", e;);
+    // NOTE: The logger is asynchronous, so errors that exit the process are printed directly
+    let overridden = cli_config.apply_overrides(&mut config).unwrap_or_else(|e| {
+        eprintln!("Error in `--set` option: {}", e);
+        process::exit(1);
     });
+
+    for o in cli_config.overrides() {
+        info!(logger, "Overriding configuration field"; o!(
+            "field" => o.path.clone(),
+            "value" => o.value.clone()
+        ));
+    }
+
+    if let Some(config::Command::CheckAssets) = cli_config.command() {
+        let code = check_assets(&logger, &config, &cli_config);
+
+        // Dropping the last handle to the asynchronous logger flushes the records that are still
+        // queued, which would otherwise be lost when the process exits
+        drop(logger);
+        process::exit(code);
+    }
+
+    let save_overrides = cli_config.save_overrides();
+
+    let mut new_config = horde_survival::run(
+        config,
+        cli_config,
+        logger.clone(),
+        console_log,
+        config_path.clone(),
+        detect_graphics,
+    );
+
+    if !save_overrides {
+        overridden.restore(&mut new_config);
+    }
+
+    if let Some(ref p) = config_path {
+        save_config(&new_config, p).unwrap_or_else(|e| {
+            error!(logger, "Error writing to configuration file: {}", e;);
+        });
+    }
 }
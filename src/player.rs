@@ -9,13 +9,25 @@ use common::specs::{self, Builder};
 use common::{cgmath, na, nphysics3d};
 use common::{Direction, Player, Position};
 
-use control::{Control, MovementForceGenerator, Spring};
+use control::{
+    AbilityConfig, Control, ControllerConfig, CrouchConfig, DashConfig, JumpTiming,
+    MovementForceGenerator, Spring, SprintConfig,
+};
 
 /// The radius of the collider of the player physics body
 pub const PLAYER_COLLIDER_RADIUS: ::Float = 0.5;
 
-/// The friction applied to the player physics body
-pub const PLAYER_FRICTION: ::Float = 0.85;
+/// How the player physics body moves on slopes and in the air
+pub const PLAYER_CONTROLLER: ControllerConfig = ControllerConfig {
+    max_slope: cgmath::Rad(::std::f64::consts::FRAC_PI_4 as ::Float),
+    slide_force: 5000.0,
+    air_acceleration: 0.3,
+    friction: 0.85,
+    air_drag: 0.99,
+    // The player's direction is set directly by the player control system, so this only applies
+    // if something else turns the player
+    max_turn_rate: cgmath::Rad(4.0 * ::std::f64::consts::PI as ::Float),
+};
 
 /// The collider margin for the player physics body
 pub const COLLIDER_MARGIN: ::Float = 0.01;
@@ -38,6 +50,28 @@ const PLAYER_ACCELERATION: ::Float = 5000.0;
 /// The jump strength of the player physics body
 const PLAYER_JUMP_STRENGTH: ::Float = 13.0;
 
+/// The sprinting parameters of the player
+const PLAYER_SPRINT: SprintConfig = SprintConfig {
+    speed_multiplier: 1.6,
+    max_stamina: 5.0,
+    stamina_cost: 1.0,
+    stamina_regen: 0.5,
+    min_stamina_to_start: 2.0,
+};
+
+/// The crouching parameters of the player
+const PLAYER_CROUCH: CrouchConfig = CrouchConfig {
+    spring_length_fraction: 0.5,
+    speed_multiplier: 0.5,
+};
+
+/// The dashing parameters of the player
+const PLAYER_DASH: DashConfig = DashConfig {
+    speed: 30.0,
+    duration: 0.15,
+    cooldown: 1.0,
+};
+
 pub fn add_player_entity(world: &mut specs::World) {
     let (physics, control) = {
         let mut phys_world = world.write_resource::<nphysics3d::world::World<::Float>>();
@@ -72,6 +106,7 @@ pub fn add_player_entity(world: &mut specs::World) {
                 PLAYER_ACCELERATION,
                 PLAYER_MAX_SPEED,
                 PLAYER_JUMP_STRENGTH,
+                JumpTiming::default(),
             );
 
             let spring = Spring::new(
@@ -80,7 +115,20 @@ pub fn add_player_entity(world: &mut specs::World) {
                 PLAYER_SPRING_FRICTION,
             );
 
-            Control::new(rb_part_handle, movement, spring, PLAYER_FRICTION, &mut phys_world)
+            let abilities = AbilityConfig {
+                sprint: PLAYER_SPRINT,
+                crouch: PLAYER_CROUCH,
+                dash: PLAYER_DASH,
+            };
+
+            Control::new(
+                rb_part_handle,
+                movement,
+                spring,
+                PLAYER_CONTROLLER,
+                abilities,
+                &mut phys_world,
+            )
         };
 
         (physics, control)
@@ -1,7 +1,7 @@
 //! A system that processes input events and controls the player entity
 
 use common;
-use common::cgmath::{self, Quaternion, Rad, Rotation3};
+use common::cgmath::{self, InnerSpace, Quaternion, Rad, Rotation3, Zero};
 use common::specs::{self, DispatcherBuilder, Join};
 use control;
 use window::input;
@@ -19,10 +19,24 @@ pub struct System {
     rotate_direction: Option<window_event::CameraRotation>,
     /// Whether the player entity should jump
     jump: bool,
+    /// Whether the player entity should release its jump
+    jump_released: bool,
+    /// Whether the player entity should dash
+    dash: bool,
+    /// Whether the sprint input is held
+    sprint: bool,
+    /// Whether crouching is enabled
+    crouch: bool,
+    /// Whether the input to fly up while noclipping is held
+    ascend: bool,
+    /// Whether the input to fly down while noclipping is held
+    descend: bool,
     /// Internally used for clamping the camera controls
     current_direction: Euler,
     /// Input state
     input_state: input::InputState,
+    /// The latest analog movement input, which is used while no movement keys are pressed
+    analog_movement: cgmath::Vector2<::Float>,
 }
 
 impl System {
@@ -31,14 +45,23 @@ impl System {
             reader_id,
             rotate_direction: None,
             jump: false,
+            jump_released: false,
+            dash: false,
+            sprint: false,
+            crouch: false,
+            ascend: false,
+            descend: false,
             current_direction: cgmath::Quaternion::from_angle_x(cgmath::Deg(0.0)).into(),
             input_state: Default::default(),
+            analog_movement: cgmath::Vector2::zero(),
         }
     }
 
     fn check_input(&mut self, event_channel: &window_event::EventChannel) {
         self.rotate_direction = None;
         self.jump = false;
+        self.jump_released = false;
+        self.dash = false;
 
         for e in event_channel.read(&mut self.reader_id) {
             match e {
@@ -51,14 +74,63 @@ impl System {
                         State::Disabled => self.input_state.remove(input),
                     }
                 }
+                Event::AnalogMovement(movement) => self.analog_movement = *movement,
+                Event::ResetInput => {
+                    self.input_state = Default::default();
+                    self.analog_movement = cgmath::Vector2::zero();
+                    self.sprint = false;
+                    self.crouch = false;
+                    self.ascend = false;
+                    self.descend = false;
+                }
                 Event::Jump => {
                     self.jump = true;
                 }
+                Event::JumpReleased => {
+                    self.jump_released = true;
+                }
+                Event::Sprint(state) => self.sprint = is_enabled(state),
+                Event::Crouch(state) => self.crouch = is_enabled(state),
+                Event::Ascend(state) => self.ascend = is_enabled(state),
+                Event::Descend(state) => self.descend = is_enabled(state),
+                Event::Dash => {
+                    self.dash = true;
+                }
                 _ => {}
             }
         }
     }
 
+    /// Returns the angle to offset the movement direction by (as a counter-clockwise rotation), and
+    /// the fraction of the maximum speed to move at
+    ///
+    /// Movement keys take priority over analog movement.
+    fn movement(&self) -> Option<(Rad<::Float>, ::Float)> {
+        if let Some(angle) = self.input_state.get_movement_angle() {
+            return Some((angle, 1.0));
+        }
+
+        let speed = self.analog_movement.magnitude();
+
+        if speed > 0.0 {
+            // Forward is along the y axis, and left is a positive rotation
+            let angle = Rad(-self.analog_movement.x.atan2(self.analog_movement.y));
+
+            Some((angle, speed.min(1.0)))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the vertical direction to fly in while noclipping, from -1 (down) to 1 (up)
+    fn vertical_movement(&self) -> ::Float {
+        match (self.ascend, self.descend) {
+            (true, false) => 1.0,
+            (false, true) => -1.0,
+            _ => 0.0,
+        }
+    }
+
     /// Applies the provided rotation to the current direction, and returns the new value
     fn update_direction(&mut self, rot: window_event::CameraRotation) -> Quaternion<::Float> {
         let current = &mut self.current_direction;
@@ -83,6 +155,7 @@ impl System {
 #[derive(SystemData)]
 pub struct Data<'a> {
     event_channel: specs::ReadExpect<'a, window_event::EventChannel>,
+    cheats: specs::ReadExpect<'a, common::Cheats>,
     player: specs::ReadStorage<'a, common::Player>,
     control: specs::WriteStorage<'a, control::Control>,
     // Direction is directly accessed because it is special for the player (it is not tied to
@@ -97,28 +170,66 @@ impl<'a> specs::System<'a> for System {
         // TODO: Maybe use delta time here for controls
         self.check_input(&data.event_channel);
 
+        let noclip = data.cheats.is_enabled(common::Cheat::Noclip);
+
         // Apply the input to the player entity
         for (d, c, _) in (&mut data.direction, &mut data.control, &data.player).join() {
+            c.set_noclip(noclip);
+
             if let Some(rot) = self.rotate_direction.clone() {
                 let new_direction = self.update_direction(rot);
                 // Rotate the player entity's direction
                 d.0 = new_direction;
             }
 
+            if self.jump_released {
+                c.release_jump();
+            }
+
             if self.jump {
                 c.jump();
             }
 
-            if let Some(angle) = self.input_state.get_movement_angle() {
+            c.set_sprint(self.sprint);
+            c.set_crouch(self.crouch);
+
+            let movement = self.movement();
+
+            if self.dash {
+                // Dash in the movement direction, or forward if the player isn't moving
+                let angle = movement.map_or(Rad(0.0), |(angle, _)| angle);
+                let dir = Quaternion::from_angle_z(angle + self.current_direction.y)
+                    * cgmath::Vector3::unit_y();
+
+                c.dash(dir.truncate());
+            }
+
+            if noclip {
+                // While noclipping, fly in the direction of the camera (the camera looks along
+                // its negative z axis), and straight up or down with the ascend and descend inputs
+                let dir = movement.map_or(cgmath::Vector3::zero(), |(angle, _)| {
+                    d.0 * Quaternion::from_angle_y(angle) * -cgmath::Vector3::unit_z()
+                }) + cgmath::Vector3::unit_z() * self.vertical_movement();
+
+                c.fly_in_direction(dir);
+            } else if let Some((angle, speed)) = movement {
                 let dir = Quaternion::from_angle_z(angle + self.current_direction.y)
                     * cgmath::Vector3::unit_y();
 
-                c.walk_in_direction(dir.truncate());
+                c.walk_in_direction(dir.truncate() * speed);
             }
         }
     }
 }
 
+/// Returns whether the provided state is `Enabled`
+fn is_enabled(state: &State) -> bool {
+    match *state {
+        State::Enabled => true,
+        State::Disabled => false,
+    }
+}
+
 /// Initializes the player control system
 pub fn initialize<'a, 'b>(
     world: &mut specs::World,
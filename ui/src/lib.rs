@@ -14,21 +14,32 @@ extern crate window;
 extern crate slog;
 extern crate assets;
 extern crate petgraph;
+#[macro_use]
+extern crate quick_error;
+extern crate ron;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 use common::{shred, specs};
 
 mod consts;
+mod localization;
 mod menus;
 mod theme;
 
+use common::conrod::text::font;
 use common::conrod::{self, gfx, render, Dimensions, Ui, UiBuilder};
-use common::{config, glutin, UiState};
+use common::{config, console, glutin, LoadingProgress, UiState};
 use window::window_event;
 
+use std::collections::HashMap;
 use std::sync::{mpsc, Arc, Mutex, MutexGuard};
 use std::time::{Duration, Instant};
 
 pub const UPS: u64 = 60;
 const UPDATE_INTERVAL: Duration = Duration::from_nanos(1_000_000_000 / UPS);
+/// The font used for languages whose string tables don't name a font
+const DEFAULT_FONT: &str = "NotoSans-Regular.ttf";
 
 /// A type that receives window events through a channel
 pub type EventReceiver = mpsc::Receiver<glutin::Event>;
@@ -67,6 +78,10 @@ pub struct System {
     last_run: Option<Instant>,
     cursor: conrod::cursor::MouseCursor,
     reader_id: window_event::ReaderId,
+    /// The font used for languages that don't require a specific font
+    default_font: font::Id,
+    /// The fonts that have been loaded for languages, by name
+    language_fonts: HashMap<String, font::Id>,
 }
 
 impl System {
@@ -81,24 +96,88 @@ impl System {
         let mut ui = UiBuilder::new(window_dim)
             .theme(theme::default_theme())
             .build();
-        let menus = menus::Menus::new(config.clone(), ui.widget_id_generator());
-
-        let font_path = assets.get_font_path("NotoSans-Regular.ttf");
+        let languages = localization::available_languages(log, assets);
+        let menus = menus::Menus::new(config.clone(), ui.widget_id_generator(), languages);
 
-        ui.fonts.insert_from_file(&font_path).unwrap_or_else(|e| {
-            error!(log, "Error loading font (at path `{:?}`): {}", font_path, e);
+        let font = load_font(assets, DEFAULT_FONT).unwrap_or_else(|e| {
+            error!(log, "{}", e;);
             panic!(common::CRASH_MSG);
         });
+        let default_font = ui.fonts.insert(font);
 
         let cursor = conrod::cursor::MouseCursor::Arrow;
 
-        Self {
+        let mut system = Self {
             ui,
             menus,
             events,
             cursor,
             last_run: None,
             reader_id,
+            default_font,
+            language_fonts: HashMap::new(),
+        };
+
+        system.set_language(log, assets, &config.interface.language);
+
+        system
+    }
+
+    /// Switches the UI text to the language with the provided code, and selects a font that covers
+    /// it
+    ///
+    /// English is used if the language fails to load, and the default font is used if the font of
+    /// the language fails to load.
+    fn set_language(&mut self, log: &slog::Logger, assets: &assets::Assets, language: &str) {
+        let localization =
+            localization::Localization::load(assets, language).unwrap_or_else(|e| {
+                error!(log, "Error loading language `{}`: {}", language, e;);
+                localization::Localization::english()
+            });
+
+        let font_id = match localization.font() {
+            Some(name) => self.language_font(log, assets, name),
+            None => self.default_font,
+        };
+        self.ui.theme.font_id = Some(font_id);
+
+        if let Some(font) = self.ui.fonts.get(font_id) {
+            let missing = localization.missing_glyphs(font);
+
+            if !missing.is_empty() {
+                warn!(log, "The font of the language doesn't cover all of its text"; o!(
+                    "language" => localization.language(),
+                    "missing_characters" => missing.into_iter().collect::<String>()
+                ));
+            }
+        }
+
+        info!(log, "Setting UI language"; o!("language" => localization.language()));
+        self.menus.set_localization(localization);
+    }
+
+    /// Returns the ID of the font with the provided name, loading it if no language has used it
+    /// yet
+    fn language_font(
+        &mut self,
+        log: &slog::Logger,
+        assets: &assets::Assets,
+        name: &str,
+    ) -> font::Id {
+        if let Some(&id) = self.language_fonts.get(name) {
+            return id;
+        }
+
+        match load_font(assets, name) {
+            Ok(font) => {
+                let id = self.ui.fonts.insert(font);
+                self.language_fonts.insert(name.to_string(), id);
+                id
+            }
+            Err(e) => {
+                error!(log, "{}", e;);
+                self.default_font
+            }
         }
     }
 
@@ -114,6 +193,18 @@ impl System {
     }
 }
 
+/// Loads the font with the provided name from the fonts directory
+fn load_font(assets: &assets::Assets, name: &str) -> Result<conrod::text::Font, String> {
+    let font_path = assets.get_font_path(name);
+
+    // The font is read through `Assets` so it can be loaded from an archive
+    assets
+        .read_bytes(&font_path)
+        .map_err(|e| e.to_string())
+        .and_then(|data| conrod::text::Font::from_bytes(data).map_err(|e| e.to_string()))
+        .map_err(|e| format!("Error loading font (at path `{:?}`): {}", font_path, e))
+}
+
 #[derive(SystemData)]
 pub struct Data<'a> {
     ui_state: specs::WriteExpect<'a, UiState>,
@@ -121,7 +212,10 @@ pub struct Data<'a> {
     window: specs::ReadExpect<'a, window::Window>,
     event_channel: specs::WriteExpect<'a, window_event::EventChannel>,
     config: specs::WriteExpect<'a, config::Config>,
+    console: specs::WriteExpect<'a, console::Console>,
+    loading_progress: specs::ReadExpect<'a, LoadingProgress>,
     log: specs::ReadExpect<'a, slog::Logger>,
+    assets: specs::ReadExpect<'a, Arc<assets::Assets>>,
 }
 
 impl<'a> specs::System<'a> for System {
@@ -130,14 +224,30 @@ impl<'a> specs::System<'a> for System {
     fn run(&mut self, mut data: Self::SystemData) {
         let window = data.window.get_window();
 
-        // Handle window resize events
+        let mut config_changed = false;
+
+        // Handle window resize and configuration change events
         for event in data.event_channel.read(&mut self.reader_id) {
-            if let window_event::Event::WindowResized(new_size) = *event {
-                let (new_width, new_height): (u32, u32) =
-                    new_size.to_physical(window.get_hidpi_factor()).into();
+            match *event {
+                window_event::Event::WindowResized(new_size) => {
+                    let (new_width, new_height): (u32, u32) =
+                        new_size.to_physical(window.get_hidpi_factor()).into();
+
+                    self.ui.win_w = new_width as conrod::Scalar;
+                    self.ui.win_h = new_height as conrod::Scalar;
+                }
+                window_event::Event::ConfigChanged(_) => config_changed = true,
+                _ => {}
+            }
+        }
+
+        // The configuration may have been changed outside of the options menu (such as by a
+        // console command), so update the copies stored by the menus
+        if config_changed {
+            self.menus.reset_config(data.config.clone());
 
-                self.ui.win_w = new_width as conrod::Scalar;
-                self.ui.win_h = new_height as conrod::Scalar;
+            if data.config.interface.language != self.menus.language() {
+                self.set_language(&data.log, &data.assets, &data.config.interface.language);
             }
         }
 
@@ -156,18 +266,34 @@ impl<'a> specs::System<'a> for System {
 
         // Update the UI
         let mut keypress = None;
+        let mut console_input = Vec::new();
 
         while let Ok(event) = self.events.try_recv() {
-            // Send keypresses to the UI if it is waiting for one
-            if self.menus.waiting_for_keypress() {
+            // Send text and keypresses to the console if it is open
+            if let UiState::Console = *data.ui_state {
                 if let glutin::Event::WindowEvent { ref event, .. } = event {
-                    if let glutin::WindowEvent::KeyboardInput { input, .. } = event {
-                        if let glutin::ElementState::Pressed = input.state {
-                            keypress = Some(input.clone());
+                    match event {
+                        glutin::WindowEvent::ReceivedCharacter(c) => {
+                            console_input.push(menus::ConsoleInput::Char(*c));
+                        }
+                        glutin::WindowEvent::KeyboardInput { input, .. } => {
+                            if let glutin::ElementState::Pressed = input.state {
+                                if let Some(key) = input.virtual_keycode {
+                                    console_input.push(menus::ConsoleInput::Key(key));
+                                }
+                            }
                         }
+                        _ => {}
                     }
                 }
             }
+
+            // Send keypresses, mouse clicks and scrolling to the UI if it is waiting for one
+            if self.menus.waiting_for_keypress() {
+                if let glutin::Event::WindowEvent { ref event, .. } = event {
+                    keypress = pressed_bind(event).or(keypress);
+                }
+            }
             if let Some(event) = conrod::backend::winit::convert_event(event, window)
             {
                 self.ui.handle_event(event);
@@ -188,8 +314,10 @@ impl<'a> specs::System<'a> for System {
             data.draw_list.0.is_none() ||
             // Rebuild widgets if a window event happened
             self.ui.global_input().events().next().is_some() ||
-            // Rebuild widgets regardless of events if the in-game menu is active
-            data.ui_state.is_in_game();
+            // Rebuild widgets regardless of events if the in-game menu or the console is active
+            data.ui_state.shows_game_world() ||
+            // Rebuild widgets while loading, so the progress bar is updated
+            data.ui_state.is_loading();
 
         // Reset the `force_redraw` flag
         self.menus.set_force_redraw(false);
@@ -199,6 +327,9 @@ impl<'a> specs::System<'a> for System {
             let mut ui = self.ui.set_widgets();
 
             match *data.ui_state {
+                UiState::Loading => self
+                    .menus
+                    .set_widgets_loading_screen(&mut ui, &data.loading_progress),
                 UiState::MainMenu => self.menus.set_widgets_main_menu(
                     &mut ui,
                     &mut data.ui_state,
@@ -225,16 +356,49 @@ impl<'a> specs::System<'a> for System {
                     &mut data.config,
                     &data.log,
                 ),
+                UiState::Console => {
+                    self.menus
+                        .set_widgets_console(&mut ui, &mut data.console, console_input)
+                }
+                UiState::Benchmark => self.menus.set_widgets_benchmark(&mut ui),
                 UiState::Exit => {}
             }
         }
     }
 }
 
+/// Returns the `Bind` that was pressed in the provided window event, if any
+fn pressed_bind(event: &glutin::WindowEvent) -> Option<config::Bind> {
+    match *event {
+        glutin::WindowEvent::KeyboardInput { input, .. } => {
+            match (input.state, input.virtual_keycode) {
+                (glutin::ElementState::Pressed, Some(key)) => {
+                    Some(config::Bind::new(config::Key::from(key), input.modifiers.into()))
+                }
+                _ => None,
+            }
+        }
+        glutin::WindowEvent::MouseInput {
+            state: glutin::ElementState::Pressed,
+            button,
+            modifiers,
+            ..
+        } => Some(config::Bind::new(
+            config::Input::Mouse(button.into()),
+            modifiers.into(),
+        )),
+        glutin::WindowEvent::MouseWheel {
+            delta, modifiers, ..
+        } => window_event::wheel_input(delta).map(|i| config::Bind::new(i, modifiers.into())),
+        _ => None,
+    }
+}
+
 /// Adds UI-related resources
 pub fn add_resources(world: &mut specs::World) {
     world.add_resource(UiDrawList(None));
-    world.add_resource(UiState::MainMenu);
+    world.add_resource(UiState::Loading);
+    world.add_resource(LoadingProgress::default());
 }
 
 /// Initializes UI-related systems
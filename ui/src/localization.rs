@@ -0,0 +1,375 @@
+//! Localization of UI text
+//!
+//! All text shown by the menus is looked up by key (such as `main_menu.start_game`) in the string
+//! table of the current language. The English table is built in, and the tables of other languages
+//! are loaded from `lang/<code>.ron` in the assets directory. Keys that are missing from a table
+//! fall back to English, so partial translations can be used.
+//!
+//! A string table looks like this:
+//!
+//! ```text
+//! (
+//!     name: "Deutsch",
+//!     strings: {
+//!         "main_menu.start_game": "Spiel starten",
+//!         "loading.progress": "Lädt... ({loaded}/{total})",
+//!     },
+//! )
+//! ```
+//!
+//! Strings can contain named arguments such as `{loaded}`, which are replaced by `format`.
+//!
+//! The default font only covers the Latin, Greek and Cyrillic scripts, so tables for languages that
+//! are written in other scripts should set the `font` field to the name of a font in the fonts
+//! directory that covers them.
+
+use assets::Assets;
+use common::conrod::text;
+use ron;
+use slog;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::{fmt, io, string};
+
+/// The code of the language that is used for keys that are missing from other languages
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// The built-in English string table
+// NOTE: If new text is added to the UI, add it here too
+const ENGLISH: &[(&str, &str)] = &[
+    ("language.name", "English"),
+    // Main menu
+    ("main_menu.title", "Horde Survival"),
+    ("main_menu.start_game", "Start Game"),
+    ("main_menu.options", "Options"),
+    ("main_menu.exit_game", "Exit Game"),
+    // Pause menu
+    ("pause_menu.resume_game", "Resume Game"),
+    ("pause_menu.options", "Options"),
+    ("pause_menu.exit_to_main_menu", "Exit to Main Menu"),
+    // Loading screen
+    ("loading.progress", "Loading... ({loaded}/{total})"),
+    ("loading.detecting_graphics", "Detecting graphics settings..."),
+    // Options menu
+    ("options.tab.camera", "Camera"),
+    ("options.tab.window", "Window"),
+    ("options.tab.graphics", "Graphics"),
+    ("options.tab.bindings", "Key Bindings"),
+    ("options.tab.interface", "Interface"),
+    ("options.back", "Back"),
+    ("options.apply", "Apply"),
+    ("options.cancel", "Cancel"),
+    ("options.on", "On"),
+    ("options.off", "Off"),
+    ("options.fov", "Field of view"),
+    ("options.sensitivity", "Sensitivity"),
+    ("options.invert_y", "Invert Mouse Y"),
+    ("options.mouse_smoothing", "Mouse Smoothing"),
+    ("options.window_size", "Window Size"),
+    ("options.fullscreen", "Fullscreen"),
+    ("options.vsync", "V-sync"),
+    ("options.pause_on_focus_loss", "Pause When Unfocused"),
+    ("options.requires_restart", "Changes require restart"),
+    ("options.graphics_preset", "Quality"),
+    ("options.preset.low", "Low"),
+    ("options.preset.medium", "Medium"),
+    ("options.preset.high", "High"),
+    ("options.preset.ultra", "Ultra"),
+    ("options.preset.custom", "Custom"),
+    ("options.postprocessing", "Postprocessing"),
+    ("options.particles", "Particles"),
+    ("options.shadows", "Shadows"),
+    ("options.shadow_map_size", "Shadow Map Size"),
+    ("options.language", "Language"),
+    ("options.bind.move_forward", "Move forward"),
+    ("options.bind.move_left", "Move left"),
+    ("options.bind.move_right", "Move right"),
+    ("options.bind.move_backward", "Move backward"),
+    ("options.bind.jump", "Jump"),
+    ("options.bind.sprint", "Sprint"),
+    ("options.bind.crouch", "Crouch"),
+    ("options.bind.dash", "Dash"),
+    ("options.bind.ascend", "Fly up (noclip)"),
+    ("options.bind.descend", "Fly down (noclip)"),
+    ("options.bind.toggle_console", "Toggle console"),
+    ("options.bind.reload_shaders", "Reload shaders"),
+    ("options.bind.category.movement", "Movement"),
+    ("options.bind.category.interface", "Interface"),
+    ("options.bind.category.debug", "Debug"),
+    ("options.bind.unbound", "None"),
+    ("options.bind.press_any_key", "Press any key or button..."),
+    ("options.bind.key_in_use", "Key already in use"),
+    ("options.auto_revert.title", "Keep window size changes?"),
+    (
+        "options.auto_revert.countdown",
+        "Auto-reverting changes in {seconds} seconds",
+    ),
+    ("options.auto_revert.keep", "Keep changes"),
+    ("options.auto_revert.revert", "Revert changes"),
+];
+
+quick_error! {
+    /// An error while loading a string table
+    #[derive(Debug)]
+    pub enum LocalizationError {
+        Io(path: PathBuf, err: io::Error) {
+            display("Error reading string table `{}`: {}", path.display(), err)
+        }
+        Utf8(path: PathBuf, err: string::FromUtf8Error) {
+            display("String table `{}` is not valid UTF-8: {}", path.display(), err)
+        }
+        Parse(path: PathBuf, err: ron::de::Error) {
+            display("Error parsing string table `{}`: {}", path.display(), err)
+        }
+    }
+}
+
+/// The contents of a string table file
+#[derive(Debug, Deserialize)]
+struct StringTable {
+    /// The name of the language, in that language
+    name: String,
+    /// A font that covers the script of the language, if the default font doesn't
+    #[serde(default)]
+    font: Option<String>,
+    strings: HashMap<String, String>,
+}
+
+/// A language that a string table is available for
+#[derive(Clone, Debug, PartialEq)]
+pub struct Language {
+    pub code: String,
+    pub name: String,
+}
+
+/// The strings of a language
+pub struct Localization {
+    language: String,
+    /// The string table of the language, or `None` if only the built-in English strings are used
+    table: Option<StringTable>,
+}
+
+impl Localization {
+    /// Returns a `Localization` that uses the built-in English strings
+    pub fn english() -> Self {
+        Self {
+            language: DEFAULT_LANGUAGE.to_string(),
+            table: None,
+        }
+    }
+
+    /// Loads the string table of the language with the provided code
+    ///
+    /// The built-in English strings are used for the default language, unless an asset layer
+    /// provides a string table for it.
+    pub fn load(assets: &Assets, language: &str) -> Result<Self, LocalizationError> {
+        let path = table_path(assets, language);
+
+        if language == DEFAULT_LANGUAGE && !assets.exists(&path) {
+            return Ok(Self::english());
+        }
+
+        let table = read_table(assets, path)?;
+
+        Ok(Self {
+            language: language.to_string(),
+            table: Some(table),
+        })
+    }
+
+    /// Returns the code of the language
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    /// Returns the name of the font (relative to the fonts directory) that the language requires,
+    /// or `None` if the default font covers it
+    pub fn font(&self) -> Option<&str> {
+        self.table.as_ref().and_then(|t| t.font.as_ref()).map(|f| f.as_str())
+    }
+
+    /// Returns the string with the provided key
+    ///
+    /// If the language doesn't have the key, the English string is returned instead, and if that
+    /// doesn't exist either, the key itself is returned so the missing string is easy to spot.
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.table
+            .as_ref()
+            .and_then(|t| t.strings.get(key))
+            .map(|s| s.as_str())
+            .or_else(|| english(key))
+            .unwrap_or(key)
+    }
+
+    /// Returns the string with the provided key, with each `{name}` replaced by the value of the
+    /// argument with that name
+    pub fn format(&self, key: &str, args: &[(&str, &fmt::Display)]) -> String {
+        let mut string = self.get(key).to_string();
+
+        for &(name, value) in args {
+            string = string.replace(&format!("{{{}}}", name), &value.to_string());
+        }
+
+        string
+    }
+
+    /// Returns the characters used by the language's strings that the provided font has no glyphs
+    /// for, sorted and without duplicates
+    pub fn missing_glyphs(&self, font: &text::Font) -> Vec<char> {
+        let mut missing = self
+            .table
+            .iter()
+            .flat_map(|t| t.strings.values())
+            .flat_map(|s| s.chars())
+            .filter(|c| !c.is_whitespace() && !c.is_control())
+            // Characters without a glyph are mapped to the `.notdef` glyph, which has ID 0
+            .filter(|&c| font.glyph(c).map_or(true, |g| g.id().0 == 0))
+            .collect::<Vec<_>>();
+
+        missing.sort();
+        missing.dedup();
+
+        missing
+    }
+}
+
+/// Returns all languages that string tables are available for, sorted by code
+///
+/// English is always available because its strings are built in. String tables that fail to load
+/// are logged and skipped.
+pub fn available_languages(log: &slog::Logger, assets: &Assets) -> Vec<Language> {
+    let mut languages = vec![Language {
+        code: DEFAULT_LANGUAGE.to_string(),
+        name: english("language.name").unwrap().to_string(),
+    }];
+
+    let files = assets.files().unwrap_or_else(|e| {
+        error!(log, "Error listing string tables: {}", e;);
+        Vec::new()
+    });
+
+    for path in files {
+        if !path.starts_with(assets.get_lang_dir())
+            || path.extension().and_then(|e| e.to_str()) != Some("ron")
+        {
+            continue;
+        }
+
+        let code = match path.file_stem().and_then(|s| s.to_str()) {
+            Some(c) => c.to_string(),
+            None => continue,
+        };
+
+        match read_table(assets, path) {
+            Ok(table) => {
+                // A string table for English replaces the name of the built-in one
+                languages.retain(|l| l.code != code);
+                languages.push(Language {
+                    code,
+                    name: table.name,
+                });
+            }
+            Err(e) => warn!(log, "Skipping language `{}`: {}", code, e;),
+        }
+    }
+
+    languages.sort_by(|a, b| a.code.cmp(&b.code));
+
+    languages
+}
+
+/// Returns the path of the string table of the language with the provided code
+fn table_path(assets: &Assets, language: &str) -> PathBuf {
+    assets.get_lang_path(format!("{}.ron", language))
+}
+
+/// Reads and parses the string table at the provided path
+fn read_table(assets: &Assets, path: PathBuf) -> Result<StringTable, LocalizationError> {
+    let data = match assets.read_bytes(&path) {
+        Ok(d) => d,
+        Err(e) => return Err(LocalizationError::Io(path, e)),
+    };
+    let data = match String::from_utf8(data) {
+        Ok(d) => d,
+        Err(e) => return Err(LocalizationError::Utf8(path, e)),
+    };
+
+    ron::de::from_str(&data).map_err(|e| LocalizationError::Parse(path, e))
+}
+
+/// Returns the built-in English string with the provided key
+fn english(key: &str) -> Option<&'static str> {
+    ENGLISH
+        .iter()
+        .find(|&&(k, _)| k == key)
+        .map(|&(_, string)| string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn localization(table: &str) -> Localization {
+        Localization {
+            language: "test".to_string(),
+            table: Some(ron::de::from_str(table).unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_fallback() {
+        let l = localization(r#"(name: "Test", strings: { "main_menu.options": "Optionen" })"#);
+
+        assert_eq!(l.get("main_menu.options"), "Optionen");
+        assert_eq!(l.get("main_menu.exit_game"), "Exit Game");
+        assert_eq!(l.get("no.such.key"), "no.such.key");
+        assert_eq!(l.font(), None);
+        assert_eq!(Localization::english().get("options.apply"), "Apply");
+    }
+
+    #[test]
+    fn test_format() {
+        let l = localization(
+            r#"(
+                name: "Test",
+                font: Some("Test.ttf"),
+                strings: { "loading.progress": "{loaded} von {total} ({loaded})" },
+            )"#,
+        );
+
+        assert_eq!(
+            l.format("loading.progress", &[("loaded", &3), ("total", &"10")]),
+            "3 von 10 (3)"
+        );
+        assert_eq!(
+            Localization::english().format("options.auto_revert.countdown", &[("seconds", &5)]),
+            "Auto-reverting changes in 5 seconds"
+        );
+        assert_eq!(l.font(), Some("Test.ttf"));
+    }
+
+    #[test]
+    fn test_english_keys_unique() {
+        let mut keys = ENGLISH.iter().map(|&(k, _)| k).collect::<Vec<_>>();
+        let len = keys.len();
+
+        keys.sort();
+        keys.dedup();
+
+        assert_eq!(keys.len(), len);
+    }
+
+    #[test]
+    fn test_english_action_names() {
+        use common::config::ACTIONS;
+
+        for info in ACTIONS {
+            let key = format!("options.bind.{}", info.name);
+            assert!(english(&key).is_some(), "{} has no English string", key);
+
+            let key = format!("options.bind.category.{}", info.category.name());
+            assert!(english(&key).is_some(), "{} has no English string", key);
+        }
+    }
+}
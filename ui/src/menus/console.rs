@@ -0,0 +1,147 @@
+//! Implementation of the developer console
+
+use common::conrod::widget::{self, Widget};
+use common::conrod::{self, color, Colorable, Positionable, Sizeable};
+use common::{console, glutin};
+
+use menus::Menus;
+use theme;
+
+const CONSOLE_HEIGHT_PCT: conrod::Scalar = 0.5;
+const CONSOLE_BACKGROUND_COLOR: color::Color = color::Color::Rgba(0.0, 0.0, 0.0, 0.8);
+const CONSOLE_FONT_SIZE: u32 = 16;
+const CONSOLE_MARGIN: conrod::Scalar = 10.0;
+
+/// Input sent to the console by the UI system
+pub enum ConsoleInput {
+    /// A character was typed
+    Char(char),
+    /// A key was pressed
+    Key(glutin::VirtualKeyCode),
+}
+
+/// State for the developer console
+pub struct ConsoleState {
+    /// The current line of input
+    input: String,
+    /// The index of the history entry being shown, if the history is being browsed
+    history_index: Option<usize>,
+}
+
+impl ConsoleState {
+    pub fn new() -> Self {
+        Self {
+            input: String::new(),
+            history_index: None,
+        }
+    }
+
+    /// Applies the provided input to the current line of input
+    fn handle_input(&mut self, input: ConsoleInput, console: &mut console::Console) {
+        match input {
+            // NOTE: The console is toggled with the grave accent key by default, so it is filtered
+            //       out to avoid typing it whenever the console is opened
+            ConsoleInput::Char(c) => {
+                if !c.is_control() && c != '`' {
+                    self.input.push(c);
+                }
+            }
+            ConsoleInput::Key(glutin::VirtualKeyCode::Back) => {
+                self.input.pop();
+            }
+            ConsoleInput::Key(glutin::VirtualKeyCode::Return)
+            | ConsoleInput::Key(glutin::VirtualKeyCode::NumpadEnter) => {
+                let line = self.input.split_off(0);
+                self.history_index = None;
+                console.submit(line);
+            }
+            ConsoleInput::Key(glutin::VirtualKeyCode::Tab) => {
+                if let Some(completed) = console.complete(&self.input) {
+                    self.input = completed;
+                }
+            }
+            ConsoleInput::Key(glutin::VirtualKeyCode::Up) => {
+                let history = console.history();
+
+                if history.is_empty() {
+                    return;
+                }
+
+                let index = match self.history_index {
+                    Some(i) => i.saturating_sub(1),
+                    None => history.len() - 1,
+                };
+
+                self.history_index = Some(index);
+                self.input = history[index].clone();
+            }
+            ConsoleInput::Key(glutin::VirtualKeyCode::Down) => {
+                let history = console.history();
+
+                match self.history_index {
+                    Some(i) if i + 1 < history.len() => {
+                        self.history_index = Some(i + 1);
+                        self.input = history[i + 1].clone();
+                    }
+                    Some(_) => {
+                        self.history_index = None;
+                        self.input.clear();
+                    }
+                    None => {}
+                }
+            }
+            ConsoleInput::Key(_) => {}
+        }
+    }
+}
+
+impl Menus {
+    pub fn set_widgets_console(
+        &mut self,
+        ui: &mut conrod::UiCell,
+        console: &mut console::Console,
+        input: Vec<ConsoleInput>,
+    ) {
+        let ids = &self.ids;
+
+        for i in input {
+            self.console_state.handle_input(i, console);
+        }
+
+        let console_height = ui.win_h * CONSOLE_HEIGHT_PCT;
+        let line_height = f64::from(CONSOLE_FONT_SIZE) * 1.25;
+
+        // The console canvas, which covers the top of the window
+        widget::Canvas::new()
+            .color(CONSOLE_BACKGROUND_COLOR)
+            .w_of(ui.window)
+            .h(console_height)
+            .mid_top_of(ui.window)
+            .set(ids.console_canvas, ui);
+
+        // Only show as many lines of output as can fit above the input line
+        let output = {
+            let visible_lines = ((console_height - CONSOLE_MARGIN * 2.0) / line_height) as usize;
+            let visible_lines = visible_lines.saturating_sub(1);
+            let lines = console.output().lines();
+            let skip = lines.len().saturating_sub(visible_lines);
+
+            lines.iter().skip(skip).cloned().collect::<Vec<_>>().join("\n")
+        };
+
+        widget::Text::new(&output)
+            .color(theme::FONT_COLOR)
+            .font_size(CONSOLE_FONT_SIZE)
+            .bottom_left_with_margins_on(
+                ids.console_canvas,
+                CONSOLE_MARGIN + line_height,
+                CONSOLE_MARGIN,
+            ).set(ids.console_output_text, ui);
+
+        widget::Text::new(&format!("> {}_", self.console_state.input))
+            .color(color::WHITE)
+            .font_size(CONSOLE_FONT_SIZE)
+            .bottom_left_with_margin_on(ids.console_canvas, CONSOLE_MARGIN)
+            .set(ids.console_input_text, ui);
+    }
+}
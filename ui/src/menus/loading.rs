@@ -0,0 +1,69 @@
+//! Implementation of the loading screen
+
+use common::conrod::widget::{self, Widget};
+use common::conrod::{self, color, Colorable, Positionable, Sizeable};
+use common::LoadingProgress;
+
+use consts::UI_BACKGROUND_COLOR;
+use menus::Menus;
+use theme;
+
+const LOADING_TEXT_FONT_SIZE: u32 = 32;
+const LOADING_TEXT_MARGIN: conrod::Scalar = 30.0;
+const PROGRESS_BAR_WIDTH: conrod::Scalar = 400.0;
+const PROGRESS_BAR_HEIGHT: conrod::Scalar = 20.0;
+const PROGRESS_BAR_BACKGROUND_COLOR: conrod::Color = color::DARK_CHARCOAL;
+const PROGRESS_BAR_COLOR: conrod::Color = color::LIGHT_GRAY;
+
+impl Menus {
+    pub fn set_widgets_loading_screen(
+        &mut self,
+        ui: &mut conrod::UiCell,
+        progress: &LoadingProgress,
+    ) {
+        let ids = &self.ids;
+
+        // Root canvas
+        widget::Canvas::new()
+            .color(UI_BACKGROUND_COLOR)
+            .set(ids.loading_root_canvas, ui);
+
+        // Progress bar background
+        widget::Rectangle::fill([PROGRESS_BAR_WIDTH, PROGRESS_BAR_HEIGHT])
+            .color(PROGRESS_BAR_BACKGROUND_COLOR)
+            .middle_of(ids.loading_root_canvas)
+            .set(ids.loading_progress_bar_background, ui);
+
+        // Progress bar, which grows from the left as assets are loaded
+        let width = PROGRESS_BAR_WIDTH * progress.fraction() as conrod::Scalar;
+        if width > 0.0 {
+            widget::Rectangle::fill([width, PROGRESS_BAR_HEIGHT])
+                .color(PROGRESS_BAR_COLOR)
+                .mid_left_of(ids.loading_progress_bar_background)
+                .set(ids.loading_progress_bar, ui);
+        }
+
+        // Loading text
+        let loading_text = self.strings.format(
+            "loading.progress",
+            &[("loaded", &progress.loaded), ("total", &progress.total)],
+        );
+
+        widget::Text::new(&loading_text)
+            .up_from(ids.loading_progress_bar_background, LOADING_TEXT_MARGIN)
+            .align_middle_x_of(ids.loading_progress_bar_background)
+            .color(theme::FONT_COLOR)
+            .font_size(LOADING_TEXT_FONT_SIZE)
+            .set(ids.loading_text, ui);
+    }
+
+    /// Creates the widgets shown while the graphics benchmark is running, which are drawn over the
+    /// game world
+    pub fn set_widgets_benchmark(&mut self, ui: &mut conrod::UiCell) {
+        widget::Text::new(self.strings.get("loading.detecting_graphics"))
+            .mid_top_with_margin_on(ui.window, LOADING_TEXT_MARGIN)
+            .color(theme::FONT_COLOR)
+            .font_size(LOADING_TEXT_FONT_SIZE)
+            .set(self.ids.benchmark_text, ui);
+    }
+}
@@ -2,7 +2,7 @@
 
 use common::conrod::widget::{self, Widget};
 use common::conrod::{self, color, Colorable, Positionable};
-use common::{config, glutin, UiState};
+use common::{self, config, glutin, UiState};
 use window::window_event;
 
 use consts::{self, GENERIC_BUTTON_SPACING, UI_BACKGROUND_COLOR};
@@ -20,6 +20,7 @@ impl Menus {
         config: &mut config::Config,
     ) {
         let ids = &self.ids;
+        let strings = &self.strings;
 
         // Root canvas
         widget::Canvas::new().set(ids.main_menu_root_canvas, ui);
@@ -31,25 +32,25 @@ impl Menus {
             .set(ids.main_canvas, ui);
 
         // Title text
-        widget::Text::new("Horde Survival")
+        widget::Text::new(strings.get("main_menu.title"))
             .mid_top_with_margin_on(ids.main_canvas, 75.0)
             .color(color::BLACK)
             .font_size(TITLE_TEXT_FONT_SIZE)
             .set(ids.title_text, ui);
 
         // Start game button
-        if consts::create_generic_button(widget::Button::new(), "Start Game")
+        if consts::create_generic_button(widget::Button::new(), strings.get("main_menu.start_game"))
             .align_middle_x_of(ids.main_canvas)
             .align_middle_y_of(ids.main_canvas)
             .set(ids.start_game_button, ui)
             .was_clicked()
         {
-            window.hide_cursor(true);
+            common::utils::grab_cursor(window, true);
             self.set_ui_state(ui_state, UiState::InGame);
         }
 
         // Options menu button
-        if consts::create_generic_button(widget::Button::new(), "Options")
+        if consts::create_generic_button(widget::Button::new(), strings.get("main_menu.options"))
             .y_relative(GENERIC_BUTTON_SPACING)
             .set(ids.main_menu_options_button, ui)
             .was_clicked()
@@ -59,7 +60,7 @@ impl Menus {
         }
 
         // Exit button
-        if consts::create_generic_button(widget::Button::new(), "Exit Game")
+        if consts::create_generic_button(widget::Button::new(), strings.get("main_menu.exit_game"))
             .y_relative(GENERIC_BUTTON_SPACING)
             .set(ids.exit_button, ui)
             .was_clicked()
@@ -74,6 +75,7 @@ impl Menus {
                 &mut self.current_config,
                 &mut self.new_config,
                 config,
+                strings,
                 ids,
                 ids.main_menu_root_canvas,
                 ui,
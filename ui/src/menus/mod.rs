@@ -1,15 +1,20 @@
 //! Implementation of game menus
 
+mod console;
 mod ingame;
+mod loading;
 mod main;
 mod options;
 mod pause;
 
+pub use self::console::ConsoleInput;
+
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Instant;
 
 use common::config;
 use common::conrod::widget::id;
+use localization::{Language, Localization};
 use UiState;
 
 widget_ids! {
@@ -44,6 +49,7 @@ widget_ids! {
         options_window_canvas,
         options_camera_canvas,
         options_bindings_canvas,
+        options_interface_canvas,
         reset_graphics_button,
         reset_window_button,
         reset_camera_button,
@@ -57,6 +63,12 @@ widget_ids! {
         sensitivity_canvas,
         sensitivity_label,
         sensitivity_slider,
+        invert_y_canvas,
+        invert_y_label,
+        invert_y_button,
+        smoothing_canvas,
+        smoothing_label,
+        smoothing_slider,
         window_size_canvas,
         window_size_label,
         window_size_button_left,
@@ -69,6 +81,15 @@ widget_ids! {
         vsync_canvas,
         vsync_label,
         vsync_button,
+        pause_on_focus_loss_canvas,
+        pause_on_focus_loss_label,
+        pause_on_focus_loss_button,
+        graphics_preset_canvas,
+        graphics_preset_label,
+        graphics_preset_button_left,
+        graphics_preset_button_right,
+        graphics_preset_text_canvas,
+        graphics_preset_text,
         postprocessing_canvas,
         postprocessing_label,
         postprocessing_button,
@@ -85,41 +106,32 @@ widget_ids! {
         shadow_map_size_text_canvas,
         shadow_map_size_text,
         bind_in_use_warning_text,
-        move_forward_canvas,
-        move_forward_canvas_2,
-        move_forward_label,
-        move_forward_button,
-        move_forward_rect,
-        move_forward_text,
-        move_forward_text_2,
-        move_left_canvas,
-        move_left_canvas_2,
-        move_left_label,
-        move_left_button,
-        move_left_rect,
-        move_left_text,
-        move_left_text_2,
-        move_right_canvas,
-        move_right_canvas_2,
-        move_right_label,
-        move_right_button,
-        move_right_rect,
-        move_right_text,
-        move_right_text_2,
-        move_backward_canvas,
-        move_backward_canvas_2,
-        move_backward_label,
-        move_backward_button,
-        move_backward_rect,
-        move_backward_text,
-        move_backward_text_2,
-        jump_canvas,
-        jump_canvas_2,
-        jump_label,
-        jump_button,
-        jump_rect,
-        jump_text,
-        jump_text_2,
+        bindings_scroll_canvas,
+        bind_category_canvas[],
+        bind_category_text[],
+        bind_canvas[],
+        bind_label[],
+        // One of each of these per bind slot of each action
+        bind_slot_canvas[],
+        bind_slot_button[],
+        bind_slot_rect[],
+        bind_slot_text[],
+        language_canvas,
+        language_label,
+        language_button_left,
+        language_button_right,
+        language_text_canvas,
+        language_text,
+        // Console
+        console_canvas,
+        console_output_text,
+        console_input_text,
+        // Loading screen
+        loading_root_canvas,
+        loading_text,
+        loading_progress_bar_background,
+        loading_progress_bar,
+        benchmark_text,
     }
 }
 
@@ -141,31 +153,8 @@ impl AutoRevertState {
     }
 }
 
-struct WaitForKeypressState {
-    move_forward: bool,
-    move_left: bool,
-    move_right: bool,
-    move_backward: bool,
-    jump: bool,
-}
-
-impl WaitForKeypressState {
-    fn new() -> Self {
-        Self {
-            move_forward: false,
-            move_left: false,
-            move_right: false,
-            move_backward: false,
-            jump: false,
-        }
-    }
-
-    /// Returns whether a UI element is waiting for a keypress
-    fn is_waiting(&self) -> bool {
-        // NOTE: If new keybindings are added, add them here too
-        self.move_forward || self.move_left || self.move_right || self.move_backward || self.jump
-    }
-}
+/// The bind slot that is waiting for a keypress in the options menu, if any
+type WaitForKeypressState = Option<(config::Action, config::BindSlot)>;
 
 /// Stores the state required to run each menu
 pub struct Menus {
@@ -186,23 +175,52 @@ pub struct Menus {
     /// The config that is edited in the options menu. Upon pressing the `Apply` button, the value
     /// of `new_config` is written to both `current_config` and the `Config` resource
     new_config: options::ConfigUiState,
+    /// State for the developer console
+    console_state: console::ConsoleState,
+    /// The strings of the current language
+    strings: Localization,
+    /// The languages that can be selected in the options menu
+    languages: Vec<Language>,
 }
 
 impl Menus {
-    pub fn new(config: config::Config, ui: id::Generator) -> Self {
+    pub fn new(config: config::Config, ui: id::Generator, languages: Vec<Language>) -> Self {
         let config: options::ConfigUiState = config.into();
         Menus {
             ids: Ids::new(ui),
             force_redraw: false.into(),
             options_menu_return_to: None,
             auto_revert_state: None,
-            wait_for_keypress_state: WaitForKeypressState::new(),
+            wait_for_keypress_state: None,
             show_key_warning: false,
             current_config: config.clone(),
             new_config: config,
+            console_state: console::ConsoleState::new(),
+            strings: Localization::english(),
+            languages,
         }
     }
 
+    /// Returns the code of the current language
+    pub fn language(&self) -> &str {
+        self.strings.language()
+    }
+
+    /// Replaces the strings used by the menus with the ones of another language
+    pub fn set_localization(&mut self, strings: Localization) {
+        self.strings = strings;
+        self.set_force_redraw(true);
+    }
+
+    /// Replaces the configuration stored by the menus with the provided one, discarding any
+    /// unapplied changes
+    pub fn reset_config(&mut self, config: config::Config) {
+        let config: options::ConfigUiState = config.into();
+        self.current_config = config.clone();
+        self.new_config = config;
+        self.set_force_redraw(true);
+    }
+
     /// Returns whether the auto-revert windows settings pop-up is currently showing
     pub fn showing_auto_revert(&self) -> bool {
         self.auto_revert_state.is_some()
@@ -210,7 +228,7 @@ impl Menus {
 
     /// Returns whether a UI element is waiting for a keypress
     pub fn waiting_for_keypress(&self) -> bool {
-        self.wait_for_keypress_state.is_waiting()
+        self.wait_for_keypress_state.is_some()
     }
 
     /// Returns whether the UI should be forced to be redrawn
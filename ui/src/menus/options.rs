@@ -2,7 +2,7 @@
 
 use common::conrod::widget::{self, Widget};
 use common::conrod::{self, color, Colorable, Labelable, Positionable, Sizeable};
-use common::{config, gfx, glutin, UiState};
+use common::{config, config_file, gfx, UiState};
 use petgraph;
 use slog;
 use window::window_event;
@@ -10,6 +10,7 @@ use window::window_event;
 use std::{cmp, fmt};
 
 use consts::{self, UI_BACKGROUND_COLOR};
+use localization::{Language, Localization};
 use menus::{AutoRevertState, Ids, Menus, WaitForKeypressState};
 use theme;
 
@@ -31,6 +32,11 @@ pub struct ConfigUiState {
     pub window: WindowConfig,
     pub camera: config::CameraConfig,
     pub bindings: config::BindConfig,
+    /// The gamepad configuration, which isn't editable in the options menu
+    pub gamepad: config::GamepadConfig,
+    /// The asset configuration, which isn't editable in the options menu
+    pub assets: config::AssetsConfig,
+    pub interface: config::InterfaceConfig,
 }
 
 impl Into<config::Config> for ConfigUiState {
@@ -41,10 +47,14 @@ impl Into<config::Config> for ConfigUiState {
         camera.sensitivity *= config::DEFAULT_SENSITIVITY;
 
         config::Config {
+            version: config_file::CONFIG_VERSION,
             graphics: self.graphics.into(),
             window: self.window.into(),
             camera: camera,
             bindings: self.bindings,
+            gamepad: self.gamepad,
+            assets: self.assets,
+            interface: self.interface,
         }
     }
 }
@@ -60,6 +70,9 @@ impl From<config::Config> for ConfigUiState {
             window: config.window.into(),
             camera,
             bindings: config.bindings,
+            gamepad: config.gamepad,
+            assets: config.assets,
+            interface: config.interface,
         }
     }
 }
@@ -82,6 +95,7 @@ pub enum ShadowMapSize {
     _512,
     _1024,
     _2048,
+    _4096,
     Custom(gfx::texture::Size),
 }
 
@@ -97,6 +111,7 @@ impl ShadowMapSize {
             ShadowMapSize::_512 => 0,
             ShadowMapSize::_1024 => 1,
             ShadowMapSize::_2048 => 2,
+            ShadowMapSize::_4096 => 3,
             ShadowMapSize::Custom(_) => -1,
         }
     }
@@ -112,7 +127,8 @@ impl SelectOption for ShadowMapSize {
         match *self {
             ShadowMapSize::_512 => ShadowMapSize::_1024,
             ShadowMapSize::_1024 => ShadowMapSize::_2048,
-            ShadowMapSize::_2048 => ShadowMapSize::_2048,
+            ShadowMapSize::_2048 => ShadowMapSize::_4096,
+            ShadowMapSize::_4096 => ShadowMapSize::_4096,
             ShadowMapSize::Custom(_) => unreachable!(),
         }
     }
@@ -122,6 +138,7 @@ impl SelectOption for ShadowMapSize {
             ShadowMapSize::_512 => ShadowMapSize::_512,
             ShadowMapSize::_1024 => ShadowMapSize::_512,
             ShadowMapSize::_2048 => ShadowMapSize::_1024,
+            ShadowMapSize::_4096 => ShadowMapSize::_2048,
             ShadowMapSize::Custom(_) => ShadowMapSize::_512,
         }
     }
@@ -140,6 +157,7 @@ impl Into<gfx::texture::Size> for ShadowMapSize {
             ShadowMapSize::_512 => 512,
             ShadowMapSize::_1024 => 1024,
             ShadowMapSize::_2048 => 2048,
+            ShadowMapSize::_4096 => 4096,
             ShadowMapSize::Custom(size) => size,
         }
     }
@@ -151,6 +169,7 @@ impl From<gfx::texture::Size> for ShadowMapSize {
             512 => ShadowMapSize::_512,
             1024 => ShadowMapSize::_1024,
             2048 => ShadowMapSize::_2048,
+            4096 => ShadowMapSize::_4096,
             other => ShadowMapSize::Custom(other),
         }
     }
@@ -291,6 +310,7 @@ pub struct WindowConfig {
     pub dimensions: WindowDimensions,
     pub fullscreen: bool,
     pub vsync: bool,
+    pub pause_on_focus_loss: bool,
 }
 
 impl Into<config::WindowConfig> for WindowConfig {
@@ -301,6 +321,7 @@ impl Into<config::WindowConfig> for WindowConfig {
             height,
             fullscreen: self.fullscreen,
             vsync: self.vsync,
+            pause_on_focus_loss: self.pause_on_focus_loss,
         }
     }
 }
@@ -311,6 +332,7 @@ impl From<config::WindowConfig> for WindowConfig {
             dimensions: WindowDimensions::from((config.width, config.height)),
             fullscreen: config.fullscreen,
             vsync: config.vsync,
+            pause_on_focus_loss: config.pause_on_focus_loss,
         }
     }
 }
@@ -335,12 +357,28 @@ impl Menus {
         &mut self,
         ui: &mut conrod::UiCell,
         ui_state: &mut UiState,
-        keypress: Option<glutin::KeyboardInput>,
+        keypress: Option<config::Bind>,
         event_channel: &mut window_event::EventChannel,
         config: &mut config::Config,
         log: &slog::Logger,
     ) {
+        {
+            let action_count = config::ACTIONS.len();
+            let category_count = bind_category_count();
+            let mut generator = ui.widget_id_generator();
+
+            self.ids.bind_category_canvas.resize(category_count, &mut generator);
+            self.ids.bind_category_text.resize(category_count, &mut generator);
+            self.ids.bind_canvas.resize(action_count, &mut generator);
+            self.ids.bind_label.resize(action_count, &mut generator);
+            self.ids.bind_slot_canvas.resize(action_count * 2, &mut generator);
+            self.ids.bind_slot_button.resize(action_count * 2, &mut generator);
+            self.ids.bind_slot_rect.resize(action_count * 2, &mut generator);
+            self.ids.bind_slot_text.resize(action_count * 2, &mut generator);
+        }
+
         let ids = &self.ids;
+        let strings = &self.strings;
 
         let ui_height = ui.window_dim()[1];
 
@@ -366,10 +404,11 @@ impl Menus {
 
         // Options sub-menu tabs
         widget::Tabs::new(&[
-            (ids.options_camera_canvas, "Camera"),
-            (ids.options_window_canvas, "Window"),
-            (ids.options_graphics_canvas, "Graphics"),
-            (ids.options_bindings_canvas, "Key Bindings"),
+            (ids.options_camera_canvas, strings.get("options.tab.camera")),
+            (ids.options_window_canvas, strings.get("options.tab.window")),
+            (ids.options_graphics_canvas, strings.get("options.tab.graphics")),
+            (ids.options_bindings_canvas, strings.get("options.tab.bindings")),
+            (ids.options_interface_canvas, strings.get("options.tab.interface")),
         ]).middle_of(ids.options_top_canvas)
         .wh_of(ids.options_top_canvas)
         .bar_thickness(50.0)
@@ -379,7 +418,7 @@ impl Menus {
         let mut update_config = false;
 
         // Back button
-        if options_transition_button(widget::Button::new(), "Back")
+        if options_transition_button(widget::Button::new(), strings.get("options.back"))
             .middle_of(ids.options_bottom_canvas)
             .set(ids.back_button, ui)
             .was_clicked()
@@ -389,7 +428,7 @@ impl Menus {
         }
 
         // Apply button
-        if options_transition_button(widget::Button::new(), "Apply")
+        if options_transition_button(widget::Button::new(), strings.get("options.apply"))
             .x_relative_to(ids.back_button, -OPTIONS_TRANSITION_SPACING)
             .set(ids.apply_button, ui)
             .was_clicked()
@@ -398,7 +437,7 @@ impl Menus {
         }
 
         // Cancel button
-        if options_transition_button(widget::Button::new(), "Cancel")
+        if options_transition_button(widget::Button::new(), strings.get("options.cancel"))
             .x_relative_to(ids.back_button, OPTIONS_TRANSITION_SPACING)
             .set(ids.cancel_button, ui)
             .was_clicked()
@@ -417,7 +456,7 @@ impl Menus {
             ui,
         );
 
-        option_label("Field of view", ids.fov_label, ids.fov_canvas, ui);
+        option_label(strings.get("options.fov"), ids.fov_label, ids.fov_canvas, ui);
 
         if let Some(new_fov) = widget::Slider::new(self.new_config.camera.fov.round(), 30.0, 120.0)
             .mid_right_with_margin_on(ids.fov_canvas, OPTION_MARGIN)
@@ -438,7 +477,7 @@ impl Menus {
         );
 
         option_label(
-            "Sensitivity",
+            strings.get("options.sensitivity"),
             ids.sensitivity_label,
             ids.sensitivity_canvas,
             ui,
@@ -455,6 +494,59 @@ impl Menus {
             self.new_config.camera.sensitivity = new_sensitivity;
         }
 
+        // Invert Y option
+        option_canvas(
+            &mut camera_option_index,
+            ids.invert_y_canvas,
+            ids.options_camera_canvas,
+            ui,
+        );
+
+        option_label(
+            strings.get("options.invert_y"),
+            ids.invert_y_label,
+            ids.invert_y_canvas,
+            ui,
+        );
+
+        if toggle_button(
+            strings,
+            &mut self.new_config.camera.invert_y,
+            ids.invert_y_button,
+            ids.invert_y_canvas,
+            // Extra margin to line up with the sliders
+            (OPTION_SLIDER_WIDTH - 150.0) / 2.0,
+            ui,
+        ) {
+            self.set_force_redraw(true);
+        }
+
+        // Mouse smoothing option
+        option_canvas(
+            &mut camera_option_index,
+            ids.smoothing_canvas,
+            ids.options_camera_canvas,
+            ui,
+        );
+
+        option_label(
+            strings.get("options.mouse_smoothing"),
+            ids.smoothing_label,
+            ids.smoothing_canvas,
+            ui,
+        );
+
+        // Values close to 1 make the camera lag too far behind the mouse to be useful
+        if let Some(new_smoothing) = widget::Slider::new(self.new_config.camera.smoothing, 0.0, 0.9)
+            .mid_right_with_margin_on(ids.smoothing_canvas, OPTION_MARGIN)
+            .label(&format!("{:.*}", 2, self.new_config.camera.smoothing))
+            .label_font_size(OPTION_LABEL_FONT_SIZE)
+            .w(OPTION_SLIDER_WIDTH)
+            .set(ids.smoothing_slider, ui)
+        {
+            self.new_config.camera.smoothing = new_smoothing;
+        }
+
         let mut window_option_index = 0;
         // Window size option
         option_canvas(
@@ -465,7 +557,7 @@ impl Menus {
         );
 
         option_label(
-            "Window Size",
+            strings.get("options.window_size"),
             ids.window_size_label,
             ids.window_size_canvas,
             ui,
@@ -495,13 +587,14 @@ impl Menus {
         );
 
         option_label(
-            "Fullscreen",
+            strings.get("options.fullscreen"),
             ids.fullscreen_label,
             ids.fullscreen_canvas,
             ui,
         );
 
         if toggle_button(
+            strings,
             &mut self.new_config.window.fullscreen,
             ids.fullscreen_button,
             ids.fullscreen_canvas,
@@ -520,9 +613,10 @@ impl Menus {
             ui,
         );
 
-        option_label("V-sync", ids.vsync_label, ids.vsync_canvas, ui);
+        option_label(strings.get("options.vsync"), ids.vsync_label, ids.vsync_canvas, ui);
 
         if toggle_button(
+            strings,
             &mut self.new_config.window.vsync,
             ids.vsync_button,
             ids.vsync_canvas,
@@ -533,10 +627,37 @@ impl Menus {
             self.set_force_redraw(true);
         }
 
+        // Pause on focus loss option
+        option_canvas(
+            &mut window_option_index,
+            ids.pause_on_focus_loss_canvas,
+            ids.options_window_canvas,
+            ui,
+        );
+
+        option_label(
+            strings.get("options.pause_on_focus_loss"),
+            ids.pause_on_focus_loss_label,
+            ids.pause_on_focus_loss_canvas,
+            ui,
+        );
+
+        if toggle_button(
+            strings,
+            &mut self.new_config.window.pause_on_focus_loss,
+            ids.pause_on_focus_loss_button,
+            ids.pause_on_focus_loss_canvas,
+            // Extra margin to line up with the window size selector
+            100.0,
+            ui,
+        ) {
+            self.set_force_redraw(true);
+        }
+
         // Warn about v-sync changes requiring restart
         if self.new_config.window.vsync != self.current_config.window.vsync {
             warning_text(
-                "Changes require restart",
+                strings.get("options.requires_restart"),
                 theme::FONT_COLOR,
                 ids.changes_require_restart_text,
                 ids.options_window_canvas,
@@ -545,6 +666,34 @@ impl Menus {
         }
 
         let mut graphics_option_index = 0;
+        // Graphics preset option
+        option_canvas(
+            &mut graphics_option_index,
+            ids.graphics_preset_canvas,
+            ids.options_graphics_canvas,
+            ui,
+        );
+
+        option_label(
+            strings.get("options.graphics_preset"),
+            ids.graphics_preset_label,
+            ids.graphics_preset_canvas,
+            ui,
+        );
+
+        if graphics_preset_selector(
+            strings,
+            &mut self.new_config.graphics,
+            ids.graphics_preset_button_left,
+            ids.graphics_preset_button_right,
+            ids.graphics_preset_text_canvas,
+            ids.graphics_preset_text,
+            ids.graphics_preset_canvas,
+            ui,
+        ) {
+            self.set_force_redraw(true);
+        }
+
         // Postprocessing option
         option_canvas(
             &mut graphics_option_index,
@@ -554,13 +703,14 @@ impl Menus {
         );
 
         option_label(
-            "Postprocessing",
+            strings.get("options.postprocessing"),
             ids.postprocessing_label,
             ids.postprocessing_canvas,
             ui,
         );
 
         if toggle_button(
+            strings,
             &mut self.new_config.graphics.postprocessing,
             ids.postprocessing_button,
             ids.postprocessing_canvas,
@@ -579,9 +729,15 @@ impl Menus {
             ui,
         );
 
-        option_label("Particles", ids.particles_label, ids.particles_canvas, ui);
+        option_label(
+            strings.get("options.particles"),
+            ids.particles_label,
+            ids.particles_canvas,
+            ui,
+        );
 
         if toggle_button(
+            strings,
             &mut self.new_config.graphics.particles,
             ids.particles_button,
             ids.particles_canvas,
@@ -600,9 +756,15 @@ impl Menus {
             ui,
         );
 
-        option_label("Shadows", ids.shadows_label, ids.shadows_canvas, ui);
+        option_label(
+            strings.get("options.shadows"),
+            ids.shadows_label,
+            ids.shadows_canvas,
+            ui,
+        );
 
         if toggle_button(
+            strings,
             &mut self.new_config.graphics.shadows,
             ids.shadows_button,
             ids.shadows_canvas,
@@ -622,7 +784,7 @@ impl Menus {
         );
 
         option_label(
-            "Shadow Map Size",
+            strings.get("options.shadow_map_size"),
             ids.shadow_map_size_label,
             ids.shadow_map_size_canvas,
             ui,
@@ -630,7 +792,7 @@ impl Menus {
 
         if option_selector(
             &mut self.new_config.graphics.shadow_map_size,
-            ShadowMapSize::_2048,
+            ShadowMapSize::_4096,
             ids.shadow_map_size_button_left,
             ids.shadow_map_size_button_right,
             ids.shadow_map_size_text_canvas,
@@ -641,171 +803,84 @@ impl Menus {
             self.set_force_redraw(true);
         };
 
+        // The bindings are in a scrollable canvas, since there can be more of them than fit
+        widget::Canvas::new()
+            .scroll_kids_vertically()
+            .color(color::TRANSPARENT)
+            .wh_of(ids.options_bindings_canvas)
+            .middle_of(ids.options_bindings_canvas)
+            .set(ids.bindings_scroll_canvas, ui);
+
         let mut bindings_option_index = 0;
-        let any_waiting_for_keypress = self.wait_for_keypress_state.is_waiting();
+        let any_waiting_for_keypress = self.wait_for_keypress_state.is_some();
         let mut redraw = false;
         let mut show_warning = false;
         let mut hide_warning = false;
+        let mut category = None;
+        let mut category_index = 0;
+
+        for (i, info) in config::ACTIONS.iter().enumerate() {
+            // Each category starts with a row containing its name
+            if category != Some(info.category) {
+                category = Some(info.category);
+
+                option_canvas(
+                    &mut bindings_option_index,
+                    ids.bind_category_canvas[category_index],
+                    ids.bindings_scroll_canvas,
+                    ui,
+                );
+
+                widget::Text::new(
+                    strings.get(&format!("options.bind.category.{}", info.category.name())),
+                ).middle_of(ids.bind_category_canvas[category_index])
+                .font_size(OPTION_NAME_FONT_SIZE)
+                .color(OPTION_NAME_COLOR)
+                .set(ids.bind_category_text[category_index], ui);
+
+                category_index += 1;
+            }
 
-        // Move forward binding option
-        option_canvas(
-            &mut bindings_option_index,
-            ids.move_forward_canvas,
-            ids.options_bindings_canvas,
-            ui,
-        );
-
-        option_label(
-            "Move forward",
-            ids.move_forward_label,
-            ids.move_forward_canvas,
-            ui,
-        );
-
-        let bind_result = binding_option(
-            &mut self.wait_for_keypress_state.move_forward,
-            any_waiting_for_keypress,
-            keypress,
-            config::BindName::MoveForward,
-            &mut self.new_config.bindings,
-            ids.move_forward_canvas_2,
-            ids.move_forward_button,
-            ids.move_forward_rect,
-            ids.move_forward_text,
-            ids.move_forward_text_2,
-            ids.move_forward_canvas,
-            ui,
-        );
-
-        redraw = redraw || bind_result.redraw;
-        show_warning = show_warning || bind_result.show_warning;
-        hide_warning = hide_warning || bind_result.hide_warning;
-
-        // Move left binding option
-        option_canvas(
-            &mut bindings_option_index,
-            ids.move_left_canvas,
-            ids.options_bindings_canvas,
-            ui,
-        );
-
-        option_label("Move left", ids.move_left_label, ids.move_left_canvas, ui);
-
-        let bind_result = binding_option(
-            &mut self.wait_for_keypress_state.move_left,
-            any_waiting_for_keypress,
-            keypress,
-            config::BindName::MoveLeft,
-            &mut self.new_config.bindings,
-            ids.move_left_canvas_2,
-            ids.move_left_button,
-            ids.move_left_rect,
-            ids.move_left_text,
-            ids.move_left_text_2,
-            ids.move_left_canvas,
-            ui,
-        );
-
-        redraw = redraw || bind_result.redraw;
-        show_warning = show_warning || bind_result.show_warning;
-        hide_warning = hide_warning || bind_result.hide_warning;
-
-        // Move right binding option
-        option_canvas(
-            &mut bindings_option_index,
-            ids.move_right_canvas,
-            ids.options_bindings_canvas,
-            ui,
-        );
-
-        option_label(
-            "Move right",
-            ids.move_right_label,
-            ids.move_right_canvas,
-            ui,
-        );
-
-        let bind_result = binding_option(
-            &mut self.wait_for_keypress_state.move_right,
-            any_waiting_for_keypress,
-            keypress,
-            config::BindName::MoveRight,
-            &mut self.new_config.bindings,
-            ids.move_right_canvas_2,
-            ids.move_right_button,
-            ids.move_right_rect,
-            ids.move_right_text,
-            ids.move_right_text_2,
-            ids.move_right_canvas,
-            ui,
-        );
-
-        redraw = redraw || bind_result.redraw;
-        show_warning = show_warning || bind_result.show_warning;
-        hide_warning = hide_warning || bind_result.hide_warning;
-
-        // Move backward binding option
-        option_canvas(
-            &mut bindings_option_index,
-            ids.move_backward_canvas,
-            ids.options_bindings_canvas,
-            ui,
-        );
-
-        option_label(
-            "Move backward",
-            ids.move_backward_label,
-            ids.move_backward_canvas,
-            ui,
-        );
-
-        let bind_result = binding_option(
-            &mut self.wait_for_keypress_state.move_backward,
-            any_waiting_for_keypress,
-            keypress,
-            config::BindName::MoveBackward,
-            &mut self.new_config.bindings,
-            ids.move_backward_canvas_2,
-            ids.move_backward_button,
-            ids.move_backward_rect,
-            ids.move_backward_text,
-            ids.move_backward_text_2,
-            ids.move_backward_canvas,
-            ui,
-        );
-
-        redraw = redraw || bind_result.redraw;
-        show_warning = show_warning || bind_result.show_warning;
-        hide_warning = hide_warning || bind_result.hide_warning;
-
-        // Jump binding option
-        option_canvas(
-            &mut bindings_option_index,
-            ids.jump_canvas,
-            ids.options_bindings_canvas,
-            ui,
-        );
+            option_canvas(
+                &mut bindings_option_index,
+                ids.bind_canvas[i],
+                ids.bindings_scroll_canvas,
+                ui,
+            );
 
-        option_label("Jump", ids.jump_label, ids.jump_canvas, ui);
-
-        let bind_result = binding_option(
-            &mut self.wait_for_keypress_state.jump,
-            any_waiting_for_keypress,
-            keypress,
-            config::BindName::Jump,
-            &mut self.new_config.bindings,
-            ids.jump_canvas_2,
-            ids.jump_button,
-            ids.jump_rect,
-            ids.jump_text,
-            ids.jump_text_2,
-            ids.jump_canvas,
-            ui,
-        );
+            option_label(
+                strings.get(&format!("options.bind.{}", info.name)),
+                ids.bind_label[i],
+                ids.bind_canvas[i],
+                ui,
+            );
 
-        redraw = redraw || bind_result.redraw;
-        show_warning = show_warning || bind_result.show_warning;
-        hide_warning = hide_warning || bind_result.hide_warning;
+            for (j, &slot) in [config::BindSlot::Primary, config::BindSlot::Secondary]
+                .iter()
+                .enumerate()
+            {
+                let slot_index = i * 2 + j;
+                let bind_result = binding_option(
+                    strings,
+                    &mut self.wait_for_keypress_state,
+                    any_waiting_for_keypress,
+                    keypress.as_ref(),
+                    info.action,
+                    slot,
+                    &mut self.new_config.bindings,
+                    ids.bind_slot_canvas[slot_index],
+                    ids.bind_slot_button[slot_index],
+                    ids.bind_slot_rect[slot_index],
+                    ids.bind_slot_text[slot_index],
+                    ids.bind_canvas[i],
+                    ui,
+                );
+
+                redraw = redraw || bind_result.redraw;
+                show_warning = show_warning || bind_result.show_warning;
+                hide_warning = hide_warning || bind_result.hide_warning;
+            }
+        }
 
         // This makes the warning persistent
         if show_warning {
@@ -823,7 +898,7 @@ impl Menus {
 
         if self.show_key_warning {
             warning_text(
-                "Key already in use",
+                strings.get("options.bind.key_in_use"),
                 color::RED,
                 ids.bind_in_use_warning_text,
                 ids.options_bindings_canvas,
@@ -831,6 +906,35 @@ impl Menus {
             );
         }
 
+        let mut interface_option_index = 0;
+        // Language option
+        option_canvas(
+            &mut interface_option_index,
+            ids.language_canvas,
+            ids.options_interface_canvas,
+            ui,
+        );
+
+        option_label(
+            strings.get("options.language"),
+            ids.language_label,
+            ids.language_canvas,
+            ui,
+        );
+
+        if language_selector(
+            &mut self.new_config.interface.language,
+            &self.languages,
+            ids.language_button_left,
+            ids.language_button_right,
+            ids.language_text_canvas,
+            ids.language_text,
+            ids.language_canvas,
+            ui,
+        ) {
+            self.set_force_redraw(true);
+        }
+
         // Auto-revert window settings pop-up
         let redraw = if self.showing_auto_revert() {
             auto_revert_popup(
@@ -838,6 +942,7 @@ impl Menus {
                 &mut self.current_config,
                 &mut self.new_config,
                 config,
+                strings,
                 ids,
                 ids.options_root_canvas,
                 ui,
@@ -875,6 +980,7 @@ impl Menus {
             self.current_config.graphics = new_config.graphics;
             self.current_config.bindings = new_config.bindings;
             self.current_config.window = new_config.window;
+            self.current_config.interface = new_config.interface;
 
             // Force redraw to make "changes require restart" warning go away
             self.set_force_redraw(true);
@@ -883,7 +989,7 @@ impl Menus {
         if exit_options_menu {
             match self.options_menu_return_to.take() {
                 Some(menu) => {
-                    self.wait_for_keypress_state = WaitForKeypressState::new();
+                    self.wait_for_keypress_state = None;
                     self.set_ui_state(ui_state, menu.into());
                 }
                 None => {
@@ -941,21 +1047,25 @@ fn option_label(
         .set(id, ui);
 }
 
-/// Creates and handles widgets for a `SelectOption` object
+/// A button of a selector widget
+enum Step {
+    Previous,
+    Next,
+}
+
+/// Creates the widgets of a selector, which shows the provided text between left and right buttons
 ///
-/// Returns `true` if the selection has changed and the UI should be redrawn
-#[must_use]
-fn option_selector<O: SelectOption + fmt::Display>(
-    option: &mut O,
-    max_option: O,
+/// Returns the button that was clicked, if any
+fn selector_widgets(
+    text: &str,
     id_button_left: petgraph::graph::NodeIndex,
     id_button_right: petgraph::graph::NodeIndex,
     id_text_canvas: petgraph::graph::NodeIndex,
     id_text: petgraph::graph::NodeIndex,
     parent: petgraph::graph::NodeIndex,
     ui: &mut conrod::UiCell,
-) -> bool {
-    let mut redraw = false;
+) -> Option<Step> {
+    let mut step = None;
 
     if widget::Button::new()
         .color(color::RED)
@@ -967,8 +1077,7 @@ fn option_selector<O: SelectOption + fmt::Display>(
         .set(id_button_right, ui)
         .was_clicked()
     {
-        *option = option.next(max_option);
-        redraw = true;
+        step = Some(Step::Next);
     }
 
     widget::Canvas::new()
@@ -978,7 +1087,7 @@ fn option_selector<O: SelectOption + fmt::Display>(
         .x_relative(-150.0)
         .set(id_text_canvas, ui);
 
-    widget::Text::new(&format!("{}", option))
+    widget::Text::new(text)
         .align_middle_y_of(id_text_canvas)
         .align_middle_x_of(id_text_canvas)
         .font_size(OPTION_LABEL_FONT_SIZE)
@@ -995,24 +1104,159 @@ fn option_selector<O: SelectOption + fmt::Display>(
         .set(id_button_left, ui)
         .was_clicked()
     {
-        *option = option.previous();
-        redraw = true;
+        step = Some(Step::Previous);
+    }
+
+    step
+}
+
+/// Creates and handles widgets for a `SelectOption` object
+///
+/// Returns `true` if the selection has changed and the UI should be redrawn
+#[must_use]
+fn option_selector<O: SelectOption + fmt::Display>(
+    option: &mut O,
+    max_option: O,
+    id_button_left: petgraph::graph::NodeIndex,
+    id_button_right: petgraph::graph::NodeIndex,
+    id_text_canvas: petgraph::graph::NodeIndex,
+    id_text: petgraph::graph::NodeIndex,
+    parent: petgraph::graph::NodeIndex,
+    ui: &mut conrod::UiCell,
+) -> bool {
+    let step = selector_widgets(
+        &format!("{}", option),
+        id_button_left,
+        id_button_right,
+        id_text_canvas,
+        id_text,
+        parent,
+        ui,
+    );
+
+    match step {
+        Some(Step::Next) => *option = option.next(max_option),
+        Some(Step::Previous) => *option = option.previous(),
+        None => return false,
+    }
+
+    true
+}
+
+/// Creates and handles widgets for selecting a language (stored as its code) from the available
+/// ones
+///
+/// Returns `true` if the selection has changed and the UI should be redrawn
+#[must_use]
+fn language_selector(
+    language: &mut String,
+    languages: &[Language],
+    id_button_left: petgraph::graph::NodeIndex,
+    id_button_right: petgraph::graph::NodeIndex,
+    id_text_canvas: petgraph::graph::NodeIndex,
+    id_text: petgraph::graph::NodeIndex,
+    parent: petgraph::graph::NodeIndex,
+    ui: &mut conrod::UiCell,
+) -> bool {
+    let index = languages.iter().position(|l| l.code == *language);
+    // The configured language may not have a string table, in which case its code is shown
+    let name = index.map_or(language.clone(), |i| languages[i].name.clone());
+
+    let step = selector_widgets(
+        &name,
+        id_button_left,
+        id_button_right,
+        id_text_canvas,
+        id_text,
+        parent,
+        ui,
+    );
+
+    let new_index = match (step, index) {
+        (None, _) => return false,
+        (Some(Step::Next), Some(i)) => cmp::min(i + 1, languages.len() - 1),
+        (Some(Step::Previous), Some(i)) => i.saturating_sub(1),
+        (Some(_), None) => 0,
+    };
+
+    match languages.get(new_index) {
+        Some(l) if index != Some(new_index) => {
+            *language = l.code.clone();
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Creates and handles a selector for graphics presets, which replaces all graphics settings with
+/// those of the selected preset
+///
+/// `Custom` is shown if the settings don't match a preset, and can't be selected.
+///
+/// Returns `true` if the selection has changed and the UI should be redrawn
+#[must_use]
+fn graphics_preset_selector(
+    strings: &Localization,
+    graphics: &mut GraphicsConfig,
+    id_button_left: petgraph::graph::NodeIndex,
+    id_button_right: petgraph::graph::NodeIndex,
+    id_text_canvas: petgraph::graph::NodeIndex,
+    id_text: petgraph::graph::NodeIndex,
+    parent: petgraph::graph::NodeIndex,
+    ui: &mut conrod::UiCell,
+) -> bool {
+    let settings: config::GraphicsConfig = graphics.clone().into();
+    let preset = settings.preset();
+    let presets = &config::GraphicsPreset::ALL;
+    let index = presets.iter().position(|&p| p == preset);
+
+    let step = selector_widgets(
+        strings.get(&format!("options.preset.{}", preset.name())),
+        id_button_left,
+        id_button_right,
+        id_text_canvas,
+        id_text,
+        parent,
+        ui,
+    );
+
+    let new_index = match (step, index) {
+        (None, _) => return false,
+        (Some(Step::Next), Some(i)) => cmp::min(i + 1, presets.len() - 1),
+        (Some(Step::Previous), Some(i)) => i.saturating_sub(1),
+        // Custom settings are between the presets, so start from the lowest one
+        (Some(_), None) => 0,
+    };
+
+    if index == Some(new_index) {
+        return false;
     }
 
-    redraw
+    match presets[new_index].settings() {
+        Some(settings) => {
+            *graphics = settings.into();
+            true
+        }
+        None => false,
+    }
 }
 
 /// Creates and handles a toggle button widget
 ///
 /// Returns `true` if the selection has changed and the UI should be redrawn
 fn toggle_button(
+    strings: &Localization,
     state: &mut bool,
     id: petgraph::graph::NodeIndex,
     parent: petgraph::graph::NodeIndex,
     extra_margin: conrod::Scalar,
     ui: &mut conrod::UiCell,
 ) -> bool {
-    let text = if *state { "On" } else { "Off" };
+    let text = if *state {
+        strings.get("options.on")
+    } else {
+        strings.get("options.off")
+    };
 
     if widget::Button::new()
         .color(color::RED)
@@ -1075,6 +1319,12 @@ fn get_config_changed_events(a: &ConfigUiState, b: &ConfigUiState) -> Vec<window
         ));
     }
 
+    if a.interface != b.interface {
+        events.push(window_event::Event::ConfigChanged(
+            window_event::ChangedConfig::Interface,
+        ));
+    }
+
     events
 }
 
@@ -1098,6 +1348,7 @@ pub fn auto_revert_popup(
     current_config: &mut ConfigUiState,
     new_config: &mut ConfigUiState,
     config_resource: &mut config::Config,
+    strings: &Localization,
     ids: &Ids,
     parent: petgraph::graph::NodeIndex,
     ui: &mut conrod::UiCell,
@@ -1136,9 +1387,9 @@ pub fn auto_revert_popup(
             .mid_top_of(ids.auto_revert_canvas)
             .set(ids.keep_changes_canvas, ui);
 
-        let auto_revert_text = &format!(
-            "Auto-reverting changes in {} seconds",
-            AUTO_REVERT_TIME - elapsed
+        let auto_revert_text = &strings.format(
+            "options.auto_revert.countdown",
+            &[("seconds", &(AUTO_REVERT_TIME - elapsed))],
         );
 
         widget::Text::new(auto_revert_text)
@@ -1148,7 +1399,7 @@ pub fn auto_revert_popup(
             .center_justify()
             .set(ids.auto_revert_text, ui);
 
-        let keep_changes_text = "Keep window size changes?";
+        let keep_changes_text = strings.get("options.auto_revert.title");
 
         widget::Text::new(keep_changes_text)
             .middle_of(ids.keep_changes_canvas)
@@ -1159,7 +1410,7 @@ pub fn auto_revert_popup(
             .w_h(popup_width * 0.4, popup_height * 0.25)
             .mid_left_with_margin_on(ids.auto_revert_canvas, 25.0)
             .down(100.0)
-            .label("Keep changes")
+            .label(strings.get("options.auto_revert.keep"))
             .label_font_size(22)
             .set(ids.keep_changes_button, ui)
             .was_clicked()
@@ -1172,7 +1423,7 @@ pub fn auto_revert_popup(
             .wh_of(ids.keep_changes_button)
             .mid_right_with_margin_on(ids.auto_revert_canvas, 25.0)
             .y_relative(0.0)
-            .label("Revert changes")
+            .label(strings.get("options.auto_revert.revert"))
             .label_font_size(22)
             .set(ids.revert_changes_button, ui)
             .was_clicked()
@@ -1222,108 +1473,133 @@ impl BindResult {
     }
 }
 
-/// Creates and handles a keybinding editor widget
+/// Returns the number of categories in the action registry, counting a category again if the
+/// actions in it aren't next to each other
+fn bind_category_count() -> usize {
+    let mut count = 0;
+    let mut category = None;
+
+    for info in config::ACTIONS {
+        if category != Some(info.category) {
+            category = Some(info.category);
+            count += 1;
+        }
+    }
+
+    count
+}
+
+/// Creates and handles the editor widget of one bind slot of an action
+///
+/// While the slot is waiting for a keypress, `Escape` cancels editing and `Back` clears the slot.
 #[must_use]
 fn binding_option(
-    // Whether this keybinding widget is waiting for a keypress
-    waiting_for_keypress: &mut bool,
+    strings: &Localization,
+    // The bind slot that is waiting for a keypress, if any
+    waiting_for_keypress: &mut WaitForKeypressState,
     // Whether any keybinding widget is waiting for a keypress
     any_waiting_for_keypress: bool,
-    keypress: Option<glutin::KeyboardInput>,
-    binding: config::BindName,
+    keypress: Option<&config::Bind>,
+    action: config::Action,
+    slot: config::BindSlot,
     bindings: &mut config::BindConfig,
     canvas_id: petgraph::graph::NodeIndex,
     button_id: petgraph::graph::NodeIndex,
     rect_id: petgraph::graph::NodeIndex,
     text_id: petgraph::graph::NodeIndex,
-    text_id_2: petgraph::graph::NodeIndex,
     parent: petgraph::graph::NodeIndex,
     ui: &mut conrod::UiCell,
 ) -> BindResult {
     let mut result = BindResult::new();
     let (w, h) = (200.0, OPTION_HEIGHT * 0.8);
+    // The secondary slot is at the right, and the primary slot is at its left
+    let margin = match slot {
+        config::BindSlot::Primary => OPTION_MARGIN * 2.0 + w,
+        config::BindSlot::Secondary => OPTION_MARGIN,
+    };
+
+    widget::Canvas::new()
+        .w_h(w, h)
+        .color(color::TRANSPARENT)
+        .mid_right_with_margin_on(parent, margin)
+        .set(canvas_id, ui);
 
-    if *waiting_for_keypress {
+    if *waiting_for_keypress == Some((action, slot)) {
         widget::Canvas::new()
             .w_h(w, h)
             .color(color::RED.clicked())
-            .mid_right_with_margin_on(parent, OPTION_MARGIN)
+            .middle_of(canvas_id)
             .set(rect_id, ui);
 
-        widget::Canvas::new()
-            .w_h(w, h)
-            .y_relative(0.0)
-            .x_relative(-w)
-            .set(canvas_id, ui);
-
-        widget::Text::new("Press any key...")
+        widget::Text::new(strings.get("options.bind.press_any_key"))
             .middle_of(rect_id)
             .set(text_id, ui);
 
-        if let Some(input) = keypress {
-            if let Some(keycode) = input.virtual_keycode {
-                match keycode {
-                    glutin::VirtualKeyCode::Escape => {
-                        // If `Escape` was pressed, stop editing this keybinding and hide the "key
-                        // in use" warning if it is showing
-                        result.hide_warning = true;
-                        result.redraw = true;
-                        *waiting_for_keypress = false;
-                    }
-                    glutin::VirtualKeyCode::LAlt
-                    | glutin::VirtualKeyCode::RAlt
-                    | glutin::VirtualKeyCode::LControl
-                    | glutin::VirtualKeyCode::RControl
-                    | glutin::VirtualKeyCode::LShift
-                    | glutin::VirtualKeyCode::RShift
-                    | glutin::VirtualKeyCode::LWin
-                    | glutin::VirtualKeyCode::RWin => {
-                        // The above keys cannot be bound
-                    }
-                    _ => {
-                        let new_bind = config::Bind::new(keycode.into(), input.modifiers.into());
-
-                        // If the keybinding is in use, show a warning and continue waiting for a
-                        // keypress
-                        if bindings.is_in_use(&new_bind) {
+        if let Some(new_bind) = keypress {
+            match new_bind.input {
+                config::Input::Key(config::Key::Escape) => {
+                    // If `Escape` was pressed, stop editing this keybinding and hide the "key in
+                    // use" warning if it is showing
+                    result.hide_warning = true;
+                    result.redraw = true;
+                    *waiting_for_keypress = None;
+                }
+                config::Input::Key(config::Key::Back) => {
+                    // If `Backspace` was pressed, unbind this slot
+                    *bindings.get_mut(action).get_mut(slot) = None;
+                    result.hide_warning = true;
+                    result.redraw = true;
+                    *waiting_for_keypress = None;
+                }
+                config::Input::Key(config::Key::LAlt)
+                | config::Input::Key(config::Key::RAlt)
+                | config::Input::Key(config::Key::LControl)
+                | config::Input::Key(config::Key::RControl)
+                | config::Input::Key(config::Key::LShift)
+                | config::Input::Key(config::Key::RShift)
+                | config::Input::Key(config::Key::LWin)
+                | config::Input::Key(config::Key::RWin) => {
+                    // The above keys cannot be bound
+                }
+                _ => {
+                    match bindings.find(new_bind) {
+                        // If the keybinding is used by another slot, show a warning and continue
+                        // waiting for a keypress
+                        Some(used_by) if used_by != (action, slot) => {
                             result.show_warning = true;
-                        } else {
-                            // Otherwise, hide the warning if it is showing and set the keybinding
+                        }
+                        // Otherwise, hide the warning if it is showing and set the keybinding
+                        _ => {
                             result.hide_warning = true;
-                            bindings.set(binding.clone(), new_bind);
-                            *waiting_for_keypress = false;
-                        };
-
-                        result.redraw = true;
+                            *bindings.get_mut(action).get_mut(slot) = Some(new_bind.clone());
+                            *waiting_for_keypress = None;
+                        }
                     }
+
+                    result.redraw = true;
                 }
             }
         }
     } else {
+        let label = match bindings.get(action).get(slot) {
+            Some(bind) => format!("{}", bind),
+            None => strings.get("options.bind.unbound").to_string(),
+        };
+
         if widget::Button::new()
             .color(color::RED)
-            .mid_right_with_margin_on(parent, OPTION_MARGIN)
+            .middle_of(canvas_id)
             .w_h(w, h)
-            .label("Change")
+            .label(&label)
             .set(button_id, ui)
             .was_clicked()
         {
             if !any_waiting_for_keypress {
-                *waiting_for_keypress = true;
+                *waiting_for_keypress = Some((action, slot));
                 result.redraw = true;
             }
         }
-
-        widget::Canvas::new()
-            .w_h(w, h)
-            .y_relative(0.0)
-            .x_relative(-w)
-            .set(canvas_id, ui);
     }
 
-    widget::Text::new(&format!("{}", bindings.get_mut(binding)))
-        .middle_of(canvas_id)
-        .set(text_id_2, ui);
-
     result
 }
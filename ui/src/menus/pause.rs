@@ -18,6 +18,7 @@ impl Menus {
         config: &mut config::Config,
     ) {
         let ids = &self.ids;
+        let strings = &self.strings;
 
         // Root canvas
         widget::Canvas::new().set(ids.pause_menu_root_canvas, ui);
@@ -29,17 +30,19 @@ impl Menus {
             .set(ids.pause_canvas, ui);
 
         // Resume game button
-        if consts::create_generic_button(widget::Button::new(), "Resume Game")
-            .align_middle_x_of(ids.pause_canvas)
-            .align_middle_y_of(ids.pause_canvas)
-            .set(ids.resume_game_button, ui)
-            .was_clicked()
+        if consts::create_generic_button(
+            widget::Button::new(),
+            strings.get("pause_menu.resume_game"),
+        ).align_middle_x_of(ids.pause_canvas)
+        .align_middle_y_of(ids.pause_canvas)
+        .set(ids.resume_game_button, ui)
+        .was_clicked()
         {
             window_event::unpause(ui_state, window, event_channel);
         }
 
         // Options menu button
-        if consts::create_generic_button(widget::Button::new(), "Options")
+        if consts::create_generic_button(widget::Button::new(), strings.get("pause_menu.options"))
             .y_relative(GENERIC_BUTTON_SPACING)
             .set(ids.pause_menu_options_button, ui)
             .was_clicked()
@@ -49,10 +52,12 @@ impl Menus {
         }
 
         // Exit to main menu button
-        if consts::create_generic_button(widget::Button::new(), "Exit to Main Menu")
-            .y_relative(GENERIC_BUTTON_SPACING)
-            .set(ids.exit_to_main_menu_button, ui)
-            .was_clicked()
+        if consts::create_generic_button(
+            widget::Button::new(),
+            strings.get("pause_menu.exit_to_main_menu"),
+        ).y_relative(GENERIC_BUTTON_SPACING)
+        .set(ids.exit_to_main_menu_button, ui)
+        .was_clicked()
         {
             self.set_ui_state(ui_state, UiState::MainMenu);
         }
@@ -64,6 +69,7 @@ impl Menus {
                 &mut self.current_config,
                 &mut self.new_config,
                 config,
+                strings,
                 ids,
                 ids.pause_menu_root_canvas,
                 ui,
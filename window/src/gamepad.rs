@@ -0,0 +1,374 @@
+//! Gamepad input
+//!
+//! Gamepads are read through gilrs. The movement stick is sent as analog movement, the look stick
+//! rotates the camera at a rate that doesn't depend on the frame rate, and buttons trigger the
+//! actions they are bound to in the same way as keys.
+
+use common::cgmath::{self, InnerSpace, Rad, Zero};
+use common::glutin::{self, ElementState};
+use common::{self, config, UiState};
+use gilrs::{self, Axis, Button, EventType, Gilrs};
+use slog;
+
+use std::time::Instant;
+
+use input::{HeldActions, ToggledActions};
+use window_event::{self, CameraRotation, Event, EventChannel};
+
+/// Reads the state of all connected gamepads
+pub struct Gamepads {
+    gilrs: Gilrs,
+    /// The analog movement that was last sent, so it is only sent again when it changes
+    last_movement: cgmath::Vector2<::Float>,
+    last_poll: Instant,
+}
+
+impl Gamepads {
+    /// Starts reading gamepad input, or returns `None` if gamepads aren't supported
+    pub fn new(log: &slog::Logger) -> Option<Self> {
+        let gilrs = match Gilrs::new() {
+            Ok(g) => g,
+            Err(e) => {
+                error!(log, "Error initializing gamepad support: {}", e;);
+                return None;
+            }
+        };
+
+        for (_, gamepad) in gilrs.gamepads() {
+            info!(log, "Found gamepad"; o!("name" => gamepad.name().to_string()));
+        }
+
+        Some(Self {
+            gilrs,
+            last_movement: cgmath::Vector2::zero(),
+            last_poll: Instant::now(),
+        })
+    }
+
+    /// Processes gamepad input, sending the resulting events to the event channel
+    ///
+    /// Sticks and bound buttons only have an effect while the game is running, but the pause button
+    /// also unpauses the game. Gamepad input is received even while another window is focused, so
+    /// it is ignored unless `focused` is `true`.
+    pub fn poll(
+        &mut self,
+        config: &config::Config,
+        channel: &mut EventChannel,
+        toggled: &mut ToggledActions,
+        held: &mut HeldActions,
+        window: &glutin::Window,
+        ui_state: &mut UiState,
+        focused: bool,
+        log: &slog::Logger,
+    ) {
+        let elapsed = self.last_poll.elapsed();
+        self.last_poll = Instant::now();
+        let delta = elapsed.as_secs() as ::Float + elapsed.subsec_nanos() as ::Float * 1e-9;
+
+        while let Some(gilrs::Event { id, event, .. }) = self.gilrs.next_event() {
+            match event {
+                EventType::Connected => {
+                    let name = self.gilrs.gamepad(id).name().to_string();
+                    info!(log, "Gamepad connected"; o!("name" => name));
+                }
+                EventType::Disconnected => {
+                    let name = self.gilrs.gamepad(id).name().to_string();
+                    info!(log, "Gamepad disconnected"; o!("name" => name));
+                }
+                EventType::ButtonPressed(..) | EventType::ButtonReleased(..) if !focused => {}
+                EventType::ButtonPressed(button, _) => {
+                    let action = button_action(config, button, ElementState::Pressed, ui_state);
+                    apply_button_action(action, config, channel, toggled, held, window, ui_state);
+                }
+                EventType::ButtonReleased(button, _) => {
+                    let action = button_action(config, button, ElementState::Released, ui_state);
+                    apply_button_action(action, config, channel, toggled, held, window, ui_state);
+                }
+                _ => {}
+            }
+        }
+
+        let (movement, look) = if config.gamepad.enabled && focused && ui_state.is_in_game() {
+            self.stick_values(config)
+        } else {
+            (cgmath::Vector2::zero(), cgmath::Vector2::zero())
+        };
+
+        if movement != self.last_movement {
+            self.last_movement = movement;
+            channel.single_write(Event::AnalogMovement(movement));
+        }
+
+        if look.magnitude2() > 0.0 {
+            // Pushing the stick right turns right (negative yaw), and pushing it up looks up
+            let rotation = CameraRotation::new(Rad(look.y * delta), Rad(-look.x * delta));
+            channel.single_write(Event::RotateCamera(rotation));
+        }
+    }
+
+    /// Forgets the analog movement that was last sent, so it is sent again on the next poll after
+    /// the input has been reset
+    pub fn reset(&mut self) {
+        self.last_movement = cgmath::Vector2::zero();
+    }
+
+    /// Returns the movement and look stick values of the first connected gamepad, after applying
+    /// the stick configuration
+    fn stick_values(
+        &self,
+        config: &config::Config,
+    ) -> (cgmath::Vector2<::Float>, cgmath::Vector2<::Float>) {
+        let gamepad = match self.gilrs.gamepads().map(|(_, g)| g).find(|g| g.is_connected()) {
+            Some(g) => g,
+            None => return (cgmath::Vector2::zero(), cgmath::Vector2::zero()),
+        };
+
+        let movement = stick_response(
+            gamepad.value(Axis::LeftStickX),
+            gamepad.value(Axis::LeftStickY),
+            &config.gamepad.movement_stick,
+        );
+        let look = stick_response(
+            gamepad.value(Axis::RightStickX),
+            gamepad.value(Axis::RightStickY),
+            &config.gamepad.look_stick,
+        );
+
+        (movement, look)
+    }
+}
+
+/// What pressing or releasing a gamepad button does
+#[derive(Debug)]
+enum ButtonAction {
+    /// Pauses the game if it is running, and unpauses it if it is paused
+    TogglePause,
+    /// Opens the console if the game is running, and closes it if it is open
+    ToggleConsole,
+    /// Triggers the actions bound to the bind, which was pressed or released
+    Bind(config::Bind, ElementState),
+}
+
+/// Returns the action of a gamepad button that was pressed or released
+///
+/// Like keys, buttons bound to actions only have an effect while the game is running, except for
+/// the pause button and the console bind, which also work in the pause menu and the console.
+fn button_action(
+    config: &config::Config,
+    button: Button,
+    state: ElementState,
+    ui_state: &UiState,
+) -> Option<ButtonAction> {
+    let button = match to_config_button(button) {
+        Some(b) if config.gamepad.enabled => b,
+        _ => return None,
+    };
+
+    // Gamepad buttons have no modifiers
+    let bind = config::Bind::new(button, Default::default());
+    let pause = button == config.gamepad.pause;
+    let console = config.bindings.toggle_console.slot_of(&bind).is_some();
+
+    match state {
+        ElementState::Pressed if pause => Some(ButtonAction::TogglePause),
+        ElementState::Pressed if console => Some(ButtonAction::ToggleConsole),
+        _ if pause || console => None,
+        _ if ui_state.is_in_game() => Some(ButtonAction::Bind(bind, state)),
+        _ => None,
+    }
+}
+
+/// Performs the action of a gamepad button
+fn apply_button_action(
+    action: Option<ButtonAction>,
+    config: &config::Config,
+    channel: &mut EventChannel,
+    toggled: &mut ToggledActions,
+    held: &mut HeldActions,
+    window: &glutin::Window,
+    ui_state: &mut UiState,
+) {
+    match action {
+        Some(ButtonAction::TogglePause) => match *ui_state {
+            UiState::InGame => {
+                common::utils::grab_cursor(window, false);
+                *ui_state = UiState::PauseMenu;
+            }
+            UiState::PauseMenu => {
+                window_event::unpause(ui_state, window, channel);
+            }
+            _ => {}
+        },
+        Some(ButtonAction::ToggleConsole) => {
+            window_event::toggle_console(ui_state, window, channel);
+        }
+        Some(ButtonAction::Bind(bind, state)) => {
+            window_event::process_bind(config, channel, toggled, held, &bind, state);
+        }
+        None => {}
+    }
+}
+
+/// Applies the deadzones, response curve, inversion and sensitivity of a stick to its raw axis
+/// values, which range from -1 to 1 with up and right being positive
+pub fn stick_response(x: f32, y: f32, stick: &config::StickConfig) -> cgmath::Vector2<::Float> {
+    let x = axis_response(x, stick.deadzone_x, stick.curve);
+    let y = axis_response(y, stick.deadzone_y, stick.curve);
+    let y = if stick.invert_y { -y } else { y };
+
+    cgmath::Vector2::new(x as ::Float, y as ::Float) * stick.sensitivity as ::Float
+}
+
+/// Rescales the part of an axis value outside the deadzone to the range 0 to 1, and applies the
+/// response curve to it
+fn axis_response(value: f32, deadzone: f32, curve: f32) -> f32 {
+    let magnitude = value.abs();
+
+    if magnitude <= deadzone {
+        return 0.0;
+    }
+
+    let scaled = ((magnitude - deadzone) / (1.0 - deadzone)).min(1.0);
+
+    scaled.powf(curve) * value.signum()
+}
+
+/// Returns the configuration name of a gilrs button, or `None` if it can't be bound
+fn to_config_button(button: Button) -> Option<config::GamepadButton> {
+    use common::config::GamepadButton;
+
+    let button = match button {
+        Button::South => GamepadButton::South,
+        Button::East => GamepadButton::East,
+        Button::North => GamepadButton::North,
+        Button::West => GamepadButton::West,
+        Button::LeftTrigger => GamepadButton::LeftBumper,
+        Button::LeftTrigger2 => GamepadButton::LeftTrigger,
+        Button::RightTrigger => GamepadButton::RightBumper,
+        Button::RightTrigger2 => GamepadButton::RightTrigger,
+        Button::Select => GamepadButton::Select,
+        Button::Start => GamepadButton::Start,
+        Button::LeftThumb => GamepadButton::LeftStick,
+        Button::RightThumb => GamepadButton::RightStick,
+        Button::DPadUp => GamepadButton::DPadUp,
+        Button::DPadDown => GamepadButton::DPadDown,
+        Button::DPadLeft => GamepadButton::DPadLeft,
+        Button::DPadRight => GamepadButton::DPadRight,
+        _ => return None,
+    };
+
+    Some(button)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stick(deadzone: f32, curve: f32) -> config::StickConfig {
+        config::StickConfig {
+            deadzone_x: deadzone,
+            deadzone_y: deadzone * 2.0,
+            curve,
+            sensitivity: 1.0,
+            invert_y: false,
+        }
+    }
+
+    #[test]
+    fn test_deadzone() {
+        let stick = stick(0.25, 1.0);
+
+        // Each axis has its own deadzone
+        assert_eq!(stick_response(0.625, 0.625, &stick), cgmath::Vector2::new(0.5, 0.25));
+        assert_eq!(stick_response(-0.125, 0.5, &stick), cgmath::Vector2::new(0.0, 0.0));
+        assert_eq!(stick_response(-1.0, 1.0, &stick), cgmath::Vector2::new(-1.0, 1.0));
+    }
+
+    #[test]
+    fn test_curve_and_sensitivity() {
+        let mut stick = stick(0.0, 2.0);
+        stick.sensitivity = 3.0;
+        stick.invert_y = true;
+
+        assert_eq!(stick_response(0.5, -0.5, &stick), cgmath::Vector2::new(0.75, 0.75));
+        assert_eq!(stick_response(-1.0, 0.0, &stick), cgmath::Vector2::new(-3.0, 0.0));
+    }
+
+    #[test]
+    fn test_config_buttons() {
+        use common::config::GamepadButton;
+
+        assert_eq!(to_config_button(Button::South), Some(GamepadButton::South));
+        assert_eq!(to_config_button(Button::LeftTrigger), Some(GamepadButton::LeftBumper));
+        assert_eq!(to_config_button(Button::LeftTrigger2), Some(GamepadButton::LeftTrigger));
+        assert_eq!(to_config_button(Button::Start), Some(GamepadButton::Start));
+        assert_eq!(to_config_button(Button::Mode), None);
+    }
+
+    #[test]
+    fn test_button_actions() {
+        use common::config::GamepadButton;
+        use common::glutin::ElementState::{Pressed, Released};
+
+        let mut config = config::Config::default();
+        let in_game = UiState::InGame;
+        let paused = UiState::PauseMenu;
+
+        match button_action(&config, Button::South, Released, &in_game) {
+            Some(ButtonAction::Bind(ref bind, Released))
+                if bind.input == config::Input::Gamepad(GamepadButton::South) => {}
+            a => panic!("Unexpected action {:?}", a),
+        }
+
+        // Binds only work in game, but pausing also works in the pause menu
+        assert!(button_action(&config, Button::South, Pressed, &paused).is_none());
+        assert!(button_action(&config, Button::South, Released, &paused).is_none());
+        match button_action(&config, Button::Start, Pressed, &paused) {
+            Some(ButtonAction::TogglePause) => {}
+            a => panic!("Unexpected action {:?}", a),
+        }
+        assert!(button_action(&config, Button::Start, Released, &in_game).is_none());
+
+        config.bindings.toggle_console.secondary =
+            Some(config::Bind::new(GamepadButton::Select, Default::default()));
+        match button_action(&config, Button::Select, Pressed, &paused) {
+            Some(ButtonAction::ToggleConsole) => {}
+            a => panic!("Unexpected action {:?}", a),
+        }
+
+        // Nothing works while gamepads are disabled
+        config.gamepad.enabled = false;
+        assert!(button_action(&config, Button::South, Pressed, &in_game).is_none());
+    }
+
+    #[test]
+    fn test_button_binds() {
+        use common::config::GamepadButton;
+        use window_event::process_bind;
+
+        let config = config::Config::default();
+        let mut channel = EventChannel::new();
+        let mut reader = channel.register_reader();
+        let mut toggled = ToggledActions::default();
+        let mut held = HeldActions::default();
+
+        // Gamepad buttons trigger actions with their modes, like keys
+        for &(button, state) in &[
+            (GamepadButton::South, ElementState::Pressed),
+            (GamepadButton::South, ElementState::Released),
+            (GamepadButton::East, ElementState::Pressed),
+            (GamepadButton::East, ElementState::Released),
+            (GamepadButton::East, ElementState::Pressed),
+        ] {
+            let bind = config::Bind::new(button, Default::default());
+
+            process_bind(&config, &mut channel, &mut toggled, &mut held, &bind, state);
+        }
+
+        let events: Vec<_> = channel
+            .read(&mut reader)
+            .map(|e| format!("{:?}", e))
+            .collect();
+        assert_eq!(events, ["Jump", "JumpReleased", "Crouch(Enabled)", "Crouch(Disabled)"]);
+    }
+}
@@ -1,6 +1,9 @@
 //! Types for storing input state
 
-use common::cgmath;
+use common::cgmath::{self, InnerSpace, Zero};
+use common::config::{Action, Input};
+
+use std::collections::{HashMap, HashSet};
 
 bitflags! {
     /// A type that stores movement key input state
@@ -67,6 +70,121 @@ impl From<Direction> for InputState {
     }
 }
 
+/// The actions in `ActionMode::Toggle` that are currently active
+#[derive(Debug, Default)]
+pub struct ToggledActions(HashSet<Action>);
+
+impl ToggledActions {
+    /// Activates the provided action if it is inactive and deactivates it otherwise, and returns
+    /// whether it is now active
+    pub fn toggle(&mut self, action: Action) -> bool {
+        if self.0.remove(&action) {
+            false
+        } else {
+            self.0.insert(action);
+            true
+        }
+    }
+
+    /// Deactivates all actions
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// The inputs that are currently holding down actions in `ActionMode::Hold`
+///
+/// An action can be bound to two inputs, so it stays active until neither of them is held.
+#[derive(Debug, Default)]
+pub struct HeldActions(HashMap<Action, Vec<Input>>);
+
+impl HeldActions {
+    /// Marks the provided input as holding down the action
+    pub fn press(&mut self, action: Action, input: &Input) {
+        let inputs = self.0.entry(action).or_insert_with(Vec::new);
+
+        if !inputs.contains(input) {
+            inputs.push(input.clone());
+        }
+    }
+
+    /// Marks the provided input as no longer holding down the action, and returns whether the
+    /// action is still held down by another input
+    pub fn release(&mut self, action: Action, input: &Input) -> bool {
+        match self.0.get_mut(&action) {
+            Some(inputs) => {
+                inputs.retain(|i| i != input);
+                !inputs.is_empty()
+            }
+            None => false,
+        }
+    }
+
+    /// Releases all actions
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// Whether the window has input focus
+///
+/// Raw mouse motion and gamepad input are received even while another window is focused, so they
+/// are ignored unless this is `true`.
+#[derive(Clone, Copy, Debug)]
+pub struct WindowFocus(pub bool);
+
+impl Default for WindowFocus {
+    fn default() -> Self {
+        WindowFocus(true)
+    }
+}
+
+/// Raw mouse motion that hasn't been turned into camera rotation yet
+#[derive(Debug)]
+pub struct MouseMotion {
+    /// The motion received since the last frame
+    pending: cgmath::Vector2<::Float>,
+    /// The smoothed motion of the last frame
+    smoothed: cgmath::Vector2<::Float>,
+}
+
+impl Default for MouseMotion {
+    fn default() -> Self {
+        Self {
+            pending: cgmath::Vector2::zero(),
+            smoothed: cgmath::Vector2::zero(),
+        }
+    }
+}
+
+impl MouseMotion {
+    /// Adds raw mouse motion, in device units with right and down being positive
+    pub fn add(&mut self, delta: (f64, f64)) {
+        self.pending += cgmath::Vector2::new(delta.0 as ::Float, delta.1 as ::Float);
+    }
+
+    /// Returns the motion to apply this frame and starts collecting the motion of the next frame
+    ///
+    /// `smoothing` is the fraction of the motion that is carried over to the next frame. The motion
+    /// that is carried over is eventually applied, so smoothing doesn't change the total rotation.
+    pub fn take_smoothed(&mut self, smoothing: ::Float) -> cgmath::Vector2<::Float> {
+        self.smoothed = self.smoothed * smoothing + self.pending * (1.0 - smoothing);
+        self.pending = cgmath::Vector2::zero();
+
+        // Stop carrying motion over once it is too small to notice
+        if self.smoothed.magnitude2() < 1e-6 {
+            self.smoothed = cgmath::Vector2::zero();
+        }
+
+        self.smoothed
+    }
+
+    /// Discards all motion, such as when the game is paused
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
 /// A movement direction
 #[derive(Clone, Copy, Debug)]
 #[repr(u8)]
@@ -76,3 +194,27 @@ pub enum Direction {
     Backward = InputState::BACKWARD.bits,
     Left = InputState::LEFT.bits,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mouse_smoothing() {
+        let mut motion = MouseMotion::default();
+
+        motion.add((4.0, -2.0));
+        motion.add((4.0, 0.0));
+        assert_eq!(motion.take_smoothed(0.0), cgmath::Vector2::new(8.0, -2.0));
+        assert_eq!(motion.take_smoothed(0.0), cgmath::Vector2::zero());
+
+        motion.add((8.0, 0.0));
+        assert_eq!(motion.take_smoothed(0.75), cgmath::Vector2::new(2.0, 0.0));
+        assert_eq!(motion.take_smoothed(0.75), cgmath::Vector2::new(1.5, 0.0));
+
+        // All of the motion is applied eventually
+        let total = (0..100).fold(3.5, |total, _| total + motion.take_smoothed(0.75).x);
+        assert!((total - 8.0).abs() < 0.01);
+        assert_eq!(motion.take_smoothed(0.75), cgmath::Vector2::zero());
+    }
+}
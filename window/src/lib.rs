@@ -8,13 +8,15 @@ use common::specs;
 extern crate bitflags;
 #[macro_use]
 extern crate slog;
+extern crate gilrs;
 
 pub mod config;
+pub mod gamepad;
 pub mod info;
 pub mod input;
 pub mod window_event;
 
-use common::{shred, glutin, Float};
+use common::{console, shred, glutin, Float};
 use common::takeable_option::Takeable;
 
 use std::ops::Deref;
@@ -113,6 +115,10 @@ pub fn initialize<'a, 'b>(
 ) -> specs::DispatcherBuilder<'a, 'b> {
     world.add_resource(info::WindowInfo::default());
     world.add_resource(window_event::EventChannel::new());
+    world.add_resource(input::ToggledActions::default());
+    world.add_resource(input::HeldActions::default());
+    world.add_resource(input::MouseMotion::default());
+    world.add_resource(input::WindowFocus::default());
 
     let mut event_channel = world.write_resource::<window_event::EventChannel>();
     let reader_id = event_channel.register_reader();
@@ -124,3 +130,32 @@ pub fn initialize<'a, 'b>(
         .with(info::System, "window-info", &[])
         .with(config_system, "window-config", &[])
 }
+
+/// Registers console commands for the systems in this crate
+pub fn register_commands(commands: &mut console::CommandRegistry) {
+    commands.register(
+        "set",
+        "set <field> <value>",
+        "Sets a configuration field (for example, `set graphics.shadows true`)",
+        Box::new(|world: &mut specs::World, args: &[&str]| {
+            if args.len() < 2 {
+                return Err("Expected a field and a value".to_string());
+            }
+
+            let (path, value) = (args[0], args[1..].join(" "));
+
+            // The field is set on a copy, so invalid values are rejected before any system sees
+            // them, like when the settings file is loaded
+            let mut config = world.read_resource::<common::config::Config>().clone();
+            let section = config.set_field(path, &value).map_err(|e| e.to_string())?;
+            config.validate().map_err(|e| e.to_string())?;
+            *world.write_resource::<common::config::Config>() = config;
+
+            world
+                .write_resource::<window_event::EventChannel>()
+                .single_write(window_event::Event::ConfigChanged(section.into()));
+
+            Ok(Some(format!("Set `{}` to `{}`", path, value)))
+        }),
+    );
+}
@@ -4,10 +4,11 @@
 
 use common::cgmath::{self, Rad};
 use common::glutin::{self, ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
-use common::{self, config, shrev, UiState};
+use common::config::{self, ActionMode};
+use common::{self, shrev, UiState};
 use slog;
 
-use input::Direction;
+use input::{Direction, HeldActions, MouseMotion, ToggledActions, WindowFocus};
 
 /// A type alias for an event channel that uses `Event`
 pub type EventChannel = shrev::EventChannel<Event>;
@@ -60,6 +61,26 @@ pub enum ChangedConfig {
     Camera,
     /// The key binding configuration has changed
     Bindings,
+    /// The gamepad configuration has changed
+    Gamepad,
+    /// The asset configuration has changed (which only takes effect after a restart)
+    Assets,
+    /// The interface configuration (such as the language) has changed
+    Interface,
+}
+
+impl From<config::ConfigSection> for ChangedConfig {
+    fn from(section: config::ConfigSection) -> Self {
+        match section {
+            config::ConfigSection::Graphics => ChangedConfig::Graphics,
+            config::ConfigSection::Window => ChangedConfig::Window,
+            config::ConfigSection::Camera => ChangedConfig::Camera,
+            config::ConfigSection::Bindings => ChangedConfig::Bindings,
+            config::ConfigSection::Gamepad => ChangedConfig::Gamepad,
+            config::ConfigSection::Assets => ChangedConfig::Assets,
+            config::ConfigSection::Interface => ChangedConfig::Interface,
+        }
+    }
 }
 
 /// The event type that is sent through the event channel
@@ -67,12 +88,33 @@ pub enum ChangedConfig {
 pub enum Event {
     /// A movement key was pressed or released, and the movement key state should be updated
     ChangeMovementKeyState(Direction, State),
+    /// The analog movement input (such as a gamepad stick) has changed
+    ///
+    /// The x axis points right and the y axis points forward. The length of the vector is the
+    /// fraction of the maximum speed to move at.
+    AnalogMovement(cgmath::Vector2<::Float>),
     /// The player jumped
     Jump,
+    /// The player released the jump input, which makes the jump lower if the player is still
+    /// rising
+    JumpReleased,
+    /// The player started or stopped sprinting
+    Sprint(State),
+    /// The player started or stopped crouching
+    Crouch(State),
+    /// The player dashed
+    Dash,
+    /// The player started or stopped flying up while noclipping
+    Ascend(State),
+    /// The player started or stopped flying down while noclipping
+    Descend(State),
     /// The camera should be rotated
     RotateCamera(CameraRotation),
     /// The shaders should be reloaded
     ReloadShaders,
+    /// All held input should be released, such as when the window loses focus and the release
+    /// events won't arrive
+    ResetInput,
     /// The game was unpaused
     Unpaused,
     /// The window was resized
@@ -87,38 +129,11 @@ pub enum Event {
 pub fn process_window_event(
     config: &config::Config,
     channel: &mut EventChannel,
-    window: &glutin::Window,
+    toggled: &mut ToggledActions,
+    held: &mut HeldActions,
     event: &WindowEvent,
 ) {
     match *event {
-        WindowEvent::CursorMoved {
-            device_id: _,
-            modifiers: _,
-            position,
-        } => {
-            common::utils::set_cursor_pos_to_window_center(window);
-
-            let center = match common::utils::get_window_center(window) {
-                Some(c) => c,
-                None => return,
-            };
-
-            let diff_pitch = position.y - center.y;
-            let diff_yaw = position.x - center.x;
-
-            // Yaw control is inverted, so invert it again to fix it
-            let diff_yaw = -diff_yaw;
-            // Pitch control is also inverted
-            let diff_pitch = -diff_pitch;
-
-            // TODO: Investigate how sensitivity scales with monitor size, and maybe fix the
-            //       handling of it
-            let rot_pitch = diff_pitch as ::Float * config.camera.sensitivity;
-            let rot_yaw = diff_yaw as ::Float * config.camera.sensitivity;
-            let camera_rot = CameraRotation::new(cgmath::Rad(rot_pitch), cgmath::Rad(rot_yaw));
-
-            channel.single_write(Event::RotateCamera(camera_rot));
-        }
         WindowEvent::KeyboardInput {
             input:
                 KeyboardInput {
@@ -129,8 +144,6 @@ pub fn process_window_event(
                 },
             ..
         } => {
-            let mut event = None;
-
             let virtual_keycode = match virtual_keycode {
                 Some(c) => c,
                 // Do nothing if there is no virtual keycode (such as when Ctrl+Shift+Alt is
@@ -139,104 +152,205 @@ pub fn process_window_event(
             };
             let key: config::Key = virtual_keycode.into();
 
-            let current_bind = config::Bind::new(key.clone(), modifiers.into());
+            let current_bind = config::Bind::new(key, modifiers.into());
 
-            // Handle movement keys
-            event = event.or_else(|| {
-                get_movement_event(
-                    &current_bind,
-                    &config.bindings.move_forward,
-                    state,
-                    Direction::Forward,
-                )
-            });
-
-            event = event.or_else(|| {
-                get_movement_event(
-                    &current_bind,
-                    &config.bindings.move_backward,
-                    state,
-                    Direction::Backward,
-                )
-            });
-
-            event = event.or_else(|| {
-                get_movement_event(
-                    &current_bind,
-                    &config.bindings.move_left,
-                    state,
-                    Direction::Left,
-                )
-            });
-
-            event = event.or_else(|| {
-                get_movement_event(
-                    &current_bind,
-                    &config.bindings.move_right,
-                    state,
-                    Direction::Right,
-                )
-            });
-
-            match state {
-                ElementState::Pressed => {
-                    if current_bind == config.bindings.reload_shaders {
-                        event = Some(Event::ReloadShaders);
-                    } else if current_bind == config.bindings.jump {
-                        event = Some(Event::Jump);
-                    }
-                }
-                ElementState::Released => {}
-            }
+            process_bind(config, channel, toggled, held, &current_bind, state);
+        }
+        WindowEvent::MouseInput {
+            state,
+            button,
+            modifiers,
+            ..
+        } => {
+            let input = config::Input::Mouse(button.into());
+
+            let current_bind = config::Bind::new(input, modifiers.into());
+
+            process_bind(config, channel, toggled, held, &current_bind, state);
+        }
+        WindowEvent::MouseWheel {
+            delta, modifiers, ..
+        } => {
+            if let Some(input) = wheel_input(delta) {
+                let current_bind = config::Bind::new(input, modifiers.into());
 
-            if let Some(e) = event {
-                channel.single_write(e);
+                // Scrolling has no release, so it is a press that is released immediately
+                process_bind(config, channel, toggled, held, &current_bind, ElementState::Pressed);
+                process_bind(config, channel, toggled, held, &current_bind, ElementState::Released);
             }
         }
         _ => {}
     }
 }
 
-/// Returns an event that enables or disables the provided movement direction, based on the provided
-/// binding comparison and element state
-fn get_movement_event(
-    // The current binding based on the latest window event
+/// Sends the camera rotation caused by the mouse motion received since the last call
+pub fn process_mouse_motion(
+    config: &config::Config,
+    channel: &mut EventChannel,
+    motion: &mut MouseMotion,
+) {
+    let motion = motion.take_smoothed(config.camera.smoothing);
+
+    if motion.x == 0.0 && motion.y == 0.0 {
+        return;
+    }
+
+    // Moving the mouse right turns right (negative yaw), and moving it up (negative y) looks up
+    let pitch = if config.camera.invert_y { motion.y } else { -motion.y };
+    let yaw = -motion.x;
+
+    let rotation = CameraRotation::new(
+        Rad(pitch * config.camera.sensitivity),
+        Rad(yaw * config.camera.sensitivity),
+    );
+
+    channel.single_write(Event::RotateCamera(rotation));
+}
+
+/// Returns the input of a mouse wheel event, or `None` if the wheel wasn't scrolled vertically
+pub fn wheel_input(delta: glutin::MouseScrollDelta) -> Option<config::Input> {
+    let y = match delta {
+        glutin::MouseScrollDelta::LineDelta(_, y) => f64::from(y),
+        glutin::MouseScrollDelta::PixelDelta(position) => position.y,
+    };
+
+    if y > 0.0 {
+        Some(config::Input::WheelUp)
+    } else if y < 0.0 {
+        Some(config::Input::WheelDown)
+    } else {
+        None
+    }
+}
+
+/// Sends the events of the actions bound to the provided `Bind`, which was pressed or released
+pub fn process_bind(
+    config: &config::Config,
+    channel: &mut EventChannel,
+    toggled: &mut ToggledActions,
+    held: &mut HeldActions,
     current_bind: &config::Bind,
-    // The binding to compare to
-    test_bind: &config::Bind,
     state: ElementState,
-    direction: Direction,
-) -> Option<Event> {
-    match state {
-        ElementState::Pressed => {
-            if current_bind.key == test_bind.key {
-                // While holding a movement key down, if the modifiers no longer match, disable
-                // that movement direction
-                let state = if current_bind.modifiers == test_bind.modifiers {
+) {
+    for info in config::ACTIONS {
+        let binds = config.bindings.get(info.action);
+
+        if binds.with_input(&current_bind.input).is_none() {
+            continue;
+        }
+
+        let modifiers_match = binds.slot_of(current_bind).is_some();
+
+        let action_state = match (info.mode, state) {
+            (ActionMode::Press, ElementState::Pressed) if modifiers_match => State::Enabled,
+            (ActionMode::Hold, ElementState::Pressed) if modifiers_match => {
+                held.press(info.action, &current_bind.input);
+                State::Enabled
+            }
+            // While holding a bind down, if the modifiers no longer match, or if a bind is released
+            // regardless of modifier state, deactivate the action unless its other bind is held
+            (ActionMode::Hold, _) => {
+                if held.release(info.action, &current_bind.input) {
+                    continue;
+                }
+
+                State::Disabled
+            }
+            (ActionMode::Toggle, ElementState::Pressed) if modifiers_match => {
+                if toggled.toggle(info.action) {
                     State::Enabled
                 } else {
                     State::Disabled
-                };
-                Some(Event::ChangeMovementKeyState(direction, state))
-            } else {
-                None
+                }
             }
+            _ => continue,
+        };
+
+        if let Some(e) = action_event(info.action, action_state) {
+            channel.single_write(e);
         }
-        ElementState::Released => {
-            // If a movement key is released, disable that movement direction regardless of modifier
-            // state
-            if current_bind.key == test_bind.key {
-                Some(Event::ChangeMovementKeyState(direction, State::Disabled))
-            } else {
-                None
-            }
+    }
+}
+
+/// Returns the event to send when the provided action is activated or deactivated, if any
+fn action_event(action: config::Action, state: State) -> Option<Event> {
+    use common::config::Action;
+
+    let direction = match action {
+        Action::MoveForward => Direction::Forward,
+        Action::MoveBackward => Direction::Backward,
+        Action::MoveLeft => Direction::Left,
+        Action::MoveRight => Direction::Right,
+        Action::Jump => {
+            return match state {
+                State::Enabled => Some(Event::Jump),
+                State::Disabled => Some(Event::JumpReleased),
+            };
         }
+        Action::Sprint => return Some(Event::Sprint(state)),
+        Action::Crouch => return Some(Event::Crouch(state)),
+        Action::Dash => return enabled(state, Event::Dash),
+        Action::Ascend => return Some(Event::Ascend(state)),
+        Action::Descend => return Some(Event::Descend(state)),
+        Action::ReloadShaders => return enabled(state, Event::ReloadShaders),
+        // The console is toggled by `process_window_event_graphics`, because it can be closed
+        // while the game isn't running
+        Action::ToggleConsole => return None,
+    };
+
+    Some(Event::ChangeMovementKeyState(direction, state))
+}
+
+/// Returns the provided event if the state is `Enabled`
+fn enabled(state: State, event: Event) -> Option<Event> {
+    match state {
+        State::Enabled => Some(event),
+        State::Disabled => None,
+    }
+}
+
+/// Handles the window gaining or losing focus
+///
+/// The focus is stored in `focus`. When focus is lost, all input is reset and the cursor is
+/// released. The game is also paused, unless it is configured to keep running in the background.
+pub fn process_focus_change(
+    config: &config::Config,
+    channel: &mut EventChannel,
+    toggled: &mut ToggledActions,
+    held: &mut HeldActions,
+    mouse_motion: &mut MouseMotion,
+    focus: &mut WindowFocus,
+    window: &glutin::Window,
+    ui_state: &mut UiState,
+    focused: bool,
+) {
+    focus.0 = focused;
+
+    if !ui_state.is_in_game() {
+        return;
+    }
+
+    if focused {
+        common::utils::grab_cursor(window, true);
+        return;
+    }
+
+    toggled.clear();
+    held.clear();
+    mouse_motion.reset();
+    channel.single_write(Event::ResetInput);
+
+    common::utils::grab_cursor(window, false);
+
+    if config.window.pause_on_focus_loss {
+        *ui_state = UiState::PauseMenu;
     }
 }
 
 /// Like `process_window_event`, but deals solely with graphics-related events and is run even while
 /// not in game
 pub fn process_window_event_graphics(
+    config: &config::Config,
     channel: &mut EventChannel,
     window: &glutin::Window,
     event: &WindowEvent,
@@ -249,21 +363,31 @@ pub fn process_window_event_graphics(
                 KeyboardInput {
                     state,
                     virtual_keycode,
-                    modifiers: _,
+                    modifiers,
                     ..
                 },
             ..
         } => {
             if let Some(key) = virtual_keycode {
+                let current_bind = config::Bind::new(config::Key::from(key), modifiers.into());
+
+                if config.bindings.toggle_console.slot_of(&current_bind).is_some() {
+                    if let ElementState::Pressed = state {
+                        toggle_console(ui_state, window, channel);
+                    }
+
+                    return;
+                }
+
                 match key {
                     VirtualKeyCode::Escape => {
                         if let ElementState::Pressed = state {
                             match *ui_state {
                                 UiState::InGame => {
-                                    window.hide_cursor(false);
+                                    common::utils::grab_cursor(window, false);
                                     *ui_state = UiState::PauseMenu;
                                 }
-                                UiState::PauseMenu => {
+                                UiState::PauseMenu | UiState::Console => {
                                     unpause(ui_state, window, channel);
                                 }
                                 _ => {}
@@ -274,12 +398,23 @@ pub fn process_window_event_graphics(
                 }
             }
         }
+        WindowEvent::MouseInput {
+            state: ElementState::Pressed,
+            button,
+            modifiers,
+            ..
+        } => {
+            let input = config::Input::Mouse(button.into());
+
+            let current_bind = config::Bind::new(input, modifiers.into());
+
+            if config.bindings.toggle_console.slot_of(&current_bind).is_some() {
+                toggle_console(ui_state, window, channel);
+            }
+        }
         WindowEvent::Resized(new_size) => {
             channel.single_write(Event::WindowResized(new_size));
 
-            // Center the cursor so the camera doesn't jump when the window resizes
-            common::utils::set_cursor_pos_to_window_center(window);
-
             let physical_size = new_size.to_physical(window.get_hidpi_factor());
             info!(log, "Window resized"; o!("new_dimensions" => format!("{:?}", physical_size)));
         }
@@ -287,13 +422,67 @@ pub fn process_window_event_graphics(
     }
 }
 
+/// Opens the developer console if the game is running, or closes it if it is open
+pub fn toggle_console(ui_state: &mut UiState, window: &glutin::Window, channel: &mut EventChannel) {
+    match *ui_state {
+        UiState::InGame => {
+            common::utils::grab_cursor(window, false);
+            *ui_state = UiState::Console;
+        }
+        UiState::Console => {
+            unpause(ui_state, window, channel);
+        }
+        _ => {}
+    }
+}
+
 /// Unpauses the game
 pub fn unpause(ui_state: &mut UiState, window: &glutin::Window, event_channel: &mut EventChannel) {
-    // Center the cursor so the camera doesn't jump when the game unpauses
-    // FIXME: This only mitigates the issue but doesn't fix it entirely
-    common::utils::set_cursor_pos_to_window_center(window);
-
-    window.hide_cursor(true);
+    common::utils::grab_cursor(window, true);
     *ui_state = UiState::InGame;
     event_channel.single_write(Event::Unpaused);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Presses or releases the provided key, and returns the jump events that were sent
+    fn jump_events(
+        config: &config::Config,
+        held: &mut HeldActions,
+        key: &config::Key,
+        state: ElementState,
+    ) -> Vec<bool> {
+        let mut channel = EventChannel::new();
+        let mut reader = channel.register_reader();
+        let bind = config::Bind::new(key.clone(), Default::default());
+
+        process_bind(config, &mut channel, &mut ToggledActions::default(), held, &bind, state);
+
+        channel
+            .read(&mut reader)
+            .filter_map(|e| match *e {
+                Event::Jump => Some(true),
+                Event::JumpReleased => Some(false),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hold_with_both_binds() {
+        let (space, up) = (config::Key::Space, config::Key::Up);
+        let mut config = config::Config::default();
+        config.bindings.jump.primary = Some(config::Bind::new(space.clone(), Default::default()));
+        config.bindings.jump.secondary = Some(config::Bind::new(up.clone(), Default::default()));
+        let mut held = HeldActions::default();
+
+        assert_eq!(jump_events(&config, &mut held, &space, ElementState::Pressed), vec![true]);
+        assert_eq!(jump_events(&config, &mut held, &up, ElementState::Pressed), vec![true]);
+
+        // The action stays active while the other bind is still held
+        assert!(jump_events(&config, &mut held, &space, ElementState::Released).is_empty());
+        assert_eq!(jump_events(&config, &mut held, &up, ElementState::Released), vec![false]);
+    }
+}